@@ -0,0 +1,303 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::types;
+use crate::api::utils::{self, OpsmlPaths};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::Duration;
+
+const DETECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// File in the cache directory holding the api version detected for each server
+const API_VERSION_CACHE: &str = "api-versions.json";
+
+/// Seconds a detected api version is reused before the server is asked again
+const API_VERSION_TTL_SECS: u64 = 60 * 60;
+
+/// Version of the OpsML server api. Servers of different generations expose the same operations
+/// under different routes and payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiVersion {
+    /// OpsML 2.x servers
+    V2,
+    /// OpsML 3.x servers, which serve under `/opsml/api` and name repositories spaces
+    V3,
+}
+
+/// Api version used by `OpsmlPaths::as_str`. Set once at startup by `init`
+static CURRENT: RwLock<ApiVersion> = RwLock::new(ApiVersion::V2);
+
+impl ApiVersion {
+    /// Api version requests are currently sent with
+    pub fn current() -> ApiVersion {
+        *CURRENT.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_current(self) {
+        *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = self;
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVersion::V2 => "v2",
+            ApiVersion::V3 => "v3",
+        }
+    }
+
+    /// Route of a logical operation for this api version
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Operation to route
+    ///
+    pub fn route(&self, path: &OpsmlPaths) -> &'static str {
+        match self {
+            ApiVersion::V2 => match path {
+                OpsmlPaths::ListCard => "/opsml/cards/list",
+                OpsmlPaths::CreateCard => "/opsml/cards/create",
+                OpsmlPaths::MetadataDownload => "/opsml/models/metadata",
+                OpsmlPaths::DownloadPresigned => "/opsml/files/presigned",
+                OpsmlPaths::DownloadFile => "/opsml/files/download",
+                OpsmlPaths::UploadFile => "/opsml/files/upload",
+                OpsmlPaths::Metric => "/opsml/metrics",
+                OpsmlPaths::ListFile => "/opsml/files/list",
+                OpsmlPaths::Healthcheck => "/opsml/healthcheck",
+                OpsmlPaths::Version => "/opsml/version",
+            },
+            ApiVersion::V3 => match path {
+                OpsmlPaths::ListCard => "/opsml/api/card/list",
+                OpsmlPaths::CreateCard => "/opsml/api/card/create",
+                OpsmlPaths::MetadataDownload => "/opsml/api/card/metadata",
+                OpsmlPaths::DownloadPresigned => "/opsml/api/files/presigned",
+                OpsmlPaths::DownloadFile => "/opsml/api/files/download",
+                OpsmlPaths::UploadFile => "/opsml/api/files/upload",
+                OpsmlPaths::Metric => "/opsml/api/experiment/metrics",
+                OpsmlPaths::ListFile => "/opsml/api/files/list",
+                OpsmlPaths::Healthcheck => "/opsml/api/healthcheck",
+                OpsmlPaths::Version => "/opsml/api/version",
+            },
+        }
+    }
+
+    /// Full url of an operation on a server
+    ///
+    /// # Arguments
+    ///
+    /// * `tracking_uri` - Server uri without a trailing slash
+    /// * `path` - Operation to route
+    ///
+    pub fn url(&self, tracking_uri: &str, path: &OpsmlPaths) -> String {
+        format!("{}{}", tracking_uri, self.route(path))
+    }
+}
+
+/// Detects the api version of a server from the health check it answers. Newer versions are tried
+/// first since older servers do not serve the newer routes
+///
+/// # Arguments
+///
+/// * `client` - Http client
+/// * `tracking_uri` - Server uri without a trailing slash
+///
+/// # Returns
+/// * `Result<ApiVersion, String>` - Api version of the server
+///
+pub async fn detect(
+    client: &reqwest::Client,
+    tracking_uri: &str,
+) -> Result<ApiVersion, anyhow::Error> {
+    let mut errors = Vec::new();
+
+    for version in [ApiVersion::V3, ApiVersion::V2] {
+        let url = version.url(tracking_uri, &OpsmlPaths::Healthcheck);
+        match client.get(&url).timeout(DETECT_TIMEOUT).send().await {
+            Ok(response) if response.status().is_success() => return Ok(version),
+            Ok(response) => errors.push(format!("{} returned {}", url, response.status())),
+            Err(e) => errors.push(format!("{}: {}", url, e)),
+        }
+    }
+
+    Err(anyhow::Error::msg(format!(
+        "No supported health check answered: {}",
+        errors.join("; ")
+    )))
+}
+
+/// Reads the api version detected for a server if it is recent enough to reuse
+///
+/// # Arguments
+///
+/// * `path` - Cache file
+/// * `tracking_uri` - Server uri without a trailing slash
+/// * `now` - Current epoch seconds
+///
+fn read_cached(path: &Path, tracking_uri: &str, now: u64) -> Option<ApiVersion> {
+    let cache: types::ApiVersionCache =
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    let detected = cache.servers.get(tracking_uri)?;
+
+    (now.saturating_sub(detected.detected_at) < API_VERSION_TTL_SECS)
+        .then_some(detected.api_version)
+}
+
+/// Records the api version detected for a server. A cache that cannot be written only costs
+/// another detection next time
+///
+/// # Arguments
+///
+/// * `path` - Cache file
+/// * `tracking_uri` - Server uri without a trailing slash
+/// * `api_version` - Detected api version
+/// * `now` - Current epoch seconds
+///
+fn write_cached(path: &Path, tracking_uri: &str, api_version: ApiVersion, now: u64) {
+    let mut cache: types::ApiVersionCache = fs::read_to_string(path)
+        .ok()
+        .and_then(|cache| serde_json::from_str(&cache).ok())
+        .unwrap_or_default();
+    cache.servers.insert(
+        tracking_uri.to_string(),
+        types::DetectedApiVersion {
+            api_version,
+            detected_at: now,
+        },
+    );
+
+    if let (Some(dir), Ok(cache)) = (path.parent(), serde_json::to_string_pretty(&cache)) {
+        if fs::create_dir_all(dir).is_ok() {
+            let _ = fs::write(path, cache);
+        }
+    }
+}
+
+/// Gets the api version of a server, reusing a recent detection from the cache directory
+///
+/// # Arguments
+///
+/// * `cache_path` - Cache file
+/// * `tracking_uri` - Server uri without a trailing slash
+///
+async fn detect_cached(cache_path: &Path, tracking_uri: &str) -> Result<ApiVersion, anyhow::Error> {
+    let now = utils::now_secs();
+    if let Some(api_version) = read_cached(cache_path, tracking_uri, now) {
+        return Ok(api_version);
+    }
+
+    let api_version = detect(&reqwest::Client::new(), tracking_uri).await?;
+    write_cached(cache_path, tracking_uri, api_version, now);
+    Ok(api_version)
+}
+
+/// Sets the api version used for requests. A requested version is used as is, otherwise it is
+/// negotiated with the server at `OPSML_TRACKING_URI` and cached for an hour. When negotiation
+/// fails the v2 api is kept so the command reports its own connection error
+///
+/// # Arguments
+///
+/// * `requested` - Api version given with `--api-version` or `OPSML_API_VERSION`
+///
+pub async fn init(requested: Option<ApiVersion>) -> ApiVersion {
+    let version = match requested {
+        Some(version) => version,
+        None => match utils::tracking_uri_from_env() {
+            Ok(uri) => detect_cached(&utils::cache_dir().join(API_VERSION_CACHE), &uri)
+                .await
+                .unwrap_or(ApiVersion::V2),
+            Err(_) => ApiVersion::V2,
+        },
+    };
+
+    version.set_current();
+    version
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        assert_eq!(
+            ApiVersion::V2.url("http://opsml", &OpsmlPaths::ListCard),
+            "http://opsml/opsml/cards/list"
+        );
+        assert_eq!(
+            ApiVersion::V3.url("http://opsml", &OpsmlPaths::ListCard),
+            "http://opsml/opsml/api/card/list"
+        );
+        assert_eq!(
+            ApiVersion::V3.route(&OpsmlPaths::Metric),
+            "/opsml/api/experiment/metrics"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_detect() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        let client = reqwest::Client::new();
+
+        // a v2 server only answers the v2 health check
+        let mock_v2 = server
+            .mock("GET", "/opsml/healthcheck")
+            .with_status(200)
+            .create();
+        assert_eq!(detect(&client, &url).await.unwrap(), ApiVersion::V2);
+        mock_v2.assert();
+
+        let mock_v3 = server
+            .mock("GET", "/opsml/api/healthcheck")
+            .with_status(200)
+            .create();
+        assert_eq!(detect(&client, &url).await.unwrap(), ApiVersion::V3);
+        mock_v3.assert();
+
+        mock_v2.remove();
+        mock_v3.remove();
+        let err = detect(&client, &url).await.unwrap_err();
+        assert!(err.to_string().contains("/opsml/healthcheck"));
+
+        // detections are reused per server until they expire
+        let mock_v3 = server
+            .mock("GET", "/opsml/api/healthcheck")
+            .with_status(200)
+            .expect(1)
+            .create();
+        let cache_path = std::env::temp_dir()
+            .join(format!("opsml-api-versions-{}", uuid::Uuid::new_v4()))
+            .join(API_VERSION_CACHE);
+        assert_eq!(
+            detect_cached(&cache_path, &url).await.unwrap(),
+            ApiVersion::V3
+        );
+        assert_eq!(
+            detect_cached(&cache_path, &url).await.unwrap(),
+            ApiVersion::V3
+        );
+        mock_v3.assert();
+
+        let detected_at = utils::now_secs();
+        assert!(read_cached(&cache_path, "http://other", detected_at).is_none());
+        assert!(read_cached(&cache_path, &url, detected_at + API_VERSION_TTL_SECS).is_none());
+        write_cached(&cache_path, "http://other", ApiVersion::V2, detected_at);
+        assert_eq!(
+            read_cached(&cache_path, "http://other", detected_at),
+            Some(ApiVersion::V2)
+        );
+        assert_eq!(
+            read_cached(&cache_path, &url, detected_at),
+            Some(ApiVersion::V3)
+        );
+        fs::remove_dir_all(cache_path.parent().unwrap()).unwrap();
+
+        // an explicit version skips negotiation
+        assert_eq!(init(Some(ApiVersion::V2)).await, ApiVersion::V2);
+        assert_eq!(ApiVersion::current(), ApiVersion::V2);
+    }
+}
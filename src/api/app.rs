@@ -0,0 +1,270 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::api_version;
+use crate::api::utils;
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use std::process::ExitStatus;
+use std::time::Duration;
+use tokio::process::{Child, Command};
+
+/// Python entrypoint installed with the opsml server
+const DEFAULT_SERVER_COMMAND: &str = "opsml-uvicorn-server";
+
+/// How long the server is given to exit after SIGTERM before it is killed
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+pub struct AppLauncher<'a> {
+    pub port: &'a i32,
+    pub login: &'a bool,
+    pub host: &'a str,
+    pub command: Option<&'a str>,
+    pub startup_timeout: Duration,
+}
+
+impl AppLauncher<'_> {
+    /// Tracking uri of the launched server
+    fn tracking_uri(&self) -> String {
+        format!("http://{}:{}", self.host, self.port)
+    }
+
+    /// Builds the server command. A custom command is run through `sh -c` with `{port}` replaced,
+    /// otherwise the opsml entrypoint is started directly. The server gets its own process group
+    /// so shutdown reaches any processes it starts
+    fn server_command(&self) -> Command {
+        let mut command = match self.command {
+            Some(template) => {
+                let mut command = Command::new("sh");
+                command
+                    .arg("-c")
+                    .arg(template.replace("{port}", &self.port.to_string()));
+                command
+            }
+            None => {
+                let mut command = Command::new(DEFAULT_SERVER_COMMAND);
+                command.arg("--port").arg(self.port.to_string());
+                if *self.login {
+                    command.arg("--login");
+                }
+                command
+            }
+        };
+
+        #[cfg(unix)]
+        command.process_group(0);
+
+        command
+    }
+
+    /// Polls the health check of each api version until the server answers, the server exits or
+    /// the timeout passes
+    ///
+    /// # Arguments
+    ///
+    /// * `server` - Running server process
+    ///
+    async fn wait_until_healthy(&self, server: &mut Child) -> Result<(), anyhow::Error> {
+        let tracking_uri = self.tracking_uri();
+        let client = reqwest::Client::new();
+        let started = tokio::time::Instant::now();
+
+        loop {
+            if let Some(status) = server.try_wait()? {
+                return Err(anyhow::Error::msg(format!(
+                    "Server exited with {} before becoming healthy",
+                    status
+                )));
+            }
+
+            if api_version::detect(&client, &tracking_uri).await.is_ok() {
+                return Ok(());
+            }
+
+            if started.elapsed() > self.startup_timeout {
+                return Err(anyhow::Error::msg(format!(
+                    "Server did not pass the health check at {} within {:?}",
+                    tracking_uri, self.startup_timeout
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Runs a command against the launched server with `OPSML_TRACKING_URI` set
+    ///
+    /// # Arguments
+    ///
+    /// * `run` - Program and arguments
+    ///
+    async fn run_child(&self, run: &[String]) -> Result<ExitStatus, anyhow::Error> {
+        let (program, args) = run
+            .split_first()
+            .with_context(|| "No command given to run")?;
+
+        Command::new(program)
+            .args(args)
+            .env("OPSML_TRACKING_URI", self.tracking_uri())
+            .status()
+            .await
+            .with_context(|| format!("Failed to run {}", program))
+    }
+
+    /// Starts the server, waits for it to become healthy and supervises it until Ctrl-C. When a
+    /// command is given it is run against the server, which is shut down once the command exits
+    ///
+    /// # Arguments
+    ///
+    /// * `run` - Optional command to run against the server
+    ///
+    pub async fn launch(&self, run: &[String]) -> Result<(), anyhow::Error> {
+        let mut server = self
+            .server_command()
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| {
+                format!(
+                    "Failed to start the opsml server. Is {} installed? Use --command to run a different server",
+                    self.command.unwrap_or(DEFAULT_SERVER_COMMAND)
+                )
+            })?;
+
+        if let Err(e) = self.wait_until_healthy(&mut server).await {
+            shutdown(&mut server).await?;
+            return Err(e);
+        }
+
+        let tracking_uri = self.tracking_uri();
+        println!("OpsML server running at {}", tracking_uri.green());
+
+        let result = if run.is_empty() {
+            println!("export OPSML_TRACKING_URI={}", tracking_uri);
+            println!("Press Ctrl-C to stop the server");
+
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => Ok(()),
+                status = server.wait() => Err(anyhow::Error::msg(format!("Server exited with {}", status?))),
+            }
+        } else {
+            tokio::select! {
+                status = self.run_child(run) => match status? {
+                    status if status.success() => Ok(()),
+                    status => Err(anyhow::Error::msg(format!("{} exited with {}", run[0], status))),
+                },
+                _ = tokio::signal::ctrl_c() => Ok(()),
+                status = server.wait() => Err(anyhow::Error::msg(format!("Server exited with {}", status?))),
+            }
+        };
+
+        println!("Stopping OpsML server");
+        shutdown(&mut server).await?;
+
+        result
+    }
+}
+
+/// Sends a signal to the server's process group
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: libc::c_int) {
+    // SAFETY: kill only sends a signal to the process group of the server we spawned
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), signal);
+    }
+}
+
+/// Asks the server to stop with SIGTERM and kills it if it has not exited after a grace period
+///
+/// # Arguments
+///
+/// * `server` - Server process
+///
+async fn shutdown(server: &mut Child) -> Result<(), anyhow::Error> {
+    #[cfg(unix)]
+    if let Some(pid) = server.id() {
+        signal_group(pid, libc::SIGTERM);
+        if tokio::time::timeout(SHUTDOWN_GRACE, server.wait())
+            .await
+            .is_err()
+        {
+            signal_group(pid, libc::SIGKILL);
+        }
+    }
+
+    if server.try_wait()?.is_some() {
+        return Ok(());
+    }
+    server.kill().await.with_context(|| "Failed to stop server")
+}
+
+/// Launches a local opsml server
+///
+/// # Arguments
+///
+/// * `port` - Port to serve on
+/// * `login` - Whether the server requires login
+/// * `host` - Host the server is reachable on
+/// * `command` - Optional server command template. `{port}` is replaced with the port
+/// * `startup_timeout` - How long to wait for the health check, e.g. `60s`
+/// * `run` - Optional command to run against the server
+///
+pub async fn launch_app(
+    port: &i32,
+    login: &bool,
+    host: &str,
+    command: Option<&str>,
+    startup_timeout: &str,
+    run: &[String],
+) -> Result<(), anyhow::Error> {
+    let launcher = AppLauncher {
+        port,
+        login,
+        host,
+        command,
+        startup_timeout: utils::parse_interval(startup_timeout)?,
+    };
+
+    launcher.launch(run).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_launch_app() {
+        let mut server = mockito::Server::new_async().await;
+        let port: i32 = server.url().rsplit(':').next().unwrap().parse().unwrap();
+
+        // mockito stands in for the server's health check while `sleep` stands in for its process
+        let mock = server
+            .mock("GET", "/opsml/healthcheck")
+            .with_status(200)
+            .expect(2)
+            .create();
+
+        let launcher = AppLauncher {
+            port: &port,
+            login: &false,
+            host: "127.0.0.1",
+            command: Some("sleep 30 # {port}"),
+            startup_timeout: Duration::from_secs(5),
+        };
+
+        let expected = format!("test \"$OPSML_TRACKING_URI\" = http://127.0.0.1:{}", port);
+        let run = ["sh".to_string(), "-c".to_string(), expected];
+        launcher.launch(&run).await.unwrap();
+
+        let failing = ["false".to_string()];
+        assert!(launcher.launch(&failing).await.is_err());
+        mock.assert();
+        mock.remove();
+
+        // a server that exits early fails fast instead of waiting for the timeout
+        let exiting = AppLauncher {
+            command: Some("exit 3"),
+            ..launcher
+        };
+        let err = exiting.launch(&[]).await.unwrap_err();
+        assert!(err.to_string().contains("before becoming healthy"));
+    }
+}
@@ -0,0 +1,367 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::metrics::{MetricGetter, MetricView};
+use crate::api::types;
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use tabled::settings::style::Style;
+use tabled::{settings::Alignment, Table};
+
+// longest operators first so `<=` is not read as `<`
+const OPERATORS: [&str; 6] = ["<=", ">=", "==", "!=", "<", ">"];
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum Comparison {
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    Ne,
+}
+
+impl Comparison {
+    fn from_str(operator: &str) -> Option<Comparison> {
+        match operator {
+            "<" => Some(Comparison::Lt),
+            "<=" => Some(Comparison::Le),
+            ">" => Some(Comparison::Gt),
+            ">=" => Some(Comparison::Ge),
+            "==" => Some(Comparison::Eq),
+            "!=" => Some(Comparison::Ne),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+        }
+    }
+
+    fn evaluate(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Lt => value < threshold,
+            Comparison::Le => value <= threshold,
+            Comparison::Gt => value > threshold,
+            Comparison::Ge => value >= threshold,
+            Comparison::Eq => value == threshold,
+            Comparison::Ne => value != threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MetricRule {
+    pub metric: String,
+    pub operator: Comparison,
+    pub threshold: f64,
+}
+
+impl fmt::Display for MetricRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.metric,
+            self.operator.as_str(),
+            self.threshold
+        )
+    }
+}
+
+impl MetricRule {
+    /// Parses a rule such as `mae<5` or `r2 >= 0.8`
+    ///
+    /// # Arguments
+    ///
+    /// * `rule` - Rule string
+    ///
+    pub fn parse(rule: &str) -> Result<MetricRule, anyhow::Error> {
+        let invalid = || {
+            format!(
+                "Invalid rule {}. Expected <metric><operator><threshold>, e.g. mae<5",
+                rule.red()
+            )
+        };
+
+        let (index, operator) = OPERATORS
+            .iter()
+            .filter_map(|op| rule.find(op).map(|index| (index, *op)))
+            .min_by_key(|(index, op)| (*index, std::cmp::Reverse(op.len())))
+            .with_context(invalid)?;
+
+        let metric = rule[..index].trim();
+        let threshold = rule[index + operator.len()..].trim();
+
+        if metric.is_empty() {
+            return Err(anyhow::Error::msg(invalid()));
+        }
+
+        Ok(MetricRule {
+            metric: metric.to_string(),
+            operator: Comparison::from_str(operator).with_context(invalid)?,
+            threshold: threshold.parse::<f64>().with_context(invalid)?,
+        })
+    }
+}
+
+/// A rule in a rules file is either a rule string or a structured rule
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RuleEntry {
+    Text(String),
+    Rule(MetricRule),
+}
+
+#[derive(Deserialize)]
+struct RulesFile {
+    rules: Vec<RuleEntry>,
+}
+
+/// Loads rules from a yaml file
+///
+/// ```yaml
+/// rules:
+///   - mae < 5
+///   - metric: r2
+///     operator: ">="
+///     threshold: 0.8
+/// ```
+///
+/// # Arguments
+///
+/// * `path` - Path to the rules file
+///
+pub fn load_rules_file(path: &Path) -> Result<Vec<MetricRule>, anyhow::Error> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Unable to read rules file {}", path.display()))?;
+    let rules_file: RulesFile = serde_yaml::from_str(&contents)
+        .with_context(|| format!("Failed to parse rules file {}", path.display()))?;
+
+    rules_file
+        .rules
+        .into_iter()
+        .map(|entry| match entry {
+            RuleEntry::Text(rule) => MetricRule::parse(&rule),
+            RuleEntry::Rule(rule) => Ok(rule),
+        })
+        .collect()
+}
+
+pub struct MetricAsserter {
+    pub rules: Vec<MetricRule>,
+}
+
+impl MetricAsserter {
+    /// Evaluates rules against metrics. The value at the last step is used for metrics
+    /// logged more than once. Missing or non-numeric metrics fail
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - Metrics sorted by step
+    ///
+    /// # Returns
+    /// * `Vec<types::MetricAssertionTable>` - Result row per rule
+    fn evaluate(&self, metrics: &[types::Metric]) -> Vec<types::MetricAssertionTable> {
+        self.rules
+            .iter()
+            .map(|rule| {
+                let value = metrics
+                    .iter()
+                    .rev()
+                    .find(|metric| metric.name == rule.metric)
+                    .map(|metric| metric.value.clone());
+
+                let passed = value
+                    .as_ref()
+                    .and_then(|value| value.as_f64())
+                    .is_some_and(|value| rule.operator.evaluate(value, rule.threshold));
+
+                types::MetricAssertionTable {
+                    metric: rule.metric.clone(),
+                    rule: rule.to_string(),
+                    value: value
+                        .map(|value| value.to_string())
+                        .unwrap_or("missing".to_string()),
+                    passed,
+                }
+            })
+            .collect()
+    }
+
+    /// Fetches metrics for a run and checks them against the rules
+    ///
+    /// # Arguments
+    ///
+    /// * `run_uid` - Run uid
+    ///
+    async fn assert_metrics(&self, run_uid: &str) -> Result<(), anyhow::Error> {
+        let metric_getter = MetricGetter {};
+        let metrics = metric_getter.fetch_metrics(run_uid).await?;
+        let metrics = metric_getter.filter_metrics(metrics, &MetricView::default())?;
+
+        let results = self.evaluate(&metrics);
+        let failed = results.iter().filter(|result| !result.passed).count();
+
+        println!("\nMetric Assertions for run {}", run_uid.bold());
+        println!(
+            "{}",
+            Table::new(&results)
+                .with(Alignment::center())
+                .with(Style::sharp())
+        );
+
+        if failed > 0 {
+            Err(anyhow::Error::msg(format!(
+                "{} of {} metric rule(s) failed",
+                failed,
+                results.len()
+            )))
+        } else {
+            println!("{}", "All metric rules passed".green());
+            Ok(())
+        }
+    }
+}
+
+/// Checks run metrics against threshold rules. Returns an error if any rule fails
+///
+/// # Arguments
+///
+/// * `name` - Name of the model
+/// * `repository` - Repository of the model
+/// * `version` - Version of the model
+/// * `uid` - Run uid
+/// * `rules` - Rule strings such as `mae<5`
+/// * `rules_file` - Yaml file with additional rules
+/// * `ignore_release_candidates` - Whether to ignore release candidates when resolving the version
+pub async fn assert_metrics(
+    name: Option<&str>,
+    repository: Option<&str>,
+    version: Option<&str>,
+    uid: Option<&str>,
+    rules: &[String],
+    rules_file: Option<&str>,
+    ignore_release_candidates: bool,
+) -> Result<(), anyhow::Error> {
+    let mut metric_rules = rules
+        .iter()
+        .map(|rule| MetricRule::parse(rule))
+        .collect::<Result<Vec<MetricRule>, anyhow::Error>>()?;
+
+    if let Some(rules_file) = rules_file {
+        metric_rules.extend(load_rules_file(Path::new(rules_file))?);
+    }
+
+    if metric_rules.is_empty() {
+        return Err(anyhow::Error::msg(
+            "Please provide at least one --rule or a --rules-file",
+        ));
+    }
+
+    let run_uid = MetricGetter {}
+        .resolve_run_uid(name, repository, version, uid, ignore_release_candidates)
+        .await?;
+
+    let asserter = MetricAsserter {
+        rules: metric_rules,
+    };
+    asserter.assert_metrics(&run_uid).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use tokio;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_parse_rules() {
+        let rule = MetricRule::parse("r2 >= 0.8").unwrap();
+        assert_eq!(rule.metric, "r2");
+        assert_eq!(rule.operator, Comparison::Ge);
+        assert_eq!(rule.threshold, 0.8);
+
+        assert_eq!(MetricRule::parse("mae<5").unwrap().operator, Comparison::Lt);
+        assert!(MetricRule::parse("mae").is_err());
+        assert!(MetricRule::parse("<5").is_err());
+        assert!(MetricRule::parse("mae<five").is_err());
+
+        let path = format!("{}.yaml", Uuid::new_v4());
+        fs::write(
+            &path,
+            "rules:\n  - mae < 5\n  - metric: r2\n    operator: \">=\"\n    threshold: 0.8\n",
+        )
+        .unwrap();
+        let rules = load_rules_file(Path::new(&path)).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1], rule);
+    }
+
+    #[tokio::test]
+    async fn test_assert_metrics() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url);
+        }
+
+        let metric_data = fs::read_to_string("./src/api/test_utils/list_metric.json").unwrap();
+        let mock_get_metrics = server
+            .mock("GET", "/opsml/metrics?run_uid=fake")
+            .with_status(200)
+            .with_body(metric_data)
+            .expect(2)
+            .create();
+
+        assert_metrics(
+            None,
+            None,
+            None,
+            Some("fake"),
+            &["mae<=5".to_string()],
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+        // mae is 5 and the r2 metric was never logged
+        let failed = assert_metrics(
+            None,
+            None,
+            None,
+            Some("fake"),
+            &["mae<5".to_string(), "r2>=0.8".to_string()],
+            None,
+            false,
+        )
+        .await;
+        assert_eq!(
+            failed.unwrap_err().to_string(),
+            "2 of 2 metric rule(s) failed"
+        );
+
+        mock_get_metrics.assert();
+    }
+}
@@ -0,0 +1,796 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::api_version::ApiVersion;
+use crate::api::cards::{self, REGISTRIES};
+use crate::api::download::download_card;
+use crate::api::metrics::MetricGetter;
+use crate::api::model::{download_model, fetch_model_metadata};
+use crate::api::route_helper::RouteHelper;
+use crate::api::types;
+use crate::api::utils;
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Text};
+use ratatui::widgets::{Block, List, ListState, Paragraph, Row, Table, TableState, Tabs};
+use ratatui::{DefaultTerminal, Frame};
+use std::io::BufRead;
+use std::path::Path;
+use std::time::Duration;
+
+// how long a blocking read waits for terminal input before the screen is redrawn
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Screen {
+    Registries,
+    Cards,
+    Detail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DetailTab {
+    Info,
+    Schema,
+    Files,
+    Metrics,
+}
+
+impl DetailTab {
+    const ALL: [DetailTab; 4] = [
+        DetailTab::Info,
+        DetailTab::Schema,
+        DetailTab::Files,
+        DetailTab::Metrics,
+    ];
+
+    fn title(&self) -> &'static str {
+        match self {
+            DetailTab::Info => "Info",
+            DetailTab::Schema => "Schema",
+            DetailTab::Files => "Files",
+            DetailTab::Metrics => "Metrics",
+        }
+    }
+
+    fn index(&self) -> usize {
+        DetailTab::ALL
+            .iter()
+            .position(|tab| tab == self)
+            .unwrap_or(0)
+    }
+
+    fn next(&self) -> DetailTab {
+        DetailTab::ALL[(self.index() + 1) % DetailTab::ALL.len()]
+    }
+
+    fn previous(&self) -> DetailTab {
+        DetailTab::ALL[(self.index() + DetailTab::ALL.len() - 1) % DetailTab::ALL.len()]
+    }
+}
+
+/// Work the event loop needs to perform after a key press
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    None,
+    LoadCards,
+    LoadDetail,
+    Download,
+    Quit,
+}
+
+/// Content of each detail tab for the selected card
+#[derive(Debug, Default)]
+struct CardDetail {
+    info: Vec<String>,
+    schema: Vec<String>,
+    files: Vec<String>,
+    metrics: Vec<String>,
+}
+
+impl CardDetail {
+    fn lines(&self, tab: DetailTab) -> &[String] {
+        match tab {
+            DetailTab::Info => &self.info,
+            DetailTab::Schema => &self.schema,
+            DetailTab::Files => &self.files,
+            DetailTab::Metrics => &self.metrics,
+        }
+    }
+}
+
+struct BrowserState {
+    screen: Screen,
+    registry_index: usize,
+    cards: Vec<types::Card>,
+    filter: String,
+    filtering: bool,
+    card_index: usize,
+    tab: DetailTab,
+    scroll: u16,
+    detail: CardDetail,
+    status: String,
+}
+
+impl BrowserState {
+    /// Creates the browser state. When a registry is given the browser opens on its card list
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - Optional registry to start in
+    ///
+    fn new(registry: Option<&str>) -> Result<Self, anyhow::Error> {
+        let (screen, registry_index) = match registry {
+            Some(registry) => {
+                let index = REGISTRIES
+                    .iter()
+                    .position(|r| *r == registry)
+                    .ok_or_else(|| {
+                        anyhow::Error::msg(format!(
+                            "Invalid registry: {}. Valid registries are: {}",
+                            registry,
+                            REGISTRIES.join(", ")
+                        ))
+                    })?;
+                (Screen::Cards, index)
+            }
+            None => (Screen::Registries, 0),
+        };
+
+        Ok(BrowserState {
+            screen,
+            registry_index,
+            cards: Vec::new(),
+            filter: String::new(),
+            filtering: false,
+            card_index: 0,
+            tab: DetailTab::Info,
+            scroll: 0,
+            detail: CardDetail::default(),
+            status: String::new(),
+        })
+    }
+
+    fn registry(&self) -> &'static str {
+        REGISTRIES[self.registry_index]
+    }
+
+    /// Cards matching the current filter. Matching is case-insensitive on name, repository,
+    /// version and uid
+    fn filtered_cards(&self) -> Vec<&types::Card> {
+        let filter = self.filter.to_lowercase();
+        self.cards
+            .iter()
+            .filter(|card| {
+                filter.is_empty()
+                    || [&card.name, &card.repository, &card.version, &card.uid]
+                        .iter()
+                        .any(|field| field.to_lowercase().contains(&filter))
+            })
+            .collect()
+    }
+
+    fn selected_card(&self) -> Option<&types::Card> {
+        self.filtered_cards().get(self.card_index).copied()
+    }
+
+    fn set_cards(&mut self, cards: Vec<types::Card>) {
+        self.cards = cards;
+        self.card_index = 0;
+        self.status = format!("{} card(s) in {}", self.cards.len(), self.registry());
+    }
+
+    fn move_selection(&mut self, down: bool) {
+        let (index, len) = match self.screen {
+            Screen::Registries => (&mut self.registry_index, REGISTRIES.len()),
+            Screen::Cards => {
+                let len = self.filtered_cards().len();
+                (&mut self.card_index, len)
+            }
+            Screen::Detail => {
+                self.scroll = if down {
+                    self.scroll.saturating_add(1)
+                } else {
+                    self.scroll.saturating_sub(1)
+                };
+                return;
+            }
+        };
+
+        if len == 0 {
+            return;
+        }
+        *index = if down {
+            (*index + 1).min(len - 1)
+        } else {
+            index.saturating_sub(1)
+        };
+    }
+
+    /// Handles key presses while the card filter is being edited
+    fn handle_filter_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.filtering = false;
+            }
+            KeyCode::Enter => self.filtering = false,
+            KeyCode::Backspace => {
+                self.filter.pop();
+            }
+            KeyCode::Char(c) => self.filter.push(c),
+            _ => return Action::None,
+        }
+        self.card_index = 0;
+        Action::None
+    }
+
+    /// Updates the state for a key press
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key event from the terminal
+    ///
+    /// # Returns
+    /// * `Action` - Work for the event loop to perform
+    ///
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+
+        if self.filtering {
+            return self.handle_filter_key(key);
+        }
+
+        match (self.screen, key.code) {
+            (_, KeyCode::Char('q')) => Action::Quit,
+            (_, KeyCode::Down | KeyCode::Char('j')) => {
+                self.move_selection(true);
+                Action::None
+            }
+            (_, KeyCode::Up | KeyCode::Char('k')) => {
+                self.move_selection(false);
+                Action::None
+            }
+            (Screen::Registries, KeyCode::Enter) => {
+                self.screen = Screen::Cards;
+                self.filter.clear();
+                Action::LoadCards
+            }
+            (Screen::Cards, KeyCode::Char('/')) => {
+                self.filtering = true;
+                Action::None
+            }
+            (Screen::Cards, KeyCode::Char('r')) => Action::LoadCards,
+            (Screen::Cards, KeyCode::Enter) if self.selected_card().is_some() => {
+                self.screen = Screen::Detail;
+                self.tab = DetailTab::Info;
+                self.scroll = 0;
+                Action::LoadDetail
+            }
+            (Screen::Cards, KeyCode::Esc) => {
+                self.screen = Screen::Registries;
+                self.cards.clear();
+                Action::None
+            }
+            (Screen::Cards | Screen::Detail, KeyCode::Char('d'))
+                if self.selected_card().is_some() =>
+            {
+                Action::Download
+            }
+            (Screen::Detail, KeyCode::Tab | KeyCode::Right | KeyCode::Char('l')) => {
+                self.tab = self.tab.next();
+                self.scroll = 0;
+                Action::None
+            }
+            (Screen::Detail, KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h')) => {
+                self.tab = self.tab.previous();
+                self.scroll = 0;
+                Action::None
+            }
+            (Screen::Detail, KeyCode::Esc) => {
+                self.screen = Screen::Cards;
+                Action::None
+            }
+            _ => Action::None,
+        }
+    }
+}
+
+/// Builds the contents of the detail tabs for a card. Failures are shown in the tab they affect
+/// so one unavailable endpoint does not hide the rest of the card
+///
+/// # Arguments
+///
+/// * `registry` - Registry of the card
+/// * `card` - Card to describe
+///
+async fn load_detail(registry: &str, card: &types::Card) -> CardDetail {
+    let mut info = vec![
+        format!("name:        {}", card.name),
+        format!("repository:  {}", card.repository),
+        format!("version:     {}", card.version),
+        format!("uid:         {}", card.uid),
+        format!("contact:     {}", card.contact),
+        format!("date:        {}", card.date.as_deref().unwrap_or("")),
+    ];
+    if let Some(runcard_uid) = &card.runcard_uid {
+        info.push(format!("runcard_uid: {}", runcard_uid));
+    }
+
+    info.push(String::new());
+    info.push("tags:".to_string());
+    let mut tags = card.tags.iter().collect::<Vec<_>>();
+    tags.sort();
+    info.extend(
+        tags.iter()
+            .map(|(key, value)| format!("  {}: {}", key, value)),
+    );
+
+    let mut schema = vec!["Data schema is only available for model cards".to_string()];
+    if registry == "model" {
+        match fetch_model_metadata(&card.uid, ApiVersion::current())
+            .await
+            .map(serde_json::to_value)
+        {
+            Ok(Ok(mut metadata)) => {
+                let data_schema = metadata
+                    .as_object_mut()
+                    .and_then(|fields| fields.remove("data_schema"));
+                schema = pretty_lines(&data_schema.unwrap_or_default());
+                info.push(String::new());
+                info.push("metadata:".to_string());
+                info.extend(pretty_lines(&metadata));
+            }
+            Ok(Err(e)) => schema = vec![format!("Failed to parse model metadata: {}", e)],
+            Err(e) => schema = vec![format!("Failed to load model metadata: {}", e)],
+        }
+    }
+
+    let files = match utils::SaveRoot::from_registry(registry) {
+        Ok(save_root) => {
+            let root = save_root.card_root(&card.repository, &card.name, &card.version);
+            match RouteHelper::list_files(&root).await {
+                Ok(response) => response
+                    .files
+                    .iter()
+                    .map(|file| {
+                        Path::new(file)
+                            .strip_prefix(&root)
+                            .map(|path| path.display().to_string())
+                            .unwrap_or_else(|_| file.clone())
+                    })
+                    .collect(),
+                Err(e) => vec![format!("Failed to list files: {}", e)],
+            }
+        }
+        Err(e) => vec![e.to_string()],
+    };
+
+    let run_uid = match registry {
+        "run" => Some(card.uid.as_str()),
+        _ => card.runcard_uid.as_deref(),
+    };
+    let metrics = match run_uid {
+        Some(run_uid) => {
+            let metric_getter = MetricGetter {};
+            match metric_getter.fetch_metrics(run_uid).await {
+                Ok(metrics) if metrics.is_empty() => vec!["No metrics logged".to_string()],
+                Ok(metrics) => match metric_getter.metric_table(&metrics) {
+                    Ok(table) => table.lines().map(String::from).collect(),
+                    Err(e) => vec![e.to_string()],
+                },
+                Err(e) => vec![format!("Failed to load metrics: {}", e)],
+            }
+        }
+        None => vec!["Card is not associated with a run".to_string()],
+    };
+
+    CardDetail {
+        info,
+        schema,
+        files,
+        metrics,
+    }
+}
+
+fn pretty_lines(value: &serde_json::Value) -> Vec<String> {
+    serde_json::to_string_pretty(value)
+        .unwrap_or_default()
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+/// Draws the browser
+///
+/// # Arguments
+///
+/// * `frame` - Frame to draw on
+/// * `state` - Browser state
+///
+fn draw(frame: &mut Frame, state: &BrowserState) {
+    let [header, body, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let mut breadcrumb = vec!["opsml".to_string()];
+    if state.screen != Screen::Registries {
+        breadcrumb.push(state.registry().to_string());
+    }
+    if state.screen == Screen::Detail {
+        if let Some(card) = state.selected_card() {
+            breadcrumb.push(format!(
+                "{}/{} v{}",
+                card.repository, card.name, card.version
+            ));
+        }
+    }
+    frame.render_widget(
+        Paragraph::new(breadcrumb.join(" > ")).style(Style::new().fg(Color::Green).bold()),
+        header,
+    );
+
+    let highlight = Style::new().add_modifier(Modifier::REVERSED);
+    match state.screen {
+        Screen::Registries => {
+            let list = List::new(REGISTRIES)
+                .block(Block::bordered().title(" Registries "))
+                .highlight_style(highlight);
+            let mut list_state = ListState::default().with_selected(Some(state.registry_index));
+            frame.render_stateful_widget(list, body, &mut list_state);
+        }
+        Screen::Cards => {
+            let [filter_area, table_area] =
+                Layout::vertical([Constraint::Length(3), Constraint::Min(3)]).areas(body);
+
+            let filter_style = match state.filtering {
+                true => Style::new().fg(Color::Yellow),
+                false => Style::new(),
+            };
+            frame.render_widget(
+                Paragraph::new(state.filter.as_str())
+                    .style(filter_style)
+                    .block(Block::bordered().title(" Filter (/) ")),
+                filter_area,
+            );
+
+            let rows = state.filtered_cards().into_iter().map(|card| {
+                Row::new(vec![
+                    card.name.clone(),
+                    card.repository.clone(),
+                    card.version.clone(),
+                    card.date.clone().unwrap_or_default(),
+                    card.uid.clone(),
+                ])
+            });
+            let table = Table::new(
+                rows,
+                [
+                    Constraint::Percentage(25),
+                    Constraint::Percentage(20),
+                    Constraint::Percentage(10),
+                    Constraint::Percentage(15),
+                    Constraint::Percentage(30),
+                ],
+            )
+            .header(
+                Row::new(["Name", "Repository", "Version", "Date", "Uid"])
+                    .style(Style::new().fg(Color::Green).bold()),
+            )
+            .block(Block::bordered().title(" Cards "))
+            .row_highlight_style(highlight);
+            let mut table_state = TableState::default().with_selected(Some(state.card_index));
+            frame.render_stateful_widget(table, table_area, &mut table_state);
+        }
+        Screen::Detail => {
+            let [tabs_area, content_area] =
+                Layout::vertical([Constraint::Length(3), Constraint::Min(3)]).areas(body);
+
+            let tabs = Tabs::new(DetailTab::ALL.iter().map(|tab| tab.title()))
+                .select(state.tab.index())
+                .highlight_style(Style::new().fg(Color::Yellow).bold())
+                .block(Block::bordered());
+            frame.render_widget(tabs, tabs_area);
+
+            let lines = state
+                .detail
+                .lines(state.tab)
+                .iter()
+                .map(|line| Line::from(line.as_str()))
+                .collect::<Vec<_>>();
+            frame.render_widget(
+                Paragraph::new(Text::from(lines))
+                    .scroll((state.scroll, 0))
+                    .block(Block::bordered()),
+                content_area,
+            );
+        }
+    }
+
+    let hints = match (state.screen, state.filtering) {
+        (_, true) => "type to filter  enter: apply  esc: clear",
+        (Screen::Registries, _) => "j/k: move  enter: open  q: quit",
+        (Screen::Cards, _) => {
+            "j/k: move  enter: details  /: filter  d: download  r: refresh  esc: back  q: quit"
+        }
+        (Screen::Detail, _) => "tab: next tab  j/k: scroll  d: download  esc: back  q: quit",
+    };
+    let footer_text = match state.status.is_empty() {
+        true => hints.to_string(),
+        false => format!("{}  |  {}", state.status, hints),
+    };
+    frame.render_widget(
+        Paragraph::new(footer_text).style(Style::new().fg(Color::DarkGray)),
+        footer,
+    );
+}
+
+/// Downloads the selected card outside of the TUI so download progress is visible
+///
+/// # Arguments
+///
+/// * `registry` - Registry of the card
+/// * `uid` - Card uid
+/// * `write_dir` - Directory to write artifacts to
+///
+async fn download_selected(
+    registry: &str,
+    uid: &str,
+    write_dir: &str,
+) -> Result<(), anyhow::Error> {
+    match registry {
+        "model" => {
+            download_model(
+                None,
+                None,
+                None,
+                Some(uid),
+                write_dir,
+                &false,
+                &false,
+                &false,
+                &false,
+                &false,
+                &[],
+                &[],
+            )
+            .await
+        }
+        _ => {
+            download_card(
+                registry,
+                None,
+                None,
+                None,
+                Some(uid),
+                write_dir,
+                &false,
+                &false,
+                &[],
+                &[],
+            )
+            .await
+        }
+    }
+}
+
+async fn load_cards(state: &mut BrowserState) {
+    match cards::fetch_cards(state.registry(), None, None, None, None, None, false).await {
+        Ok(response) => state.set_cards(response.cards),
+        Err(e) => {
+            state.cards.clear();
+            state.status = format!("Failed to list cards: {}", e);
+        }
+    }
+}
+
+/// Waits for the next terminal event off the async runtime
+///
+/// # Returns
+/// * `Result<Option<Event>, String>` - Event, or None if nothing arrived before the poll interval
+async fn next_event() -> Result<Option<Event>, anyhow::Error> {
+    let event = tokio::task::spawn_blocking(|| -> std::io::Result<Option<Event>> {
+        if event::poll(INPUT_POLL_INTERVAL)? {
+            event::read().map(Some)
+        } else {
+            Ok(None)
+        }
+    })
+    .await??;
+
+    Ok(event)
+}
+
+async fn run(
+    terminal: &mut DefaultTerminal,
+    state: &mut BrowserState,
+    write_dir: &str,
+) -> Result<(), anyhow::Error> {
+    if state.screen == Screen::Cards {
+        load_cards(state).await;
+    }
+
+    loop {
+        terminal.draw(|frame| draw(frame, state))?;
+
+        let key = match next_event().await? {
+            Some(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        match state.handle_key(key) {
+            Action::None => {}
+            Action::Quit => return Ok(()),
+            Action::LoadCards => {
+                state.status = format!("Loading {} cards...", state.registry());
+                terminal.draw(|frame| draw(frame, state))?;
+                load_cards(state).await;
+            }
+            Action::LoadDetail => {
+                if let Some(card) = state.selected_card().cloned() {
+                    state.status = format!("Loading {}...", card.name);
+                    terminal.draw(|frame| draw(frame, state))?;
+                    state.detail = load_detail(state.registry(), &card).await;
+                    state.status.clear();
+                }
+            }
+            Action::Download => {
+                if let Some(card) = state.selected_card().cloned() {
+                    ratatui::restore();
+                    println!(
+                        "Downloading {}/{} v{} to {}",
+                        card.repository, card.name, card.version, write_dir
+                    );
+                    state.status =
+                        match download_selected(state.registry(), &card.uid, write_dir).await {
+                            Ok(()) => format!("Downloaded {} v{}", card.name, card.version),
+                            Err(e) => {
+                                println!("Download failed: {}", e);
+                                format!("Download of {} failed", card.name)
+                            }
+                        };
+                    println!("Press Enter to return to the browser");
+                    tokio::task::spawn_blocking(|| {
+                        std::io::stdin().lock().read_line(&mut String::new())
+                    })
+                    .await??;
+                    *terminal = ratatui::init();
+                }
+            }
+        }
+    }
+}
+
+/// Opens the interactive registry browser
+///
+/// # Arguments
+///
+/// * `registry` - Optional registry to open
+/// * `write_dir` - Directory downloads are written to
+///
+pub async fn browse(registry: Option<&str>, write_dir: &str) -> Result<(), anyhow::Error> {
+    let mut state = BrowserState::new(registry)?;
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut state, write_dir).await;
+    ratatui::restore();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use std::collections::HashMap;
+
+    fn card(name: &str, repository: &str, version: &str) -> types::Card {
+        types::Card {
+            name: name.to_string(),
+            repository: repository.to_string(),
+            date: None,
+            contact: "fake".to_string(),
+            version: version.to_string(),
+            uid: format!("{}-uid", name),
+            tags: HashMap::new(),
+            runcard_uid: None,
+        }
+    }
+
+    fn press(state: &mut BrowserState, code: KeyCode) -> Action {
+        state.handle_key(KeyEvent::from(code))
+    }
+
+    #[test]
+    fn test_filter_cards() {
+        let mut state = BrowserState::new(Some("model")).unwrap();
+        state.set_cards(vec![
+            card("churn-model", "growth", "1.0.0"),
+            card("eta-model", "logistics", "2.1.0"),
+            card("Churn-v2", "growth", "1.1.0"),
+        ]);
+
+        press(&mut state, KeyCode::Char('/'));
+        for c in "CHURN".chars() {
+            press(&mut state, KeyCode::Char(c));
+        }
+        let names = state
+            .filtered_cards()
+            .iter()
+            .map(|card| card.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["churn-model", "Churn-v2"]);
+
+        // q is typed into the filter rather than quitting
+        assert_eq!(press(&mut state, KeyCode::Char('q')), Action::None);
+        assert!(state.filtered_cards().is_empty());
+
+        press(&mut state, KeyCode::Esc);
+        assert!(!state.filtering);
+        assert_eq!(state.filtered_cards().len(), 3);
+
+        assert!(BrowserState::new(Some("fake")).is_err());
+    }
+
+    #[test]
+    fn test_key_navigation() {
+        let mut state = BrowserState::new(None).unwrap();
+        assert_eq!(state.screen, Screen::Registries);
+
+        press(&mut state, KeyCode::Char('j'));
+        assert_eq!(state.registry(), "model");
+        assert_eq!(press(&mut state, KeyCode::Enter), Action::LoadCards);
+        assert_eq!(state.screen, Screen::Cards);
+
+        // nothing to open without cards
+        assert_eq!(press(&mut state, KeyCode::Enter), Action::None);
+
+        state.set_cards(vec![card("a", "repo", "1.0.0"), card("b", "repo", "1.0.0")]);
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Down);
+        assert_eq!(state.selected_card().unwrap().name, "b");
+
+        assert_eq!(press(&mut state, KeyCode::Enter), Action::LoadDetail);
+        assert_eq!(state.screen, Screen::Detail);
+        press(&mut state, KeyCode::Tab);
+        assert_eq!(state.tab, DetailTab::Schema);
+        press(&mut state, KeyCode::BackTab);
+        press(&mut state, KeyCode::BackTab);
+        assert_eq!(state.tab, DetailTab::Metrics);
+        assert_eq!(press(&mut state, KeyCode::Char('d')), Action::Download);
+
+        press(&mut state, KeyCode::Esc);
+        assert_eq!(state.screen, Screen::Cards);
+        press(&mut state, KeyCode::Esc);
+        assert_eq!(state.screen, Screen::Registries);
+        assert_eq!(press(&mut state, KeyCode::Char('q')), Action::Quit);
+    }
+
+    #[test]
+    fn test_draw_cards() {
+        let mut state = BrowserState::new(Some("data")).unwrap();
+        state.set_cards(vec![card("test-data", "test-repo", "1.0.0")]);
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+        terminal.draw(|frame| draw(frame, &state)).unwrap();
+
+        let content = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect::<String>();
+        assert!(content.contains("opsml > data"));
+        assert!(content.contains("test-data"));
+        assert!(content.contains("1 card(s) in data"));
+    }
+}
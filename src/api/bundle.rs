@@ -0,0 +1,833 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::api_version::ApiVersion;
+use crate::api::cards;
+use crate::api::download::FileDownloader;
+use crate::api::metrics::MetricGetter;
+use crate::api::model::download_model_metadata;
+use crate::api::oci::{sorted_entries, write_reproducible_tar};
+use crate::api::server::OpsmlServer;
+use crate::api::storage::StorageBackend;
+use crate::api::types;
+use crate::api::utils::{self, SaveRoot, OPSML_ROOT};
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const BUNDLE_MANIFEST: &str = "manifest.json";
+const BUNDLE_FORMAT_VERSION: u32 = 1;
+const ZSTD_LEVEL: i32 = 10;
+
+/// Card json, model metadata, metrics and run card json of each model, under `cards/<uid>/`
+const CARDS_DIR: &str = "cards";
+const CARD_FILE: &str = "card.json";
+const METRICS_FILE: &str = "metrics.json";
+const RUN_CARD_FILE: &str = "run-card.json";
+
+/// Artifacts of each model, laid out like the storage root under `files/`
+const FILES_DIR: &str = "files";
+
+/// Writes a value as pretty json. Maps are written with sorted keys
+fn write_json<T: serde::Serialize>(path: &Path, value: &T) -> Result<(), anyhow::Error> {
+    let value = serde_json::to_value(value)?;
+    fs::write(path, serde_json::to_vec_pretty(&value)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Hashes a file
+///
+/// # Arguments
+///
+/// * `path` - File to hash
+///
+/// # Returns
+/// * `Result<(u64, String), String>` - Size and hex encoded sha256 of the file
+///
+fn hash_file(path: &Path) -> Result<(u64, String), anyhow::Error> {
+    let mut file =
+        fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut file, &mut hasher)?;
+
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+/// Lists every file under a bundle directory, except the manifest, with its size and hash
+fn hash_files(dir: &Path) -> Result<Vec<types::BundleFile>, anyhow::Error> {
+    let mut files = Vec::new();
+
+    for relative in sorted_entries(dir)? {
+        let path = dir.join(&relative);
+        if path.is_dir() || relative == Path::new(BUNDLE_MANIFEST) {
+            continue;
+        }
+
+        let (size, sha256) = hash_file(&path)?;
+        files.push(types::BundleFile {
+            path: relative.to_string_lossy().to_string(),
+            size,
+            sha256,
+        });
+    }
+
+    Ok(files)
+}
+
+/// Checks a bundle's manifest against the files found in the bundle
+///
+/// # Arguments
+///
+/// * `manifest` - Manifest read from the bundle
+/// * `actual` - Files found in the bundle, except the manifest
+///
+fn check_manifest(
+    manifest: &types::BundleManifest,
+    actual: &[types::BundleFile],
+) -> Result<(), anyhow::Error> {
+    if manifest.format_version != BUNDLE_FORMAT_VERSION {
+        return Err(anyhow::Error::msg(format!(
+            "Unsupported bundle format version {}. This client reads version {}",
+            manifest.format_version, BUNDLE_FORMAT_VERSION
+        )));
+    }
+
+    let expected: BTreeMap<&str, &types::BundleFile> = manifest
+        .files
+        .iter()
+        .map(|file| (file.path.as_str(), file))
+        .collect();
+    let mut errors = Vec::new();
+
+    for file in actual.iter() {
+        match expected.get(file.path.as_str()) {
+            Some(listed) if *listed == file => {}
+            Some(_) => errors.push(format!("{} does not match its hash", file.path)),
+            None => errors.push(format!("{} is not listed in the manifest", file.path)),
+        }
+    }
+    for path in expected.keys() {
+        if !actual.iter().any(|file| file.path == *path) {
+            errors.push(format!("{} is missing", path));
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(anyhow::Error::msg(format!(
+            "Bundle failed verification:\n  {}",
+            errors.join("\n  ")
+        )));
+    }
+
+    Ok(())
+}
+
+/// Parses a bundle manifest
+fn parse_manifest(raw: &str) -> Result<types::BundleManifest, anyhow::Error> {
+    serde_json::from_str(raw).with_context(|| "Failed to parse bundle manifest")
+}
+
+/// Checks the files of an unpacked bundle against its manifest
+///
+/// # Arguments
+///
+/// * `dir` - Unpacked bundle
+///
+/// # Returns
+/// * `Result<types::BundleManifest, String>` - Verified manifest
+///
+pub fn verify_dir(dir: &Path) -> Result<types::BundleManifest, anyhow::Error> {
+    let manifest = parse_manifest(
+        &fs::read_to_string(dir.join(BUNDLE_MANIFEST))
+            .with_context(|| format!("Bundle has no {}", BUNDLE_MANIFEST))?,
+    )?;
+
+    check_manifest(&manifest, &hash_files(dir)?)?;
+    Ok(manifest)
+}
+
+/// Checks the files of a bundle against its manifest as they are read from the archive, so
+/// nothing is written to disk
+///
+/// # Arguments
+///
+/// * `bundle` - Bundle file
+///
+/// # Returns
+/// * `Result<types::BundleManifest, String>` - Verified manifest
+///
+fn verify_archive(bundle: &Path) -> Result<types::BundleManifest, anyhow::Error> {
+    let file =
+        fs::File::open(bundle).with_context(|| format!("Failed to open {}", bundle.display()))?;
+    let mut archive = tar::Archive::new(zstd::Decoder::new(file)?);
+
+    let mut manifest = None;
+    let mut actual = Vec::new();
+    for entry in archive
+        .entries()
+        .with_context(|| format!("Failed to read {}", bundle.display()))?
+    {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path()?.to_string_lossy().to_string();
+        if path == BUNDLE_MANIFEST {
+            let mut raw = String::new();
+            entry.read_to_string(&mut raw)?;
+            manifest = Some(parse_manifest(&raw)?);
+            continue;
+        }
+
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut entry, &mut hasher)?;
+        actual.push(types::BundleFile {
+            path,
+            size,
+            sha256: format!("{:x}", hasher.finalize()),
+        });
+    }
+
+    let manifest = manifest.with_context(|| format!("Bundle has no {}", BUNDLE_MANIFEST))?;
+    check_manifest(&manifest, &actual)?;
+    Ok(manifest)
+}
+
+/// Unpacks a bundle into a fresh directory and verifies it
+///
+/// # Arguments
+///
+/// * `bundle` - Bundle file
+/// * `dest` - Directory to unpack to. Replaced if it exists
+///
+fn unpack(bundle: &Path, dest: &Path) -> Result<types::BundleManifest, anyhow::Error> {
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+
+    let file =
+        fs::File::open(bundle).with_context(|| format!("Failed to open {}", bundle.display()))?;
+    let decoder = zstd::Decoder::new(file)?;
+    tar::Archive::new(decoder)
+        .unpack(dest)
+        .with_context(|| format!("Failed to unpack {}", bundle.display()))?;
+
+    verify_dir(dest)
+}
+
+/// Runs `f` against an unpacked copy of a bundle, then removes the copy. The copy is written to
+/// the temp directory, so bundles can be read from read-only media
+async fn with_unpacked<T, F, Fut>(bundle: &str, f: F) -> Result<T, anyhow::Error>
+where
+    F: FnOnce(PathBuf, types::BundleManifest) -> Fut,
+    Fut: std::future::Future<Output = Result<T, anyhow::Error>>,
+{
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    let staging = env::temp_dir().join(format!("opsml-bundle-{}-{}", std::process::id(), nanos));
+    let result = match unpack(Path::new(bundle), &staging) {
+        Ok(manifest) => f(staging.clone(), manifest).await,
+        Err(e) => Err(e),
+    };
+
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    result
+}
+
+pub struct BundleCreator<'a> {
+    pub uids: &'a [String],
+    pub out: &'a str,
+}
+
+impl BundleCreator<'_> {
+    /// Writes a model's card, metadata, metrics and files to the staging directory
+    ///
+    /// # Arguments
+    ///
+    /// * `uid` - Model card uid
+    /// * `staging` - Directory the bundle is staged in
+    ///
+    async fn stage_model(
+        &self,
+        uid: &str,
+        staging: &Path,
+    ) -> Result<types::BundleModel, anyhow::Error> {
+        // the card is kept as the server returned it so it can be registered on import
+        let raw_card = cards::get_raw_card("model", uid).await?;
+        let card: types::Card =
+            serde_json::from_value(raw_card.clone()).with_context(|| "Failed to parse card")?;
+
+        let card_dir = staging.join(CARDS_DIR).join(&card.uid);
+        fs::create_dir_all(&card_dir)?;
+        write_json(&card_dir.join(CARD_FILE), &raw_card)?;
+
+        download_model_metadata(
+            None,
+            None,
+            None,
+            Some(&card.uid),
+            &card_dir.to_string_lossy(),
+            &false,
+        )
+        .await?;
+
+        let metrics = match card.runcard_uid.as_deref() {
+            Some(run_uid) => {
+                let raw_run = cards::get_raw_card("run", run_uid).await?;
+                write_json(&card_dir.join(RUN_CARD_FILE), &raw_run)?;
+                MetricGetter {}.fetch_metrics(run_uid).await?
+            }
+            None => Vec::new(),
+        };
+        write_json(&card_dir.join(METRICS_FILE), &metrics)?;
+
+        let rpath_root = SaveRoot::Model.card_root(&card.repository, &card.name, &card.version);
+        let files_dir = staging.join(FILES_DIR);
+        FileDownloader {
+            write_dir: &files_dir.to_string_lossy(),
+            dry_run: &false,
+            include: &[],
+            exclude: &[],
+        }
+        .download_files(&rpath_root, Path::new(OPSML_ROOT))
+        .await?;
+
+        Ok(types::BundleModel {
+            uid: card.uid,
+            name: card.name,
+            repository: card.repository,
+            version: card.version,
+            rpath_root: rpath_root.to_string_lossy().to_string(),
+        })
+    }
+
+    /// Stages every model and writes the manifest
+    async fn stage(&self, staging: &Path) -> Result<(), anyhow::Error> {
+        let mut models = Vec::new();
+        for uid in self.uids.iter() {
+            models.push(self.stage_model(uid, staging).await?);
+        }
+
+        let manifest = types::BundleManifest {
+            format_version: BUNDLE_FORMAT_VERSION,
+            api_version: ApiVersion::current(),
+            models,
+            files: hash_files(staging)?,
+        };
+        write_json(&staging.join(BUNDLE_MANIFEST), &manifest)
+    }
+
+    /// Writes the staged directory as a zstd compressed tar
+    fn pack(&self, staging: &Path) -> Result<(), anyhow::Error> {
+        let file =
+            fs::File::create(self.out).with_context(|| format!("Failed to create {}", self.out))?;
+        let encoder = zstd::Encoder::new(io::BufWriter::new(file), ZSTD_LEVEL)?;
+        let encoder = write_reproducible_tar(staging, Path::new(""), encoder)?;
+        encoder.finish()?.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Bundles models with their cards, metadata, metrics and files for use without a server
+///
+/// # Arguments
+///
+/// * `creator` - Models to bundle and the bundle file to write
+///
+pub async fn create_bundle(creator: &BundleCreator<'_>) -> Result<(), anyhow::Error> {
+    if creator.uids.is_empty() {
+        return Err(anyhow::Error::msg("Provide at least one model uid"));
+    }
+
+    let staging = PathBuf::from(format!("{}.staging", creator.out));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+
+    let result = match creator.stage(&staging).await {
+        Ok(()) => creator.pack(&staging),
+        Err(e) => Err(e),
+    };
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    result?;
+
+    let (size, sha256) = hash_file(Path::new(creator.out))?;
+    println!(
+        "Wrote bundle of {} model(s) to {} ({})",
+        creator.uids.len(),
+        creator.out.green(),
+        utils::format_bytes(size)
+    );
+    println!("sha256: {}", sha256);
+    Ok(())
+}
+
+/// Checks every file of a bundle against its manifest without contacting a server or writing
+/// to disk
+///
+/// # Arguments
+///
+/// * `bundle` - Bundle file
+///
+pub async fn verify_bundle(bundle: &str) -> Result<(), anyhow::Error> {
+    let manifest = verify_archive(Path::new(bundle))?;
+
+    println!(
+        "{} model(s), {} file(s) verified",
+        manifest.models.len(),
+        manifest.files.len()
+    );
+    for model in manifest.models.iter() {
+        println!(
+            "  {} {} (version {}) {}",
+            model.repository,
+            model.name.green(),
+            model.version,
+            model.uid.dimmed()
+        );
+    }
+    Ok(())
+}
+
+/// Uploads the files of an unpacked bundle to their `opsml-root:/` paths in a storage bucket
+///
+/// # Arguments
+///
+/// * `staging` - Unpacked bundle
+/// * `manifest` - Verified manifest
+/// * `uri` - Storage uri (`s3://`, `gs://`, `az://`, `file://`)
+///
+async fn upload_to_storage(
+    staging: &Path,
+    manifest: &types::BundleManifest,
+    uri: &str,
+) -> Result<(), anyhow::Error> {
+    let backend = StorageBackend::from_uri(uri)?;
+    let files_dir = Path::new(FILES_DIR);
+
+    for file in manifest.files.iter() {
+        let Ok(relative) = Path::new(&file.path).strip_prefix(files_dir) else {
+            continue;
+        };
+        let rpath = format!("{}{}", OPSML_ROOT, relative.to_string_lossy());
+        println!("Uploading: {}", rpath.green());
+        backend.upload(&staging.join(&file.path), &rpath).await?;
+    }
+
+    Ok(())
+}
+
+/// Copies an unpacked bundle into a local directory
+fn copy_to_dir(
+    staging: &Path,
+    manifest: &types::BundleManifest,
+    dir: &str,
+) -> Result<(), anyhow::Error> {
+    for file in manifest.files.iter() {
+        let lpath = Path::new(dir).join(&file.path);
+        utils::create_dir_path(&lpath)?;
+        fs::copy(staging.join(&file.path), &lpath)
+            .with_context(|| format!("Failed to write {}", lpath.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Registers a bundled model with a server. Files and metrics are written before the card so a
+/// card on the server always has its artifacts, and an interrupted import can be re-run
+///
+/// # Arguments
+///
+/// * `server` - Server to register with
+/// * `staging` - Unpacked bundle
+/// * `manifest` - Verified manifest
+/// * `model` - Model to register
+/// * `upload_files` - Whether to upload the model's files through the server
+///
+async fn register_model(
+    server: &OpsmlServer,
+    staging: &Path,
+    manifest: &types::BundleManifest,
+    model: &types::BundleModel,
+    upload_files: bool,
+) -> Result<(), anyhow::Error> {
+    let card_dir = staging.join(CARDS_DIR).join(&model.uid);
+    let raw_card: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(card_dir.join(CARD_FILE))?)
+            .with_context(|| format!("Failed to parse card of {}", model.uid))?;
+    let card: types::Card =
+        serde_json::from_value(raw_card.clone()).with_context(|| "Failed to parse card")?;
+    let registered = server.is_registered("model", &card).await?;
+
+    if upload_files {
+        // files of a card the server has not registered may be partial copies from an
+        // interrupted import, so they are all uploaded again
+        let existing = match registered {
+            true => {
+                server
+                    .list_existing_files(Path::new(&model.rpath_root))
+                    .await?
+            }
+            false => HashSet::new(),
+        };
+
+        let files_dir = Path::new(FILES_DIR);
+        for file in manifest.files.iter() {
+            let Ok(relative) = Path::new(&file.path).strip_prefix(files_dir) else {
+                continue;
+            };
+            let rpath = format!("{}{}", OPSML_ROOT, relative.to_string_lossy());
+            if !Path::new(&rpath).starts_with(&model.rpath_root) || existing.contains(&rpath) {
+                continue;
+            }
+
+            println!("Uploading: {}", rpath.green());
+            server
+                .upload_local_file(&rpath, &staging.join(&file.path))
+                .await?;
+        }
+    }
+
+    if let Some(run_uid) = card.runcard_uid.as_deref() {
+        let metrics: Vec<types::Metric> =
+            serde_json::from_str(&fs::read_to_string(card_dir.join(METRICS_FILE))?)
+                .with_context(|| format!("Failed to parse metrics of {}", model.uid))?;
+        let (logged, skipped) = server.log_missing_metrics(run_uid, metrics).await?;
+        println!("Logged {} metric(s), {} already on server", logged, skipped);
+
+        // the model card points at its run, so the run is registered first
+        let raw_run: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(card_dir.join(RUN_CARD_FILE))?)
+                .with_context(|| format!("Failed to parse run card of {}", model.uid))?;
+        let run: types::Card =
+            serde_json::from_value(raw_run.clone()).with_context(|| "Failed to parse run card")?;
+        if !server.is_registered("run", &run).await? {
+            server.register_card("run", &raw_run).await?;
+            println!("Registered run card {}", run.uid.green());
+        }
+    }
+
+    if registered {
+        println!("Card {} is already registered", card.uid);
+    } else {
+        server.register_card("model", &raw_card).await?;
+        println!("Registered card {}", card.uid.green());
+    }
+
+    Ok(())
+}
+
+/// Verifies a bundle and imports it into a local directory, a storage bucket and/or a server.
+/// A directory receives `cards/` and `files/`, where `files/` can be used as `OPSML_STORAGE_MOUNT`.
+/// A storage uri receives the files at their `opsml-root:/` paths. A server registers the cards
+/// and logs their metrics, and receives the files unless they were written to a storage uri
+///
+/// # Arguments
+///
+/// * `bundle` - Bundle file
+/// * `into` - Directory or storage uri (`s3://`, `gs://`, `az://`, `file://`)
+/// * `server` - Server url or name to register the cards with
+///
+pub async fn import_bundle(
+    bundle: &str,
+    into: Option<&str>,
+    server: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    if into.is_none() && server.is_none() {
+        return Err(anyhow::Error::msg(
+            "Provide a directory or storage uri to import into, a server to register with, or both",
+        ));
+    }
+
+    with_unpacked(bundle, |staging, manifest| async move {
+        let server = match server {
+            Some(server) => Some(OpsmlServer::connect(server).await?),
+            None => None,
+        };
+        if let Some(server) = server.as_ref() {
+            if server.api_version != manifest.api_version {
+                return Err(anyhow::Error::msg(format!(
+                    "Cannot register cards read from a {} server with a {} server",
+                    manifest.api_version.as_str(),
+                    server.api_version.as_str()
+                )));
+            }
+        }
+
+        let to_storage = into.is_some_and(|into| into.contains("://"));
+        match into {
+            Some(uri) if to_storage => upload_to_storage(&staging, &manifest, uri).await?,
+            Some(dir) => copy_to_dir(&staging, &manifest, dir)?,
+            None => {}
+        }
+
+        match server.as_ref() {
+            Some(server) => {
+                for model in manifest.models.iter() {
+                    println!(
+                        "Registering {} (version {}) with {}",
+                        model.name.green(),
+                        model.version,
+                        server.uri
+                    );
+                    register_model(server, &staging, &manifest, model, !to_storage).await?;
+                }
+            }
+            None if to_storage => println!(
+                "{}",
+                "Card records are not registered with a server. Pass --server to register them"
+                    .yellow()
+            ),
+            None => {}
+        }
+
+        println!(
+            "Imported {} model(s) and {} file(s)",
+            manifest.models.len(),
+            manifest.files.len()
+        );
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_bundle_round_trip() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url.clone());
+        }
+
+        let cards = fs::read_to_string("./src/api/test_utils/list_cards.json").unwrap();
+        let metadata = fs::read_to_string("./src/api/test_utils/metadata.json").unwrap();
+        let metrics = fs::read_to_string("./src/api/test_utils/list_metric.json").unwrap();
+        let files = types::ListFileResponse {
+            files: vec![
+                "opsml-root:/OPSML_MODEL_REGISTRY/repository/name/v1.0.0/model.onnx".to_string(),
+            ],
+        };
+
+        let runs = r#"{"cards": [{"name": "run", "repository": "repository", "contact": "devops@opsml.com",
+            "version": "1.0.0", "uid": "0987654321", "tags": {}, "project": "opsml"}]}"#;
+        let _mock_runs = server
+            .mock("POST", "/opsml/cards/list")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"registry_type": "run"}"#.to_string(),
+            ))
+            .with_status(201)
+            .with_body(runs)
+            .create();
+        let _mock_cards = server
+            .mock("POST", "/opsml/cards/list")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"registry_type": "model"}"#.to_string(),
+            ))
+            .with_status(201)
+            .with_body(cards)
+            .create();
+        let _mock_metadata = server
+            .mock("POST", "/opsml/models/metadata")
+            .with_status(201)
+            .with_body(metadata)
+            .create();
+        let mock_metrics = server
+            .mock("GET", "/opsml/metrics?run_uid=0987654321")
+            .with_status(200)
+            .with_body(metrics)
+            .expect(2)
+            .create();
+        let _mock_list = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/opsml/files/list\\?".to_string()),
+            )
+            .with_status(200)
+            .with_body(serde_json::to_string(&files).unwrap())
+            .create();
+        let _mock_presigned = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/opsml/files/presigned".to_string()),
+            )
+            .with_status(200)
+            .with_body(format!(r#"{{"url": "{}/model-bytes"}}"#, url))
+            .create();
+        let _mock_download = server
+            .mock("GET", "/model-bytes")
+            .with_status(200)
+            .with_body("onnx bytes")
+            .create();
+
+        let dir = env::temp_dir().join(format!("opsml-bundle-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let uids = vec!["1234567890".to_string()];
+
+        // bundling the same cards twice produces the same bytes
+        let first = dir.join("first.tar.zst");
+        let second = dir.join("second.tar.zst");
+        for out in [&first, &second] {
+            create_bundle(&BundleCreator {
+                uids: &uids,
+                out: out.to_str().unwrap(),
+            })
+            .await
+            .unwrap();
+        }
+        mock_metrics.assert();
+        assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+        assert!(!dir.join("first.tar.zst.staging").exists());
+
+        // verifying writes nothing next to the bundle, which may be on read-only media
+        let entries = fs::read_dir(&dir).unwrap().count();
+        verify_bundle(first.to_str().unwrap()).await.unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), entries);
+
+        let cache = dir.join("cache");
+        import_bundle(first.to_str().unwrap(), cache.to_str(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(
+                cache.join("files/OPSML_MODEL_REGISTRY/repository/name/v1.0.0/model.onnx")
+            )
+            .unwrap(),
+            "onnx bytes"
+        );
+        let card: types::Card = serde_json::from_str(
+            &fs::read_to_string(cache.join("cards/1234567890/card.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(card.runcard_uid.as_deref(), Some("0987654321"));
+        assert!(cache.join("cards/1234567890/model-metadata.json").exists());
+        assert!(!dir.join("first.tar.zst.unpacked").exists());
+
+        // a storage uri receives the files at their opsml-root paths
+        let bucket = dir.join("bucket");
+        fs::create_dir_all(&bucket).unwrap();
+        import_bundle(
+            first.to_str().unwrap(),
+            Some(&format!("file://{}", bucket.display())),
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(bucket
+            .join("OPSML_MODEL_REGISTRY/repository/name/v1.0.0/model.onnx")
+            .exists());
+
+        // a server receives the files and metrics, then registers the card as it was read
+        let mut target = mockito::Server::new_with_opts_async(mockito::ServerOpts::default()).await;
+        target
+            .mock("GET", "/opsml/healthcheck")
+            .with_status(200)
+            .create();
+        let empty_cards = target
+            .mock("POST", "/opsml/cards/list")
+            .with_status(200)
+            .with_body(r#"{"cards": []}"#)
+            .create();
+        target
+            .mock("GET", "/opsml/metrics?run_uid=0987654321")
+            .with_status(200)
+            .with_body(r#"{"metric": []}"#)
+            .create();
+        let mock_upload = target
+            .mock(
+                "POST",
+                "/opsml/files/upload?path=opsml-root%3A%2FOPSML_MODEL_REGISTRY%2Frepository%2Fname%2Fv1.0.0%2Fmodel.onnx",
+            )
+            .match_header("content-length", "10")
+            .match_body("onnx bytes")
+            .with_status(200)
+            .expect(1)
+            .create();
+        let mock_log_metrics = target
+            .mock("POST", "/opsml/metrics")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"metric": [{"name": "mae", "value": 5}]}"#.to_string(),
+            ))
+            .with_status(200)
+            .expect(1)
+            .create();
+        let mock_register = target
+            .mock("POST", "/opsml/cards/create")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"registry_type": "model", "card": {"uid": "1234567890", "contact": "devops@opsml.com"}}"#
+                    .to_string(),
+            ))
+            .with_status(200)
+            .expect(1)
+            .create();
+        let mock_register_run = target
+            .mock("POST", "/opsml/cards/create")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"registry_type": "run", "card": {"uid": "0987654321", "project": "opsml"}}"#
+                    .to_string(),
+            ))
+            .with_status(200)
+            .expect(1)
+            .create();
+        import_bundle(first.to_str().unwrap(), None, Some(&target.url()))
+            .await
+            .unwrap();
+        mock_upload.assert();
+        mock_log_metrics.assert();
+        mock_register.assert();
+        mock_register_run.assert();
+
+        // a card registered under another uid is not replaced
+        empty_cards.remove();
+        target
+            .mock("POST", "/opsml/cards/list")
+            .with_status(200)
+            .with_body(r#"{"cards": [{"uid": "other"}]}"#)
+            .create();
+        let err = import_bundle(first.to_str().unwrap(), None, Some(&target.url()))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("already registered"));
+        mock_register.assert();
+
+        // a changed file fails verification
+        let unpacked = dir.join("unpacked");
+        unpack(&first, &unpacked).unwrap();
+        fs::write(unpacked.join("cards/1234567890/metrics.json"), "[]").unwrap();
+        fs::write(unpacked.join("extra.txt"), "extra").unwrap();
+        let err = verify_dir(&unpacked).unwrap_err().to_string();
+        assert!(err.contains("cards/1234567890/metrics.json does not match its hash"));
+        assert!(err.contains("extra.txt is not listed in the manifest"));
+
+        // as does verifying the archive without unpacking it
+        let tampered = dir.join("tampered.tar.zst");
+        let encoder = zstd::Encoder::new(fs::File::create(&tampered).unwrap(), ZSTD_LEVEL).unwrap();
+        write_reproducible_tar(&unpacked, Path::new(""), encoder)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let err = verify_bundle(tampered.to_str().unwrap())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cards/1234567890/metrics.json does not match its hash"));
+        assert!(err.contains("extra.txt is not listed in the manifest"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
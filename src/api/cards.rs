@@ -1,6 +1,7 @@
 /// Copyright (c) Shipt, Inc.
 /// This source code is licensed under the MIT license found in the
 /// LICENSE file in the root directory of this source tree.
+use crate::api::api_version::ApiVersion;
 use crate::api::route_helper::RouteHelper;
 use crate::api::types;
 use crate::api::utils;
@@ -12,6 +13,56 @@ use std::collections::HashMap;
 use tabled::settings::style::Style;
 use tabled::{settings::Alignment, Table};
 
+/// Registries cards can be listed from
+pub const REGISTRIES: [&str; 6] = ["data", "model", "run", "pipeline", "audit", "project"];
+
+/// Parses a card list response. The v2 api wraps cards in an object while the v3 api returns them
+/// as a list
+///
+/// # Arguments
+///
+/// * `response` - Response body
+/// * `version` - Api version of the server
+///
+pub fn parse_cards(
+    response: &str,
+    version: ApiVersion,
+) -> Result<types::ListCardResponse, anyhow::Error> {
+    match version {
+        ApiVersion::V2 => serde_json::from_str(response)
+            .with_context(|| "Failed to load response to ListCardResponse JSON"),
+        ApiVersion::V3 => {
+            let cards: Vec<types::Card> = serde_json::from_str(response)
+                .with_context(|| "Failed to load v3 card list response JSON")?;
+            Ok(types::ListCardResponse { cards })
+        }
+    }
+}
+
+/// Parses a card list response without dropping fields this client does not know about
+///
+/// # Arguments
+///
+/// * `response` - Response body
+/// * `version` - Api version of the server
+///
+pub fn parse_raw_cards(
+    response: &str,
+    version: ApiVersion,
+) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+    let value: serde_json::Value =
+        serde_json::from_str(response).with_context(|| "Failed to load card list response JSON")?;
+    let cards = match version {
+        ApiVersion::V2 => value.get("cards").cloned(),
+        ApiVersion::V3 => Some(value),
+    };
+
+    match cards {
+        Some(serde_json::Value::Array(cards)) => Ok(cards),
+        _ => Err(anyhow::Error::msg("Card list response has no cards")),
+    }
+}
+
 struct CardLister<'a> {
     pub registry_type: &'a str,
     pub name: Option<&'a str>,
@@ -22,6 +73,7 @@ struct CardLister<'a> {
     pub tags: HashMap<String, String>,
     pub max_date: Option<&'a str>,
     pub ignore_release_candidates: &'a bool,
+    pub api_version: ApiVersion,
 }
 impl CardLister<'_> {
     /// Checks if registry is valid
@@ -33,9 +85,7 @@ impl CardLister<'_> {
     fn validate_registry(&self) -> Result<(), anyhow::Error> {
         // Determines correct  registry to use
 
-        let registries = ["data", "model", "run", "pipeline", "audit", "project"];
-
-        if registries.contains(&self.registry_type) {
+        if REGISTRIES.contains(&self.registry_type) {
             Ok(())
         } else {
             Err(anyhow::Error::msg(format!(
@@ -57,9 +107,7 @@ impl CardLister<'_> {
     fn parse_list_response(&self, response: &str) -> Result<String, anyhow::Error> {
         // Parses response and creates a table
 
-        let cards: types::ListCardResponse = serde_json::from_str(response)
-            .with_context(|| "Failed to load response to ListCardResponse JSON")
-            .unwrap();
+        let cards = parse_cards(response, self.api_version)?;
 
         let mut card_table: Vec<types::CardTable> = Vec::new();
 
@@ -92,6 +140,7 @@ impl CardLister<'_> {
     /// # Returns
     /// HashMap<String, String> - Tags hashmap
     ///
+    #[allow(clippy::unnecessary_unwrap)]
     fn construct_tags(&mut self, tag_name: Option<Vec<String>>, tag_value: Option<Vec<String>>) {
         let mut tags: HashMap<String, String> = HashMap::new();
         if tag_name.is_some() && tag_value.is_some() {
@@ -127,16 +176,53 @@ impl CardLister<'_> {
             ignore_release_candidates: self.ignore_release_candidates,
         };
 
-        let response = RouteHelper::make_post_request(
-            &utils::OpsmlPaths::ListCard.as_str(),
-            &list_table_request,
-        )
-        .await
+        let url = utils::OpsmlPaths::ListCard.url_for(self.api_version);
+        let response = match self.api_version {
+            ApiVersion::V2 => RouteHelper::make_post_request(&url, &list_table_request).await,
+            ApiVersion::V3 => {
+                let request = types::ListCardRequestV3::from(&list_table_request);
+                RouteHelper::make_post_request(&url, &request).await
+            }
+        }
         .unwrap();
 
         Ok(response)
     }
 
+    /// Fetches cards matching the lister's arguments
+    ///
+    /// # Returns
+    /// * `Result<types::ListCardResponse, String>` - Cards returned by the server
+    ///
+    async fn fetch_cards(&self) -> Result<types::ListCardResponse, anyhow::Error> {
+        self.validate_registry()?;
+        let response = self.make_card_request().await?;
+
+        if response.status().is_success() {
+            parse_cards(&response.text().await?, self.api_version)
+        } else {
+            Err(anyhow::Error::msg(format!(
+                "Failed to make call to list cards: {}",
+                response.text().await?
+            )))
+        }
+    }
+
+    /// Fetches cards matching the lister's arguments as the server returns them
+    async fn fetch_raw_cards(&self) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        self.validate_registry()?;
+        let response = self.make_card_request().await?;
+
+        if response.status().is_success() {
+            parse_raw_cards(&response.text().await?, self.api_version)
+        } else {
+            Err(anyhow::Error::msg(format!(
+                "Failed to make call to list cards: {}",
+                response.text().await?
+            )))
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn get_cards(
         registry: &str,
@@ -161,6 +247,7 @@ impl CardLister<'_> {
             tags,
             max_date,
             ignore_release_candidates: &ignore_release_candidates,
+            api_version: ApiVersion::current(),
         };
 
         card_lister.validate_registry()?;
@@ -228,6 +315,119 @@ pub async fn list_cards(
     .await
 }
 
+/// Fetches cards from a registry without printing them
+///
+/// # Arguments
+///
+/// * `registry` - Registry to list cards from
+/// * `name` - Name of card
+/// * `repository` - repository name
+/// * `version` - Card version
+/// * `uid` - Card uid
+/// * `limit` - Limit number of cards returned
+/// * `ignore_release_candidates` - Whether to ignore release candidates
+///
+pub async fn fetch_cards(
+    registry: &str,
+    name: Option<&str>,
+    repository: Option<&str>,
+    version: Option<&str>,
+    uid: Option<&str>,
+    limit: Option<i16>,
+    ignore_release_candidates: bool,
+) -> Result<types::ListCardResponse, anyhow::Error> {
+    let card_lister = CardLister {
+        registry_type: registry,
+        name,
+        repository,
+        version,
+        uid,
+        limit: limit.as_ref(),
+        tags: HashMap::new(),
+        max_date: None,
+        ignore_release_candidates: &ignore_release_candidates,
+        api_version: ApiVersion::current(),
+    };
+
+    card_lister.fetch_cards().await
+}
+
+/// Gets a single card from a registry. The most recent matching card is returned
+///
+/// # Arguments
+///
+/// * `registry` - Registry to get card from
+/// * `name` - Name of card
+/// * `repository` - repository name
+/// * `version` - Card version
+/// * `uid` - Card uid
+/// * `ignore_release_candidates` - Whether to ignore release candidates
+///
+pub async fn get_card(
+    registry: &str,
+    name: Option<&str>,
+    repository: Option<&str>,
+    version: Option<&str>,
+    uid: Option<&str>,
+    ignore_release_candidates: bool,
+) -> Result<types::Card, anyhow::Error> {
+    utils::check_args(name, repository, version, uid).await?;
+
+    let cards = fetch_cards(
+        registry,
+        name,
+        repository,
+        version,
+        uid,
+        Some(1),
+        ignore_release_candidates,
+    )
+    .await?;
+
+    cards.cards.into_iter().next().with_context(|| {
+        format!(
+            "No card found in {} registry for the provided arguments",
+            registry.red()
+        )
+    })
+}
+
+/// Gets a card by uid as the server returns it, keeping fields `types::Card` does not read so
+/// the card can be registered with another server
+///
+/// # Arguments
+///
+/// * `registry` - Registry to get card from
+/// * `uid` - Card uid
+///
+pub async fn get_raw_card(registry: &str, uid: &str) -> Result<serde_json::Value, anyhow::Error> {
+    let card_lister = CardLister {
+        registry_type: registry,
+        name: None,
+        repository: None,
+        version: None,
+        uid: Some(uid),
+        limit: Some(&1),
+        tags: HashMap::new(),
+        max_date: None,
+        ignore_release_candidates: &false,
+        api_version: ApiVersion::current(),
+    };
+
+    card_lister
+        .fetch_raw_cards()
+        .await?
+        .into_iter()
+        .next()
+        .with_context(|| {
+            format!(
+                "No card found in {} registry with uid {}",
+                registry.red(),
+                uid
+            )
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -246,6 +446,7 @@ mod tests {
             version: "1.0.0".to_string(),
             uid: "uid".to_string(),
             tags: HashMap::new(),
+            runcard_uid: None,
         };
         vec.push(card);
         let mock_response = types::ListCardResponse { cards: vec };
@@ -261,6 +462,7 @@ mod tests {
             tags: HashMap::new(),
             max_date: None,
             ignore_release_candidates: &false,
+            api_version: ApiVersion::V2,
         };
 
         let card_table = card_lister.parse_list_response(&string_response);
@@ -301,4 +503,80 @@ mod tests {
 
         mock.assert();
     }
+
+    #[tokio::test]
+    async fn test_get_card() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url);
+        }
+
+        let path = "./src/api/test_utils/list_cards.json";
+        let data = fs::read_to_string(path).expect("Unable to read file");
+
+        let mock = server
+            .mock("POST", "/opsml/cards/list")
+            .with_status(201)
+            .with_body(data)
+            .create();
+
+        let card = get_card("data", None, None, None, Some("1234567890"), false)
+            .await
+            .unwrap();
+        assert_eq!(card.name, "name");
+        assert_eq!(card.version, "1.0.0");
+
+        // invalid registry is rejected before making a request
+        assert!(
+            get_card("fake", None, None, None, Some("1234567890"), false)
+                .await
+                .is_err()
+        );
+
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_cards_v3() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url);
+        }
+
+        let path = "./src/api/test_utils/v3/list_cards.json";
+        let data = fs::read_to_string(path).expect("Unable to read file");
+
+        // v3 servers take a space rather than a repository and return a bare list
+        let mock = server
+            .mock("POST", "/opsml/api/card/list")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"registry_type": "model", "space": "space"}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(data)
+            .create();
+
+        let card_lister = CardLister {
+            registry_type: "model",
+            name: None,
+            repository: Some("space"),
+            version: None,
+            uid: None,
+            limit: None,
+            tags: HashMap::new(),
+            max_date: None,
+            ignore_release_candidates: &false,
+            api_version: ApiVersion::V3,
+        };
+        let response = card_lister.fetch_cards().await;
+
+        let card = response.unwrap().cards.remove(0);
+        assert_eq!(card.repository, "space");
+        assert_eq!(card.uid, "v3-1234567890");
+        mock.assert();
+    }
 }
@@ -1,8 +1,16 @@
 /// Copyright (c) Demml
 /// This source code is licensed under the MIT license found in the
 /// LICENSE file in the root directory of this source tree.
-use crate::api::commands::{DownloadModelArgs, ListCards, ModelMetadataArgs, ModelMetricArgs};
+use crate::api::commands::{
+    AssertMetricsArgs, BrowseArgs, BundleCreateArgs, BundleImportArgs, BundleVerifyArgs,
+    CompletionsArgs, DoctorArgs, DownloadCardArgs, DownloadModelArgs, ExportOciArgs, FilesCatArgs,
+    FilesGetArgs, FilesLsArgs, FilesPutArgs, FilesTreeArgs, GenDocsArgs, LaunchAppArgs, ListCards,
+    LogMetricArgs, LogMetricsArgs, MetricsMatrixArgs, MirrorArgs, ModelMetadataArgs,
+    ModelMetricArgs, RollbackArgs, WatchArgs,
+};
 
+use crate::api::api_version::ApiVersion;
+#[allow(unused_imports)]
 use clap::command;
 use clap::Parser;
 use clap::Subcommand;
@@ -10,6 +18,15 @@ use clap::Subcommand;
 #[derive(Parser)]
 #[command(about = "CLI tool for Interacting with an Opsml server")]
 pub struct Cli {
+    /// OpsML server api version. Negotiated with the server when not set
+    #[arg(
+        long = "api-version",
+        global = true,
+        value_enum,
+        env = "OPSML_API_VERSION"
+    )]
+    pub api_version: Option<ApiVersion>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -34,13 +51,133 @@ pub enum Commands {
     ///
     /// opsml-cli download-model --name model_name --version 1.0.0
     /// opsml-cli download-model --name model_name --version 1.0.0 --no-onnx
+    /// opsml-cli download-model --name model_name --version 1.0.0 --dry-run
+    /// opsml-cli download-model --uid uid --include '*.safetensors' --exclude 'optimizer*'
+    /// opsml-cli download-model --name model_name --version 1.0.0 --layout versioned --keep 3
     DownloadModel(DownloadModelArgs),
+    /// Point the `current` link of a model downloaded with --layout versioned at an earlier version
+    ///
+    /// # Example
+    ///
+    /// opsml-cli rollback --name model_name
+    /// opsml-cli rollback --name model_name --write-dir models --to 1.2.0
+    Rollback(RollbackArgs),
+    /// Download the artifacts of a card from the data, model, run or pipeline registry
+    ///
+    /// # Example
+    ///
+    /// opsml-cli download-card --registry data --uid uid
+    /// opsml-cli download-card --registry run --name run_name --repository repo --version 1.0.0
+    DownloadCard(DownloadCardArgs),
+    /// Package a model and its metadata as an OCI image layout for container builds
+    ///
+    /// # Example
+    ///
+    /// opsml-cli export-oci --uid uid --output model.tar
+    /// opsml-cli export-oci --name model_name --repository repo --version 1.0.0 --layout model-oci
+    ExportOci(ExportOciArgs),
+    /// Bundle models for air-gapped environments and import them on the other side
+    ///
+    /// # Example
+    ///
+    /// opsml-cli bundle create --uid uid1,uid2 --out models.tar.zst
+    /// opsml-cli bundle import models.tar.zst --into /mnt/opsml-cache
+    #[command(subcommand)]
+    Bundle(BundleCommands),
+    /// Copy a card with its files and metrics from one OpsML server to another, keeping its uid
+    /// and version. Re-running skips what the target already has
+    ///
+    /// # Example
+    ///
+    /// opsml-cli mirror --from staging --to prod --name model --repository repo --version 1.4.0
+    /// opsml-cli mirror --from https://opsml-staging.example.com --to prod --registry data --uid uid
+    Mirror(MirrorArgs),
+    /// Browse files in the OpsML artifact store
+    ///
+    /// # Example
+    ///
+    /// opsml-cli files ls uid --long
+    /// opsml-cli files tree opsml-root:/OPSML_MODEL_REGISTRY/repo/model/v1.0.0
+    #[command(subcommand)]
+    Files(FilesCommands),
     /// Retrieve model metrics
     ///
     /// # Example
     ///
-    /// opsml-cli get-model-metrics --name model_name --version 1.0.0
+    /// opsml-cli get-model-metrics --name model_name --repository repo --version 1.0.0
+    /// opsml-cli get-model-metrics --uid run_uid
+    /// opsml-cli get-model-metrics --uid run_uid --metric loss,accuracy --step-range 100..500 --chart
+    /// opsml-cli get-model-metrics --uid run_uid --export metrics.csv
+    /// opsml-cli get-model-metrics --uid run_uid --format prometheus
     GetModelMetrics(ModelMetricArgs),
+    /// Compare metrics across many runs in one table
+    ///
+    /// # Example
+    ///
+    /// opsml-cli metrics-matrix --uid a,b,c --lower-is-better mae
+    /// opsml-cli metrics-matrix --name model --repository repo --versions 1.0.0..1.4.0 --higher-is-better r2
+    MetricsMatrix(MetricsMatrixArgs),
+    /// Check model metrics against threshold rules. Exits non-zero if any rule fails
+    ///
+    /// # Example
+    ///
+    /// opsml-cli assert-metrics --uid run_uid --rule 'mae<5' --rule 'r2>=0.8'
+    /// opsml-cli assert-metrics --name model --repository repo --rules-file rules.yaml
+    AssertMetrics(AssertMetricsArgs),
+    /// Log a metric to a run
+    ///
+    /// # Example
+    ///
+    /// opsml-cli log-metric --run-uid run_uid --name latency_p99 --value 12.3 --step 1
+    LogMetric(LogMetricArgs),
+    /// Log metrics from a jsonl file to a run
+    ///
+    /// # Example
+    ///
+    /// opsml-cli log-metrics --file metrics.jsonl --run-uid run_uid
+    LogMetrics(LogMetricsArgs),
+    /// Browse registries, cards, files and metrics in an interactive terminal UI
+    ///
+    /// # Example
+    ///
+    /// opsml-cli browse --registry model
+    Browse(BrowseArgs),
+    /// Start and supervise a local opsml server
+    ///
+    /// # Example
+    ///
+    /// opsml-cli launch-app --port 8888 --login
+    /// opsml-cli launch-app --port 8888 -- python train.py
+    LaunchApp(LaunchAppArgs),
+    /// Poll a registry and run a hook for each new card version
+    ///
+    /// # Example
+    ///
+    /// opsml-cli watch --registry model --name model --repository repo --interval 60s --exec './reload.sh {uid} {version}'
+    /// opsml-cli watch --name model --repository repo --download --write-dir models --once
+    Watch(WatchArgs),
+    /// Check the tracking uri, network, server health, access and version compatibility
+    ///
+    /// # Example
+    ///
+    /// opsml-cli doctor
+    /// opsml-cli doctor --tracking-uri https://opsml.example.com
+    Doctor(DoctorArgs),
+    /// Generate a shell completion script
+    ///
+    /// Card names and repositories are completed from the server unless --static is set
+    ///
+    /// # Example
+    ///
+    /// opsml-cli completions bash > ~/.local/share/bash-completion/completions/opsml-cli
+    /// opsml-cli completions zsh > "${fpath[1]}/_opsml-cli"
+    Completions(CompletionsArgs),
+    /// Generate man pages and a Markdown command reference
+    ///
+    /// # Example
+    ///
+    /// opsml-cli gen-docs --out docs
+    GenDocs(GenDocsArgs),
 
     ///  Show opsml-cli version
     ///
@@ -57,6 +194,86 @@ pub enum Commands {
     Info,
 }
 
+impl Commands {
+    /// Whether the command talks to the server at `OPSML_TRACKING_URI` and needs its api version
+    pub fn uses_server(&self) -> bool {
+        !matches!(
+            self,
+            Commands::LaunchApp(_)
+                | Commands::Files(FilesCommands::Put(_))
+                | Commands::Bundle(BundleCommands::Import(_))
+                | Commands::Bundle(BundleCommands::Verify(_))
+                | Commands::Mirror(_)
+                | Commands::Rollback(_)
+                | Commands::Doctor(_)
+                | Commands::Completions(_)
+                | Commands::GenDocs(_)
+                | Commands::Version
+                | Commands::Info
+        )
+    }
+}
+
+#[derive(Subcommand)]
+pub enum FilesCommands {
+    /// List files under a card or opsml-root path
+    ///
+    /// # Example
+    ///
+    /// opsml-cli files ls uid --recursive --long
+    Ls(FilesLsArgs),
+    /// Show files under a card or opsml-root path as a tree
+    ///
+    /// # Example
+    ///
+    /// opsml-cli files tree uid --registry data
+    Tree(FilesTreeArgs),
+    /// Download a single file
+    ///
+    /// # Example
+    ///
+    /// opsml-cli files get opsml-root:/OPSML_MODEL_REGISTRY/repo/model/v1.0.0/config.json config.json
+    Get(FilesGetArgs),
+    /// Stream a file to stdout without writing it to disk
+    ///
+    /// # Example
+    ///
+    /// opsml-cli files cat opsml-root:/OPSML_MODEL_REGISTRY/repo/model/v1.0.0/config.json | jq
+    Cat(FilesCatArgs),
+    /// Upload a file straight to the storage bucket set with OPSML_STORAGE_URI
+    ///
+    /// # Example
+    ///
+    /// opsml-cli files put model.onnx opsml-root:/OPSML_MODEL_REGISTRY/repo/model/v1.0.0/model.onnx
+    Put(FilesPutArgs),
+}
+
+#[derive(Subcommand)]
+pub enum BundleCommands {
+    /// Write model cards, metadata, metrics and files to a reproducible tar.zst with a manifest of
+    /// file hashes
+    ///
+    /// # Example
+    ///
+    /// opsml-cli bundle create --uid uid1,uid2 --out models.tar.zst
+    Create(BundleCreateArgs),
+    /// Verify a bundle and copy it into a directory or a storage bucket, and register its cards
+    /// with a server
+    ///
+    /// # Example
+    ///
+    /// opsml-cli bundle import models.tar.zst --into /mnt/opsml-cache
+    /// opsml-cli bundle import models.tar.zst --into s3://bucket/opsml --server https://opsml.internal
+    /// opsml-cli bundle import models.tar.zst --server airgap
+    Import(BundleImportArgs),
+    /// Check every file of a bundle against its manifest. No server is needed
+    ///
+    /// # Example
+    ///
+    /// opsml-cli bundle verify models.tar.zst
+    Verify(BundleVerifyArgs),
+}
+
 pub const LOGO_TEXT: &str = "
  ██████  ██████  ███████ ███    ███ ██             ██████ ██      ██ 
 ██    ██ ██   ██ ██      ████  ████ ██            ██      ██      ██ 
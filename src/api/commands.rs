@@ -1,20 +1,23 @@
 /// Copyright (c) Shipt, Inc.
 /// This source code is licensed under the MIT license found in the
 /// LICENSE file in the root directory of this source tree.
-use clap::Args;
+use crate::api::completions;
+use crate::api::types::{MetricAggregation, MetricFormat, ModelLayout};
+use clap::{ArgGroup, Args};
+use clap_complete::{ArgValueCandidates, Shell};
 
 #[derive(Args)]
 pub struct ListCards {
     /// Name of the registry (data, model, run, etc)
-    #[arg(long = "registry")]
+    #[arg(long = "registry", add = ArgValueCandidates::new(completions::registry_candidates))]
     pub registry: String,
 
     /// Name given to a card
-    #[arg(long = "name")]
+    #[arg(long = "name", add = ArgValueCandidates::new(completions::name_candidates))]
     pub name: Option<String>,
 
     /// repository name
-    #[arg(long = "repository")]
+    #[arg(long = "repository", add = ArgValueCandidates::new(completions::repository_candidates))]
     pub repository: Option<String>,
 
     /// Card version
@@ -49,7 +52,7 @@ pub struct ListCards {
 #[derive(Args)]
 pub struct ModelMetadataArgs {
     /// Name given to card
-    #[arg(long = "name")]
+    #[arg(long = "name", add = ArgValueCandidates::new(completions::name_candidates))]
     pub name: Option<String>,
 
     /// Card version
@@ -57,7 +60,7 @@ pub struct ModelMetadataArgs {
     pub version: Option<String>,
 
     /// Card repository
-    #[arg(long = "repository")]
+    #[arg(long = "repository", add = ArgValueCandidates::new(completions::repository_candidates))]
     pub repository: Option<String>,
 
     /// Card uid
@@ -76,7 +79,7 @@ pub struct ModelMetadataArgs {
 #[derive(Args)]
 pub struct DownloadModelArgs {
     /// Name given to card
-    #[arg(long = "name")]
+    #[arg(long = "name", add = ArgValueCandidates::new(completions::name_candidates))]
     pub name: Option<String>,
 
     /// Card version
@@ -84,7 +87,7 @@ pub struct DownloadModelArgs {
     pub version: Option<String>,
 
     /// Card repository
-    #[arg(long = "repository")]
+    #[arg(long = "repository", add = ArgValueCandidates::new(completions::repository_candidates))]
     pub repository: Option<String>,
 
     /// Card uid
@@ -110,13 +113,444 @@ pub struct DownloadModelArgs {
     /// ignore release candidate
     #[arg(long = "ignore_release_candidate", default_value = "false")]
     pub ignore_release_candidates: bool,
+
+    /// Print the planned downloads without transferring any files
+    #[arg(long = "dry-run", default_value = "false")]
+    pub dry_run: bool,
+
+    /// Only download files matching this glob (relative to the model root). Can be repeated
+    #[arg(long = "include")]
+    pub include: Vec<String>,
+
+    /// Skip files matching this glob (relative to the model root). Can be repeated
+    #[arg(long = "exclude")]
+    pub exclude: Vec<String>,
+
+    /// Write files flat into the write directory, or to <write-dir>/<name>/<version> with a
+    /// `current` symlink to the latest download
+    #[arg(long = "layout", value_enum, default_value = "flat")]
+    pub layout: ModelLayout,
+
+    /// Versions kept with --layout versioned. Older versions are removed after a download
+    #[arg(long = "keep", default_value = "3", value_parser = clap::value_parser!(u16).range(1..))]
+    pub keep: u16,
 }
 
 #[derive(Args)]
-pub struct ModelMetricArgs {
+pub struct RollbackArgs {
+    /// Name of the model
+    #[arg(long = "name", add = ArgValueCandidates::new(completions::name_candidates))]
+    pub name: String,
+
+    /// Write directory the model was downloaded to with --layout versioned
+    #[arg(long = "write-dir", default_value = "models")]
+    pub write_dir: String,
+
+    /// Version to roll back to. Defaults to the newest version older than the current one
+    #[arg(long = "to")]
+    pub to: Option<String>,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("destination").required(true).args(["output", "layout"])))]
+pub struct ExportOciArgs {
+    /// Name given to card
+    #[arg(long = "name", add = ArgValueCandidates::new(completions::name_candidates))]
+    pub name: Option<String>,
+
+    /// Card version
+    #[arg(long = "version")]
+    pub version: Option<String>,
+
+    /// Card repository
+    #[arg(long = "repository", add = ArgValueCandidates::new(completions::repository_candidates))]
+    pub repository: Option<String>,
+
     /// Card uid
     #[arg(long = "uid")]
-    pub uid: String,
+    pub uid: Option<String>,
+
+    /// Tar file to write the OCI image layout to
+    #[arg(long = "output")]
+    pub output: Option<String>,
+
+    /// Directory to write the OCI image layout to
+    #[arg(long = "layout")]
+    pub layout: Option<String>,
+
+    /// Directory the model is staged in before packaging
+    #[arg(long = "write-dir", default_value = "models")]
+    pub write_dir: String,
+
+    /// CPU architecture recorded in the image config
+    #[arg(long = "architecture", default_value = "amd64")]
+    pub architecture: String,
+
+    /// Boolean indicating whether to download onnx or trained model
+    #[arg(long = "onnx", default_value = "false")]
+    pub onnx: bool,
+
+    /// Boolean indicating whether to use the quantized version of the model (huggingface only)
+    #[arg(long = "quantize", default_value = "false")]
+    pub quantize: bool,
+
+    /// Boolean indicating whether to download any preprocessors with the model
+    #[arg(long = "preprocessor", default_value = "false")]
+    pub preprocessor: bool,
+
+    /// ignore release candidate
+    #[arg(long = "ignore_release_candidate", default_value = "false")]
+    pub ignore_release_candidates: bool,
+}
+
+#[derive(Args)]
+pub struct MirrorArgs {
+    /// Server to copy from. A url, or a name read from OPSML_TRACKING_URI_<NAME>
+    #[arg(long = "from")]
+    pub from: String,
+
+    /// Server to copy to. A url, or a name read from OPSML_TRACKING_URI_<NAME>
+    #[arg(long = "to")]
+    pub to: String,
+
+    /// Name of the registry (data, model, run, pipeline)
+    #[arg(long = "registry", default_value = "model", add = ArgValueCandidates::new(completions::registry_candidates))]
+    pub registry: String,
+
+    /// Name given to card
+    #[arg(long = "name")]
+    pub name: Option<String>,
+
+    /// Card version
+    #[arg(long = "version")]
+    pub version: Option<String>,
+
+    /// Card repository
+    #[arg(long = "repository")]
+    pub repository: Option<String>,
+
+    /// Card uid
+    #[arg(long = "uid")]
+    pub uid: Option<String>,
+
+    /// ignore release candidate
+    #[arg(long = "ignore_release_candidate", default_value = "false")]
+    pub ignore_release_candidates: bool,
+}
+
+#[derive(Args)]
+pub struct BundleCreateArgs {
+    /// Model card uids to bundle, separated by commas
+    #[arg(
+        long = "uid",
+        required = true,
+        use_value_delimiter = true,
+        value_delimiter = ','
+    )]
+    pub uid: Vec<String>,
+
+    /// Bundle file to write, e.g. models.tar.zst
+    #[arg(long = "out")]
+    pub out: String,
+}
+
+#[derive(Args)]
+pub struct BundleImportArgs {
+    /// Bundle file written by `bundle create`
+    pub bundle: String,
+
+    /// Directory or storage uri (s3://, gs://, az://, file://) to import into
+    #[arg(long = "into", required_unless_present = "server")]
+    pub into: Option<String>,
+
+    /// Server to register the cards with. A url, or a name read from OPSML_TRACKING_URI_<NAME>.
+    /// Files are uploaded through it unless --into is a storage uri
+    #[arg(long = "server")]
+    pub server: Option<String>,
+}
+
+#[derive(Args)]
+pub struct BundleVerifyArgs {
+    /// Bundle file written by `bundle create`
+    pub bundle: String,
+}
+
+#[derive(Args)]
+pub struct DownloadCardArgs {
+    /// Name of the registry (data, model, run, pipeline)
+    #[arg(long = "registry", add = ArgValueCandidates::new(completions::registry_candidates))]
+    pub registry: String,
+
+    /// Name given to card
+    #[arg(long = "name", add = ArgValueCandidates::new(completions::name_candidates))]
+    pub name: Option<String>,
+
+    /// Card version
+    #[arg(long = "version")]
+    pub version: Option<String>,
+
+    /// Card repository
+    #[arg(long = "repository", add = ArgValueCandidates::new(completions::repository_candidates))]
+    pub repository: Option<String>,
+
+    /// Card uid
+    #[arg(long = "uid")]
+    pub uid: Option<String>,
+
+    /// Write directory
+    #[arg(long = "write-dir", default_value = "artifacts")]
+    pub write_dir: String,
+
+    /// ignore release candidate
+    #[arg(long = "ignore_release_candidate", default_value = "false")]
+    pub ignore_release_candidates: bool,
+
+    /// Print the planned downloads without transferring any files
+    #[arg(long = "dry-run", default_value = "false")]
+    pub dry_run: bool,
+
+    /// Only download files matching this glob (relative to the card root). Can be repeated
+    #[arg(long = "include")]
+    pub include: Vec<String>,
+
+    /// Skip files matching this glob (relative to the card root). Can be repeated
+    #[arg(long = "exclude")]
+    pub exclude: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct FilesLsArgs {
+    /// Card uid or opsml-root path (opsml-root:/...)
+    pub target: String,
+
+    /// Registry used to resolve a card uid (data, model, run, pipeline)
+    #[arg(long = "registry", default_value = "model", add = ArgValueCandidates::new(completions::registry_candidates))]
+    pub registry: String,
+
+    /// List all nested files instead of the first level
+    #[arg(long = "recursive", short = 'r', default_value = "false")]
+    pub recursive: bool,
+
+    /// Show file size and modification time columns (`-` when unknown)
+    #[arg(long = "long", short = 'l', default_value = "false")]
+    pub long: bool,
+}
+
+#[derive(Args)]
+pub struct FilesTreeArgs {
+    /// Card uid or opsml-root path (opsml-root:/...)
+    pub target: String,
+
+    /// Registry used to resolve a card uid (data, model, run, pipeline)
+    #[arg(long = "registry", default_value = "model", add = ArgValueCandidates::new(completions::registry_candidates))]
+    pub registry: String,
+}
+
+#[derive(Args)]
+pub struct FilesGetArgs {
+    /// Remote path of the file
+    pub rpath: String,
+
+    /// Local path to write the file to
+    pub lpath: String,
+}
+
+#[derive(Args)]
+pub struct FilesPutArgs {
+    /// Local file to upload
+    pub lpath: String,
+
+    /// Remote opsml-root path to write to (opsml-root:/...)
+    pub rpath: String,
+}
+
+#[derive(Args)]
+pub struct FilesCatArgs {
+    /// Remote path of the file
+    pub rpath: String,
+
+    /// Only print the first N lines
+    #[arg(long = "head")]
+    pub head: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct ModelMetricArgs {
+    /// Run uid the metrics were logged to
+    #[arg(long = "uid")]
+    pub uid: Option<String>,
+
+    /// Name of the model card
+    #[arg(long = "name", add = ArgValueCandidates::new(completions::name_candidates))]
+    pub name: Option<String>,
+
+    /// Model card repository
+    #[arg(long = "repository", add = ArgValueCandidates::new(completions::repository_candidates))]
+    pub repository: Option<String>,
+
+    /// Model card version. Most recent version is used if not provided
+    #[arg(long = "version")]
+    pub version: Option<String>,
+
+    /// ignore release candidate
+    #[arg(long = "ignore_release_candidate", default_value = "false")]
+    pub ignore_release_candidates: bool,
+
+    /// Only show these metrics
+    #[arg(long = "metric", use_value_delimiter = true, value_delimiter = ',')]
+    pub metric: Option<Vec<String>>,
+
+    /// Only show steps in this inclusive range (start..end, start.. or ..end)
+    #[arg(long = "step-range")]
+    pub step_range: Option<String>,
+
+    /// Show the value at the last step of each metric
+    #[arg(
+        long = "last",
+        group = "aggregation",
+        conflicts_with = "format",
+        default_value = "false"
+    )]
+    pub last: bool,
+
+    /// Show the minimum value of each metric
+    #[arg(
+        long = "min",
+        group = "aggregation",
+        conflicts_with = "format",
+        default_value = "false"
+    )]
+    pub min: bool,
+
+    /// Show the maximum value of each metric
+    #[arg(
+        long = "max",
+        group = "aggregation",
+        conflicts_with = "format",
+        default_value = "false"
+    )]
+    pub max: bool,
+
+    /// Show the mean value of each metric
+    #[arg(
+        long = "mean",
+        group = "aggregation",
+        conflicts_with = "format",
+        default_value = "false"
+    )]
+    pub mean: bool,
+
+    /// Draw a sparkline of each metric over its steps
+    #[arg(
+        long = "chart",
+        conflicts_with_all = ["aggregation", "format"],
+        default_value = "false"
+    )]
+    pub chart: bool,
+
+    /// Output format written to stdout
+    #[arg(long = "format", value_enum, default_value = "table")]
+    pub format: MetricFormat,
+
+    /// Export metrics to a file (.csv, .parquet, .json, .prom, or .txt with --format)
+    #[arg(long = "export")]
+    pub export: Option<String>,
+}
+
+impl ModelMetricArgs {
+    /// Gets the aggregation selected by the --last, --min, --max and --mean flags
+    pub fn aggregation(&self) -> Option<MetricAggregation> {
+        if self.last {
+            Some(MetricAggregation::Last)
+        } else if self.min {
+            Some(MetricAggregation::Min)
+        } else if self.max {
+            Some(MetricAggregation::Max)
+        } else if self.mean {
+            Some(MetricAggregation::Mean)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct BrowseArgs {
+    /// Registry to open. Starts on the registry list when omitted
+    #[arg(long = "registry", add = ArgValueCandidates::new(completions::registry_candidates))]
+    pub registry: Option<String>,
+
+    /// Write directory for downloads started from the browser
+    #[arg(long = "write-dir", default_value = "artifacts")]
+    pub write_dir: String,
+}
+
+#[derive(Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate the completion script for
+    #[arg(value_enum)]
+    pub shell: Shell,
+
+    /// Generate a static script without server-backed name and repository completions
+    #[arg(long = "static", default_value = "false")]
+    pub static_only: bool,
+}
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Name of the registry to watch
+    #[arg(long = "registry", default_value = "model", add = ArgValueCandidates::new(completions::registry_candidates))]
+    pub registry: String,
+
+    /// Name of the card to watch
+    #[arg(long = "name", add = ArgValueCandidates::new(completions::name_candidates))]
+    pub name: Option<String>,
+
+    /// Repository of the card to watch
+    #[arg(long = "repository", add = ArgValueCandidates::new(completions::repository_candidates))]
+    pub repository: Option<String>,
+
+    /// Polling interval, e.g. 30s, 5m or 1h
+    #[arg(long = "interval", default_value = "60s")]
+    pub interval: String,
+
+    /// Command to run for each new version. {uid}, {version}, {name}, {repository} and {registry} are replaced
+    #[arg(long = "exec")]
+    pub exec: Option<String>,
+
+    /// Download each new version before running the hook
+    #[arg(long = "download", default_value = "false")]
+    pub download: bool,
+
+    /// Write directory for downloads
+    #[arg(long = "write-dir", default_value = "models")]
+    pub write_dir: String,
+
+    /// File recording the last seen version so restarts do not fire again
+    #[arg(long = "state-file", default_value = ".opsml-watch.json")]
+    pub state_file: String,
+
+    /// Poll once and exit
+    #[arg(long = "once", default_value = "false")]
+    pub once: bool,
+
+    /// ignore release candidate
+    #[arg(long = "ignore_release_candidate", default_value = "false")]
+    pub ignore_release_candidates: bool,
+}
+
+#[derive(Args)]
+pub struct DoctorArgs {
+    /// Tracking uri to check instead of OPSML_TRACKING_URI
+    #[arg(long = "tracking-uri")]
+    pub tracking_uri: Option<String>,
+}
+
+#[derive(Args)]
+pub struct GenDocsArgs {
+    /// Directory to write the Markdown reference and man pages to
+    #[arg(long = "out", default_value = "docs")]
+    pub out: String,
 }
 
 #[derive(Args)]
@@ -128,4 +562,120 @@ pub struct LaunchAppArgs {
     /// Default port to use with the opsml server
     #[arg(long = "port", default_value = "8888")]
     pub port: i32,
+
+    /// Host the server is reachable on
+    #[arg(long = "host", default_value = "127.0.0.1")]
+    pub host: String,
+
+    /// Command that starts the server instead of opsml-uvicorn-server. {port} is replaced with the port
+    #[arg(long = "command")]
+    pub command: Option<String>,
+
+    /// How long to wait for the server health check, e.g. 60s
+    #[arg(long = "startup-timeout", default_value = "60s")]
+    pub startup_timeout: String,
+
+    /// Command to run against the server. The server stops when it exits
+    #[arg(last = true)]
+    pub run: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct MetricsMatrixArgs {
+    /// Run uids to compare
+    #[arg(long = "uid", use_value_delimiter = true, value_delimiter = ',')]
+    pub uid: Option<Vec<String>>,
+
+    /// Name of the model card
+    #[arg(long = "name", add = ArgValueCandidates::new(completions::name_candidates))]
+    pub name: Option<String>,
+
+    /// Model card repository
+    #[arg(long = "repository", add = ArgValueCandidates::new(completions::repository_candidates))]
+    pub repository: Option<String>,
+
+    /// Inclusive range of model versions to compare (start..end, start.. or ..end)
+    #[arg(long = "versions")]
+    pub versions: Option<String>,
+
+    /// Only show these metrics
+    #[arg(long = "metric", use_value_delimiter = true, value_delimiter = ',')]
+    pub metric: Option<Vec<String>>,
+
+    /// Metrics where the lowest value is best
+    #[arg(
+        long = "lower-is-better",
+        use_value_delimiter = true,
+        value_delimiter = ','
+    )]
+    pub lower_is_better: Vec<String>,
+
+    /// Metrics where the highest value is best
+    #[arg(
+        long = "higher-is-better",
+        use_value_delimiter = true,
+        value_delimiter = ','
+    )]
+    pub higher_is_better: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct AssertMetricsArgs {
+    /// Run uid the metrics were logged to
+    #[arg(long = "uid")]
+    pub uid: Option<String>,
+
+    /// Name of the model card
+    #[arg(long = "name", add = ArgValueCandidates::new(completions::name_candidates))]
+    pub name: Option<String>,
+
+    /// Model card repository
+    #[arg(long = "repository", add = ArgValueCandidates::new(completions::repository_candidates))]
+    pub repository: Option<String>,
+
+    /// Model card version. Most recent version is used if not provided
+    #[arg(long = "version")]
+    pub version: Option<String>,
+
+    /// Threshold rule such as 'mae<5' or 'r2>=0.8'. Can be repeated
+    #[arg(long = "rule")]
+    pub rule: Vec<String>,
+
+    /// Yaml file with a list of rules
+    #[arg(long = "rules-file")]
+    pub rules_file: Option<String>,
+
+    /// ignore release candidate
+    #[arg(long = "ignore_release_candidate", default_value = "false")]
+    pub ignore_release_candidates: bool,
+}
+
+#[derive(Args)]
+pub struct LogMetricArgs {
+    /// Run uid to log the metric to
+    #[arg(long = "run-uid")]
+    pub run_uid: String,
+
+    /// Metric name
+    #[arg(long = "name")]
+    pub name: String,
+
+    /// Metric value
+    #[arg(long = "value", allow_negative_numbers = true)]
+    pub value: f64,
+
+    /// Step the metric was recorded at
+    #[arg(long = "step")]
+    pub step: Option<i64>,
+}
+
+#[derive(Args)]
+pub struct LogMetricsArgs {
+    /// Jsonl file with one metric per line ({"name": "mae", "value": 5, "step": 1})
+    #[arg(long = "file")]
+    pub file: String,
+
+    /// Run uid for metrics without a run_uid field
+    #[arg(long = "run-uid")]
+    pub run_uid: Option<String>,
 }
@@ -0,0 +1,303 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::cards::{self, REGISTRIES};
+use crate::api::types;
+use crate::api::utils::{cache_dir, now_secs};
+use anyhow::{Context, Result};
+use clap_complete::env::Shells;
+use clap_complete::{CompletionCandidate, Shell};
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// Environment variable the completion scripts use to call back into opsml-cli
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+const BIN_NAME: &str = "opsml-cli";
+
+/// Seconds cached card names and repositories are reused before querying the server again
+const CACHE_TTL_SECS: u64 = 300;
+
+/// Registries queried for name and repository completions
+const COMPLETION_REGISTRIES: [&str; 4] = ["data", "model", "run", "pipeline"];
+
+/// How long completions wait on the server before falling back to stale caches
+const SERVER_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Writes a completion script for a shell
+///
+/// # Arguments
+///
+/// * `cmd` - Clap command to complete
+/// * `shell` - Shell to generate the script for
+/// * `static_only` - Generate a static script without server completions
+/// * `buf` - Writer for the script
+///
+pub fn write_completion_script(
+    cmd: &mut clap::Command,
+    shell: Shell,
+    static_only: bool,
+    buf: &mut dyn Write,
+) -> Result<(), anyhow::Error> {
+    if static_only {
+        clap_complete::generate(shell, cmd, BIN_NAME, buf);
+        return Ok(());
+    }
+
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell.to_string())
+        .with_context(|| format!("Dynamic completions are not supported for {}", shell))?;
+
+    completer
+        .write_registration(COMPLETE_VAR, BIN_NAME, BIN_NAME, BIN_NAME, buf)
+        .with_context(|| "Failed to write completion script")
+}
+
+/// Completion candidates for `--registry`
+pub fn registry_candidates() -> Vec<CompletionCandidate> {
+    REGISTRIES
+        .iter()
+        .map(|registry| CompletionCandidate::new(*registry))
+        .collect()
+}
+
+/// Completion candidates for `--name`, queried from the server
+pub fn name_candidates() -> Vec<CompletionCandidate> {
+    card_candidates(|cache| cache.names)
+}
+
+/// Completion candidates for `--repository`, queried from the server
+pub fn repository_candidates() -> Vec<CompletionCandidate> {
+    card_candidates(|cache| cache.repositories)
+}
+
+/// Gets the registry already typed on the command line being completed
+///
+/// # Arguments
+///
+/// * `args` - Words of the command line being completed
+///
+fn typed_registry(args: &[String]) -> Option<&str> {
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == "--registry" {
+            args.get(i + 1).map(|registry| registry.as_str())
+        } else {
+            arg.strip_prefix("--registry=")
+        }
+    })
+}
+
+/// Collects values from the cached cards of each registry, or only the registry passed with
+/// `--registry`. Completion must never fail loudly, so a missing tracking uri or an unreachable
+/// server produces no candidates. Servers that do not answer in time are completed from stale
+/// caches
+///
+/// # Arguments
+///
+/// * `select` - Selects the values to complete from a registry cache
+///
+fn card_candidates(select: fn(types::CompletionCache) -> Vec<String>) -> Vec<CompletionCandidate> {
+    let Ok(tracking_uri) = env::var("OPSML_TRACKING_URI") else {
+        return Vec::new();
+    };
+    let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    else {
+        return Vec::new();
+    };
+
+    let args: Vec<String> = env::args().collect();
+    let registries: Vec<&str> = match typed_registry(&args) {
+        Some(registry) if COMPLETION_REGISTRIES.contains(&registry) => vec![registry],
+        _ => COMPLETION_REGISTRIES.to_vec(),
+    };
+
+    let cache_dir = &cache_dir();
+    let tracking_uri = &tracking_uri;
+    let caches = runtime.block_on(futures::future::join_all(registries.into_iter().map(
+        |registry| async move {
+            let loaded = tokio::time::timeout(
+                SERVER_TIMEOUT,
+                load_cache(cache_dir, registry, tracking_uri),
+            )
+            .await;
+
+            match loaded {
+                Ok(Ok(cache)) => Some(cache),
+                _ => read_stale_cache(&cache_dir.join(format!("{}.json", registry)), tracking_uri),
+            }
+        },
+    )));
+
+    let mut values = BTreeSet::new();
+    for cache in caches.into_iter().flatten() {
+        values.extend(select(cache));
+    }
+
+    values.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Reads a registry cache of the current server regardless of its age
+///
+/// # Arguments
+///
+/// * `path` - Cache file
+/// * `tracking_uri` - Current opsml tracking uri
+///
+fn read_stale_cache(path: &Path, tracking_uri: &str) -> Option<types::CompletionCache> {
+    let cache: types::CompletionCache =
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+
+    (cache.tracking_uri == tracking_uri).then_some(cache)
+}
+
+/// Reads a registry cache if it is fresh and belongs to the current server
+///
+/// # Arguments
+///
+/// * `path` - Cache file
+/// * `tracking_uri` - Current opsml tracking uri
+/// * `now` - Current epoch seconds
+///
+fn read_cache(path: &Path, tracking_uri: &str, now: u64) -> Option<types::CompletionCache> {
+    let cache = read_stale_cache(path, tracking_uri)?;
+
+    let fresh = now.saturating_sub(cache.fetched_at) < CACHE_TTL_SECS;
+    fresh.then_some(cache)
+}
+
+/// Loads card names and repositories for a registry, refreshing the on-disk cache when it is stale
+///
+/// # Arguments
+///
+/// * `dir` - Cache directory
+/// * `registry` - Registry to complete from
+/// * `tracking_uri` - Current opsml tracking uri
+///
+pub async fn load_cache(
+    dir: &Path,
+    registry: &str,
+    tracking_uri: &str,
+) -> Result<types::CompletionCache, anyhow::Error> {
+    let path = dir.join(format!("{}.json", registry));
+    let now = now_secs();
+
+    if let Some(cache) = read_cache(&path, tracking_uri, now) {
+        return Ok(cache);
+    }
+
+    let response = cards::fetch_cards(registry, None, None, None, None, None, false).await?;
+    let names = response
+        .cards
+        .iter()
+        .map(|card| card.name.clone())
+        .collect::<BTreeSet<_>>();
+    let repositories = response
+        .cards
+        .iter()
+        .map(|card| card.repository.clone())
+        .collect::<BTreeSet<_>>();
+
+    let cache = types::CompletionCache {
+        tracking_uri: tracking_uri.to_string(),
+        fetched_at: now,
+        names: names.into_iter().collect(),
+        repositories: repositories.into_iter().collect(),
+    };
+
+    // a cache that cannot be written only costs a server round trip next time
+    if fs::create_dir_all(dir).is_ok() {
+        let _ = fs::write(&path, serde_json::to_string(&cache)?);
+    }
+
+    Ok(cache)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::cli::Cli;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_write_completion_script() {
+        let mut buf = Vec::new();
+        write_completion_script(&mut Cli::command(), Shell::Bash, false, &mut buf).unwrap();
+        let script = String::from_utf8(buf).unwrap();
+        assert!(script.contains(COMPLETE_VAR));
+        assert!(script.contains(BIN_NAME));
+
+        let mut buf = Vec::new();
+        write_completion_script(&mut Cli::command(), Shell::Zsh, true, &mut buf).unwrap();
+        let script = String::from_utf8(buf).unwrap();
+        assert!(script.contains("#compdef opsml-cli"));
+        assert!(script.contains("list-cards"));
+    }
+
+    #[test]
+    fn test_typed_registry() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<String>>();
+
+        assert_eq!(
+            typed_registry(&args(
+                "opsml-cli -- opsml-cli list-cards --registry run --name"
+            )),
+            Some("run")
+        );
+        assert_eq!(
+            typed_registry(&args(
+                "opsml-cli -- opsml-cli list-cards --registry=data --name"
+            )),
+            Some("data")
+        );
+        assert_eq!(
+            typed_registry(&args("opsml-cli -- opsml-cli list-cards --name")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_cache() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", &url);
+        }
+
+        let path = "./src/api/test_utils/list_cards.json";
+        let data = fs::read_to_string(path).expect("Unable to read file");
+
+        // the second load is served from the cache
+        let mock = server
+            .mock("POST", "/opsml/cards/list")
+            .with_status(201)
+            .with_body(data)
+            .expect(1)
+            .create();
+
+        let dir = env::temp_dir().join(format!("opsml-completions-{}", uuid::Uuid::new_v4()));
+        let cache = load_cache(&dir, "data", &url).await.unwrap();
+        assert_eq!(cache.names, vec!["name"]);
+        assert_eq!(cache.repositories, vec!["repository"]);
+
+        let cached = load_cache(&dir, "data", &url).await.unwrap();
+        assert_eq!(cached.names, cache.names);
+        mock.assert();
+
+        // caches from another server or past the ttl are ignored
+        let cache_path = dir.join("data.json");
+        assert!(read_cache(&cache_path, &url, cache.fetched_at).is_some());
+        assert!(read_cache(&cache_path, "http://other", cache.fetched_at).is_none());
+        assert!(read_cache(&cache_path, &url, cache.fetched_at + CACHE_TTL_SECS).is_none());
+        assert!(read_stale_cache(&cache_path, &url).is_some());
+        assert!(read_stale_cache(&cache_path, "http://other").is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,268 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use std::fs;
+use std::path::Path;
+
+/// File name of the Markdown reference written by `gen-docs`
+pub const REFERENCE_FILE: &str = "cli-reference.md";
+
+const EXAMPLE_HEADING: &str = "# Example";
+
+/// Splits a command's long help into its description and the commands listed under `# Example`.
+/// Clap joins consecutive doc comment lines, so examples are split again on the binary name
+///
+/// # Arguments
+///
+/// * `cmd` - Command to describe
+/// * `bin` - Binary name every example starts with
+///
+fn description_and_examples(cmd: &clap::Command, bin: &str) -> (String, Vec<String>) {
+    let help = cmd
+        .get_long_about()
+        .or(cmd.get_about())
+        .map(|help| help.to_string())
+        .unwrap_or_default();
+
+    match help.split_once(EXAMPLE_HEADING) {
+        Some((description, examples)) => (
+            description.trim().to_string(),
+            examples
+                .split(&format!("{} ", bin))
+                .map(str::trim)
+                .filter(|example| !example.is_empty())
+                .map(|example| format!("{} {}", bin, example))
+                .collect(),
+        ),
+        None => (help.trim().to_string(), Vec::new()),
+    }
+}
+
+/// Escapes text for use inside a Markdown table cell
+fn table_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Renders the option table of a command
+///
+/// # Arguments
+///
+/// * `cmd` - Command whose arguments are rendered
+///
+fn render_arguments(cmd: &clap::Command) -> String {
+    let args = cmd
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set() && arg.get_id() != "help")
+        .collect::<Vec<_>>();
+
+    if args.is_empty() {
+        return String::new();
+    }
+
+    let mut table = String::from("| Option | Description | Default |\n| --- | --- | --- |\n");
+    for arg in args {
+        let value_name = arg
+            .get_value_names()
+            .and_then(|names| names.first())
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| arg.get_id().to_string().to_uppercase());
+
+        let option = match arg.get_long() {
+            Some(long) if arg.get_action().takes_values() => {
+                format!("`--{} <{}>`", long, value_name)
+            }
+            Some(long) => format!("`--{}`", long),
+            None => format!("`<{}>`", value_name),
+        };
+
+        let mut description = arg
+            .get_help()
+            .map(|help| help.to_string())
+            .unwrap_or_default();
+        let possible_values = arg
+            .get_possible_values()
+            .iter()
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_string())
+            .collect::<Vec<_>>();
+        if arg.get_action().takes_values() && !possible_values.is_empty() {
+            description.push_str(&format!(" (one of: {})", possible_values.join(", ")));
+        }
+        if arg.is_required_set() {
+            description = format!("**Required.** {}", description);
+        }
+
+        let default = arg
+            .get_default_values()
+            .iter()
+            .map(|value| format!("`{}`", value.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        table.push_str(&format!(
+            "| {} | {} | {} |\n",
+            option,
+            table_cell(description.trim()),
+            default
+        ));
+    }
+
+    table
+}
+
+/// Renders a command and its subcommands as Markdown sections
+///
+/// # Arguments
+///
+/// * `cmd` - Command to render
+/// * `path` - Full invocation of the command, e.g. `opsml-cli files ls`
+/// * `out` - Markdown output
+///
+fn render_command(cmd: &clap::Command, path: &str, out: &mut String) {
+    let bin = path.split(' ').next().unwrap_or_default();
+    let (description, examples) = description_and_examples(cmd, bin);
+
+    out.push_str(&format!("## `{}`\n\n", path));
+    if !description.is_empty() {
+        out.push_str(&format!("{}\n\n", description));
+    }
+
+    let usage = cmd.clone().render_usage().to_string();
+    let usage = usage.trim_start_matches("Usage: ");
+    out.push_str(&format!("```console\n{}\n```\n\n", usage));
+
+    let arguments = render_arguments(cmd);
+    if !arguments.is_empty() {
+        out.push_str(&format!("{}\n", arguments));
+    }
+
+    let subcommands = cmd
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set())
+        .collect::<Vec<_>>();
+    if !subcommands.is_empty() {
+        out.push_str("Subcommands:\n\n");
+        for sub in subcommands.iter() {
+            let about = sub
+                .get_about()
+                .map(|about| about.to_string())
+                .unwrap_or_default();
+            out.push_str(&format!("- `{} {}` - {}\n", path, sub.get_name(), about));
+        }
+        out.push('\n');
+    }
+
+    if !examples.is_empty() {
+        out.push_str("Examples:\n\n```console\n");
+        for example in examples {
+            out.push_str(&format!("$ {}\n", example));
+        }
+        out.push_str("```\n\n");
+    }
+
+    for sub in subcommands {
+        render_command(sub, &format!("{} {}", path, sub.get_name()), out);
+    }
+}
+
+/// Renders the Markdown reference for every subcommand and flag
+///
+/// # Arguments
+///
+/// * `cmd` - Root clap command
+///
+/// # Returns
+/// * `String` - Markdown reference
+///
+pub fn render_markdown(cmd: clap::Command) -> String {
+    let mut cmd = cmd.disable_help_subcommand(true);
+    cmd.build();
+
+    let name = cmd.get_name().to_string();
+    let mut out = format!(
+        "# {} command reference\n\n<!-- Generated by `{} gen-docs`. Do not edit by hand. -->\n\n",
+        name, name
+    );
+    if let Some(about) = cmd.get_about() {
+        out.push_str(&format!("{}\n\n", about));
+    }
+
+    for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        render_command(sub, &format!("{} {}", name, sub.get_name()), &mut out);
+    }
+
+    format!("{}\n", out.trim_end())
+}
+
+/// Writes roff man pages for the command and every subcommand along with the Markdown reference
+///
+/// # Arguments
+///
+/// * `cmd` - Root clap command
+/// * `out_dir` - Directory to write docs to. Man pages are written to `{out_dir}/man`
+///
+pub fn gen_docs(cmd: clap::Command, out_dir: &str) -> Result<(), anyhow::Error> {
+    let out_dir = Path::new(out_dir);
+    let man_dir = out_dir.join("man");
+    fs::create_dir_all(&man_dir)
+        .with_context(|| format!("Failed to create {}", man_dir.display()))?;
+
+    clap_mangen::generate_to(cmd.clone(), &man_dir)
+        .with_context(|| format!("Failed to write man pages to {}", man_dir.display()))?;
+
+    let reference = out_dir.join(REFERENCE_FILE);
+    fs::write(&reference, render_markdown(cmd))
+        .with_context(|| format!("Failed to write {}", reference.display()))?;
+
+    println!(
+        "Wrote man pages to {} and reference to {}",
+        man_dir.display().green(),
+        reference.display().green()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::cli::Cli;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_reference_is_current() {
+        let checked_in = fs::read_to_string(Path::new("docs").join(REFERENCE_FILE))
+            .expect("Unable to read docs/cli-reference.md");
+
+        assert!(
+            checked_in == render_markdown(Cli::command()),
+            "docs/cli-reference.md is stale. Regenerate it with `cargo run -- gen-docs --out docs`"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let reference = render_markdown(Cli::command());
+
+        assert!(reference.contains("## `opsml-cli files ls`"));
+        assert!(reference.contains("| `--write-dir <WRITE_DIR>` | Write directory | `models` |"));
+        assert!(reference.contains("$ opsml-cli list-cards --registry data"));
+        assert!(reference
+            .contains("$ opsml-cli download-model --name model_name --version 1.0.0 --dry-run\n"));
+        assert!(!reference.contains("# Example"));
+    }
+
+    #[test]
+    fn test_gen_docs() {
+        let dir = std::env::temp_dir().join(format!("opsml-docs-{}", uuid::Uuid::new_v4()));
+        gen_docs(Cli::command(), dir.to_str().unwrap()).unwrap();
+
+        assert!(dir.join(REFERENCE_FILE).exists());
+        assert!(dir.join("man").join("opsml-cli.1").exists());
+        assert!(dir.join("man").join("opsml-cli-files-ls.1").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
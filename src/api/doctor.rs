@@ -0,0 +1,595 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::api_version::{self, ApiVersion};
+use crate::api::types;
+use crate::api::utils::{self, OpsmlPaths};
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tabled::settings::style::Style;
+use tabled::{settings::Alignment, Table};
+
+/// Server versions this client is known to work with
+pub const SUPPORTED_SERVER_VERSIONS: &str = ">=2.0.0, <4.0.0";
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Checks in the order they run
+const CHECKS: [&str; 9] = [
+    "environment",
+    "url",
+    "dns",
+    "tcp",
+    "tls",
+    "health",
+    "version",
+    "access",
+    "compatibility",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+impl CheckStatus {
+    fn as_str(&self) -> String {
+        match self {
+            CheckStatus::Pass => "PASS".green().bold().to_string(),
+            CheckStatus::Fail => "FAIL".red().bold().to_string(),
+            CheckStatus::Skip => "SKIP".yellow().to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CheckResult {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+    hint: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn fail(name: &'static str, error: &anyhow::Error, hint: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            status: CheckStatus::Fail,
+            detail: format!("{:#}", error),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn skip(name: &'static str, detail: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            status: CheckStatus::Skip,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+}
+
+/// Marks every check after the last recorded one as skipped
+fn skip_remaining(results: &mut Vec<CheckResult>) {
+    for name in &CHECKS[results.len()..] {
+        results.push(CheckResult::skip(name, "Skipped after an earlier failure"));
+    }
+}
+
+pub struct Doctor {
+    pub tracking_uri: Option<String>,
+}
+
+impl Doctor {
+    /// Parses the tracking uri and checks it points at an http(s) server
+    fn check_url(uri: &str) -> Result<Url, anyhow::Error> {
+        let url = Url::parse(uri).with_context(|| format!("Failed to parse {}", uri))?;
+
+        if !["http", "https"].contains(&url.scheme()) {
+            return Err(anyhow::Error::msg(format!(
+                "Unsupported scheme: {}",
+                url.scheme()
+            )));
+        }
+        if url.host_str().is_none() {
+            return Err(anyhow::Error::msg("Missing host"));
+        }
+
+        Ok(url)
+    }
+
+    /// Resolves the host of the tracking uri
+    async fn check_dns(url: &Url) -> Result<SocketAddr, anyhow::Error> {
+        let host = url.host_str().unwrap_or_default();
+        let port = url.port_or_known_default().unwrap_or(80);
+
+        tokio::time::timeout(TIMEOUT, tokio::net::lookup_host((host, port)))
+            .await
+            .with_context(|| format!("Timed out resolving {}", host))??
+            .next()
+            .with_context(|| format!("{} did not resolve to any address", host))
+    }
+
+    async fn check_tcp(addr: SocketAddr) -> Result<(), anyhow::Error> {
+        tokio::time::timeout(TIMEOUT, tokio::net::TcpStream::connect(addr))
+            .await
+            .with_context(|| format!("Timed out connecting to {}", addr))??;
+        Ok(())
+    }
+
+    /// Performs a TLS handshake with the server
+    async fn check_tls(url: &Url, addr: SocketAddr) -> Result<(), anyhow::Error> {
+        let host = url.host_str().unwrap_or_default().to_string();
+
+        tokio::task::spawn_blocking(move || -> Result<(), anyhow::Error> {
+            let stream = std::net::TcpStream::connect_timeout(&addr, TIMEOUT)?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+
+            native_tls::TlsConnector::new()?
+                .connect(&host, stream)
+                .map_err(|e| anyhow::Error::msg(e.to_string()))?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Fetches the server's opsml version. Servers without a version endpoint return `None`
+    async fn check_version(
+        client: &reqwest::Client,
+        uri: &str,
+        api_version: ApiVersion,
+    ) -> Result<Option<semver::Version>, anyhow::Error> {
+        let url = api_version.url(uri, &OpsmlPaths::Version);
+        let response = client.get(&url).timeout(TIMEOUT).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(anyhow::Error::msg(format!(
+                "{} returned {}",
+                url,
+                response.status()
+            )));
+        }
+
+        let version: types::ServerVersion = response
+            .json()
+            .await
+            .with_context(|| "Failed to parse version response")?;
+
+        semver::Version::parse(&version.version)
+            .map(Some)
+            .with_context(|| format!("Invalid server version: {}", version.version))
+    }
+
+    /// Lists a single card to check the server accepts this client's requests. opsml-cli does
+    /// not send credentials, so this checks the server allows requests without them
+    async fn check_access(
+        client: &reqwest::Client,
+        uri: &str,
+        api_version: ApiVersion,
+    ) -> Result<(), anyhow::Error> {
+        let tags = HashMap::new();
+        let request_body = types::ListTableRequest {
+            registry_type: "model",
+            name: None,
+            repository: None,
+            version: None,
+            limit: Some(&1),
+            uid: None,
+            tags: &tags,
+            max_date: None,
+            ignore_release_candidates: &false,
+        };
+
+        let request = client
+            .post(api_version.url(uri, &OpsmlPaths::ListCard))
+            .timeout(TIMEOUT);
+        let response = match api_version {
+            ApiVersion::V2 => request.json(&request_body),
+            ApiVersion::V3 => request.json(&types::ListCardRequestV3::from(&request_body)),
+        }
+        .send()
+        .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(anyhow::Error::msg(format!(
+                "Listing cards returned {}",
+                status
+            ))),
+        }
+    }
+
+    /// Checks the server version against the versions this client supports
+    fn check_compatibility(version: &semver::Version) -> Result<(), anyhow::Error> {
+        let supported = semver::VersionReq::parse(SUPPORTED_SERVER_VERSIONS)?;
+
+        match supported.matches(version) {
+            true => Ok(()),
+            false => Err(anyhow::Error::msg(format!(
+                "Server version {} is outside {}",
+                version, SUPPORTED_SERVER_VERSIONS
+            ))),
+        }
+    }
+
+    /// Runs every check in order. Checks that depend on a failed check are skipped
+    async fn run(&self) -> Vec<CheckResult> {
+        let mut results = Vec::new();
+
+        let Some(uri) = self.tracking_uri.as_deref() else {
+            results.push(CheckResult::fail(
+                "environment",
+                &anyhow::Error::msg("OPSML_TRACKING_URI is not set"),
+                "Export OPSML_TRACKING_URI, e.g. export OPSML_TRACKING_URI=https://opsml.example.com",
+            ));
+            skip_remaining(&mut results);
+            return results;
+        };
+        let uri = utils::remove_suffix(uri, '/');
+        results.push(CheckResult::pass(
+            "environment",
+            format!("OPSML_TRACKING_URI={}", uri),
+        ));
+
+        let url = match Doctor::check_url(&uri) {
+            Ok(url) => {
+                results.push(CheckResult::pass(
+                    "url",
+                    format!("{} uses {}", uri, url.scheme()),
+                ));
+                url
+            }
+            Err(e) => {
+                results.push(CheckResult::fail(
+                    "url",
+                    &e,
+                    "OPSML_TRACKING_URI must be a full http(s) url such as http://localhost:8888",
+                ));
+                skip_remaining(&mut results);
+                return results;
+            }
+        };
+
+        let addr = match Doctor::check_dns(&url).await {
+            Ok(addr) => {
+                results.push(CheckResult::pass(
+                    "dns",
+                    format!("Resolved to {}", addr.ip()),
+                ));
+                addr
+            }
+            Err(e) => {
+                results.push(CheckResult::fail(
+                    "dns",
+                    &e,
+                    "Check the host name for typos and that you are on the right network or VPN",
+                ));
+                skip_remaining(&mut results);
+                return results;
+            }
+        };
+
+        match Doctor::check_tcp(addr).await {
+            Ok(()) => results.push(CheckResult::pass("tcp", format!("Connected to {}", addr))),
+            Err(e) => {
+                results.push(CheckResult::fail(
+                    "tcp",
+                    &e,
+                    "Check the server is running and the port is correct and reachable through any firewall",
+                ));
+                skip_remaining(&mut results);
+                return results;
+            }
+        }
+
+        if url.scheme() == "https" {
+            match Doctor::check_tls(&url, addr).await {
+                Ok(()) => results.push(CheckResult::pass("tls", "TLS handshake succeeded")),
+                Err(e) => {
+                    results.push(CheckResult::fail(
+                        "tls",
+                        &e,
+                        "Check the server certificate is valid for the host and trusted by this machine",
+                    ));
+                    skip_remaining(&mut results);
+                    return results;
+                }
+            }
+        } else {
+            results.push(CheckResult::skip("tls", "Tracking uri uses http"));
+        }
+
+        let client = reqwest::Client::new();
+        // later checks use the v2 api when the server's api version cannot be detected
+        let api_version = match api_version::detect(&client, &uri).await {
+            Ok(api_version) => {
+                results.push(CheckResult::pass(
+                    "health",
+                    format!(
+                        "Server is healthy and serves the {} api",
+                        api_version.as_str()
+                    ),
+                ));
+                api_version
+            }
+            Err(e) => {
+                results.push(CheckResult::fail(
+                    "health",
+                    &e,
+                    "Check OPSML_TRACKING_URI points at the opsml server rather than another service, and the server logs",
+                ));
+                ApiVersion::V2
+            }
+        };
+
+        let version = match Doctor::check_version(&client, &uri, api_version).await {
+            Ok(Some(version)) => {
+                results.push(CheckResult::pass(
+                    "version",
+                    format!("Server runs opsml {}", version),
+                ));
+                Some(version)
+            }
+            Ok(None) => {
+                results.push(CheckResult::skip(
+                    "version",
+                    "Server does not expose a version endpoint",
+                ));
+                None
+            }
+            Err(e) => {
+                results.push(CheckResult::fail(
+                    "version",
+                    &e,
+                    "The server did not report its version. Check the server logs",
+                ));
+                None
+            }
+        };
+
+        match Doctor::check_access(&client, &uri, api_version).await {
+            Ok(()) => results.push(CheckResult::pass(
+                "access",
+                "Listed cards from the model registry without credentials",
+            )),
+            Err(e) => results.push(CheckResult::fail(
+                "access",
+                &e,
+                "The server rejected a request without credentials, which opsml-cli does not send. Check the server's login settings and any proxy in front of it",
+            )),
+        }
+
+        match version {
+            Some(version) => match Doctor::check_compatibility(&version) {
+                Ok(()) => results.push(CheckResult::pass(
+                    "compatibility",
+                    format!("{} is within {}", version, SUPPORTED_SERVER_VERSIONS),
+                )),
+                Err(e) => results.push(CheckResult::fail(
+                    "compatibility",
+                    &e,
+                    format!("Use an opsml-cli release that supports opsml {}", version),
+                )),
+            },
+            None => results.push(CheckResult::skip("compatibility", "Server version unknown")),
+        }
+
+        results
+    }
+}
+
+/// Checks the client configuration and the server it points at, printing each check with hints
+/// for the ones that fail
+///
+/// # Arguments
+///
+/// * `tracking_uri` - Tracking uri to check. Read from `OPSML_TRACKING_URI` when `None`
+///
+pub async fn run_doctor(tracking_uri: Option<&str>) -> Result<(), anyhow::Error> {
+    let doctor = Doctor {
+        tracking_uri: tracking_uri
+            .map(String::from)
+            .or_else(|| utils::tracking_uri_from_env().ok()),
+    };
+    let results = doctor.run().await;
+
+    let table = results
+        .iter()
+        .map(|result| types::DoctorCheckTable {
+            check: result.name.to_string(),
+            status: result.status.as_str(),
+            detail: result.detail.clone(),
+        })
+        .collect::<Vec<_>>();
+    println!(
+        "{}",
+        Table::new(table)
+            .with(Alignment::left())
+            .with(Style::sharp())
+    );
+
+    let failed = results
+        .iter()
+        .filter(|result| result.status == CheckStatus::Fail)
+        .collect::<Vec<_>>();
+    for result in failed.iter() {
+        if let Some(hint) = &result.hint {
+            println!("{} {}", format!("{}:", result.name).bold().red(), hint);
+        }
+    }
+
+    if failed.is_empty() {
+        println!("{}", "All checks passed".green());
+        Ok(())
+    } else {
+        Err(anyhow::Error::msg(format!(
+            "{} of {} check(s) failed",
+            failed.len(),
+            results.len()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statuses(results: &[CheckResult]) -> Vec<(&str, CheckStatus)> {
+        results
+            .iter()
+            .map(|result| (result.name, result.status))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_doctor() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+
+        let mock_health = server
+            .mock("GET", "/opsml/healthcheck")
+            .with_status(200)
+            .create();
+        let mock_version = server
+            .mock("GET", "/opsml/version")
+            .with_status(200)
+            .with_body(r#"{"version": "2.1.0"}"#)
+            .create();
+        let mock_list = server
+            .mock("POST", "/opsml/cards/list")
+            .with_status(401)
+            .create();
+
+        let doctor = Doctor {
+            tracking_uri: Some(format!("{}/", url)),
+        };
+        let results = doctor.run().await;
+        assert_eq!(
+            statuses(&results),
+            vec![
+                ("environment", CheckStatus::Pass),
+                ("url", CheckStatus::Pass),
+                ("dns", CheckStatus::Pass),
+                ("tcp", CheckStatus::Pass),
+                ("tls", CheckStatus::Skip),
+                ("health", CheckStatus::Pass),
+                ("version", CheckStatus::Pass),
+                ("access", CheckStatus::Fail),
+                ("compatibility", CheckStatus::Pass),
+            ]
+        );
+        assert!(results[7].detail.contains("401"));
+
+        mock_health.assert();
+        mock_version.assert();
+        mock_list.assert();
+    }
+
+    #[tokio::test]
+    async fn test_doctor_v3() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+
+        let mock_health = server
+            .mock("GET", "/opsml/api/healthcheck")
+            .with_status(200)
+            .create();
+        let mock_version = server
+            .mock("GET", "/opsml/api/version")
+            .with_status(200)
+            .with_body(r#"{"version": "3.0.2"}"#)
+            .create();
+        let mock_list = server
+            .mock("POST", "/opsml/api/card/list")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"space": null}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body("[]")
+            .create();
+
+        let results = Doctor {
+            tracking_uri: Some(url),
+        }
+        .run()
+        .await;
+        assert!(results
+            .iter()
+            .all(|result| result.status != CheckStatus::Fail));
+        assert!(results[5].detail.contains("v3 api"));
+
+        mock_health.assert();
+        mock_version.assert();
+        mock_list.assert();
+    }
+
+    #[tokio::test]
+    async fn test_doctor_without_version_endpoint() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+
+        let _mock_health = server
+            .mock("GET", "/opsml/healthcheck")
+            .with_status(200)
+            .create();
+        let mock_version = server
+            .mock("GET", "/opsml/version")
+            .with_status(404)
+            .create();
+        let _mock_list = server
+            .mock("POST", "/opsml/cards/list")
+            .with_status(200)
+            .with_body(r#"{"cards": []}"#)
+            .create();
+
+        let results = Doctor {
+            tracking_uri: Some(url),
+        }
+        .run()
+        .await;
+        assert_eq!(results[6].status, CheckStatus::Skip);
+        assert_eq!(results[7].status, CheckStatus::Pass);
+        assert_eq!(results[8].status, CheckStatus::Skip);
+        mock_version.assert();
+    }
+
+    #[tokio::test]
+    async fn test_doctor_misconfigured() {
+        let results = Doctor { tracking_uri: None }.run().await;
+        assert_eq!(results[0].status, CheckStatus::Fail);
+        assert!(results[1..]
+            .iter()
+            .all(|result| result.status == CheckStatus::Skip));
+
+        let results = Doctor {
+            tracking_uri: Some("localhost:8888".to_string()),
+        }
+        .run()
+        .await;
+        assert_eq!(results[1].status, CheckStatus::Fail);
+
+        assert!(Doctor::check_compatibility(&semver::Version::new(2, 4, 1)).is_ok());
+        assert!(Doctor::check_compatibility(&semver::Version::new(3, 0, 2)).is_ok());
+        assert!(Doctor::check_compatibility(&semver::Version::new(1, 5, 0)).is_err());
+        assert!(Doctor::check_compatibility(&semver::Version::new(4, 0, 0)).is_err());
+    }
+}
@@ -0,0 +1,333 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::cards;
+use crate::api::route_helper::RouteHelper;
+use crate::api::types;
+use crate::api::utils;
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use std::path::Path;
+use tabled::settings::style::Style;
+use tabled::{settings::Alignment, Table};
+
+pub struct FileDownloader<'a> {
+    pub write_dir: &'a str,
+    pub dry_run: &'a bool,
+    pub include: &'a [String],
+    pub exclude: &'a [String],
+}
+
+impl FileDownloader<'_> {
+    /// Maps remote files to the local paths they will be written to.
+    /// Files removed by the include/exclude filters are returned separately
+    ///
+    /// # Arguments
+    ///
+    /// * `rpath` - Remote path to file
+    /// * `rpath_root` - Remote root that is stripped from each file path
+    ///
+    /// # Returns
+    /// * `Result<types::DownloadPlan, String>` - Planned remote to local mapping
+    pub async fn plan_files(
+        &self,
+        rpath: &Path,
+        rpath_root: &Path,
+    ) -> Result<types::DownloadPlan, anyhow::Error> {
+        let file_filter = utils::FileFilter::new(self.include, self.exclude)?;
+
+        let rpath_files: Vec<types::FileInfo> = RouteHelper::list_files(rpath)
+            .await?
+            .files
+            .iter()
+            .map(|file| types::FileInfo::from_name(file))
+            .collect();
+
+        let mut plan = types::DownloadPlan::default();
+
+        for file in rpath_files.iter() {
+            let stripped_path = Path::new(&file.name)
+                .strip_prefix(rpath_root)
+                .with_context(|| "Failed to create file path")?;
+
+            let planned_file = types::PlannedFile {
+                rpath: file.name.clone(),
+                lpath: Path::new(self.write_dir).join(stripped_path),
+                size: file.size,
+            };
+
+            if file_filter.is_match(stripped_path) {
+                plan.files.push(planned_file);
+            } else {
+                plan.skipped.push(planned_file);
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// Prints planned downloads without transferring any files
+    ///
+    /// # Arguments
+    ///
+    /// * `plan` - Planned remote to local mapping
+    ///
+    fn print_plan(&self, plan: &types::DownloadPlan) {
+        let plan_table: Vec<types::DownloadPlanTable> = plan
+            .files
+            .iter()
+            .map(|file| types::DownloadPlanTable {
+                remote: file.rpath.clone(),
+                local: file.lpath.display().to_string(),
+                size: file
+                    .size
+                    .map(utils::format_bytes)
+                    .unwrap_or("unknown".to_string()),
+            })
+            .collect();
+
+        // the total is only known when every file's size is
+        let total: Option<u64> = plan.files.iter().map(|file| file.size).sum();
+
+        println!(
+            "\n{}",
+            "Dry run: no files will be downloaded".bold().yellow()
+        );
+        println!(
+            "{}",
+            Table::new(plan_table)
+                .with(Alignment::left())
+                .with(Style::sharp())
+        );
+        println!(
+            "{} file(s), {} total",
+            plan.files.len(),
+            total
+                .map(utils::format_bytes)
+                .unwrap_or("unknown".to_string())
+                .green()
+        );
+        self.print_skipped(plan);
+    }
+
+    /// Prints files removed by the include/exclude filters
+    ///
+    /// # Arguments
+    ///
+    /// * `plan` - Planned remote to local mapping
+    ///
+    fn print_skipped(&self, plan: &types::DownloadPlan) {
+        if plan.skipped.is_empty() {
+            return;
+        }
+
+        println!("Skipped {} file(s) by filter:", plan.skipped.len());
+        for file in plan.skipped.iter() {
+            println!("  {}", file.rpath.dimmed());
+        }
+    }
+
+    /// Downloads all files under a remote path
+    ///
+    /// # Arguments
+    ///
+    /// * `rpath` - Remote path to file
+    /// * `rpath_root` - Remote root that is stripped from each file path
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Result of file download
+    pub async fn download_files(
+        &self,
+        rpath: &Path,
+        rpath_root: &Path,
+    ) -> Result<(), anyhow::Error> {
+        let plan = self.plan_files(rpath, rpath_root).await?;
+
+        if self.dry_run == &true {
+            self.print_plan(&plan);
+            return Ok(());
+        }
+
+        // iterate over each file and download
+        for file in plan.files.iter() {
+            println!(
+                "Downloading: {} from {}",
+                file.lpath.display().to_string().green(),
+                file.rpath
+            );
+
+            utils::create_dir_path(&file.lpath)?;
+            RouteHelper::download_file(&file.lpath, &file.rpath).await?;
+        }
+
+        println!("Downloaded {} file(s)", plan.files.len());
+        self.print_skipped(&plan);
+
+        Ok(())
+    }
+}
+
+pub struct CardDownloader<'a> {
+    pub registry: &'a str,
+    pub name: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub repository: Option<&'a str>,
+    pub uid: Option<&'a str>,
+    pub ignore_release_candidates: &'a bool,
+    pub files: FileDownloader<'a>,
+}
+
+impl CardDownloader<'_> {
+    /// Downloads all artifacts stored under a card's registry root
+    /// (data splits and profiles for data cards, logged artifacts for run cards)
+    async fn download_card(&self) -> Result<(), anyhow::Error> {
+        let save_root = utils::SaveRoot::from_registry(self.registry)?;
+
+        let card = cards::get_card(
+            self.registry,
+            self.name,
+            self.repository,
+            self.version,
+            self.uid,
+            *self.ignore_release_candidates,
+        )
+        .await?;
+
+        let rpath_root = save_root.card_root(&card.repository, &card.name, &card.version);
+
+        println!(
+            "Downloading {} card {} (version {})",
+            self.registry.bold(),
+            card.name.green(),
+            card.version.green()
+        );
+
+        self.files.download_files(&rpath_root, &rpath_root).await
+    }
+}
+
+/// Downloads the artifacts of a card from any registry that stores files
+///
+/// * `registry` - Registry of the card (data, model, run, pipeline)
+/// * `name` - Name of card
+/// * `version` - Version of card
+/// * `repository` - repository associated with card
+/// * `uid` - uid of card
+/// * `write_dir` - directory to write to
+/// * `ignore_release_candidates` - Flag to ignore release candidates
+/// * `dry_run` - Flag to print planned downloads without transferring files
+/// * `include` - Glob patterns of files to download
+/// * `exclude` - Glob patterns of files to skip
+///
+#[allow(clippy::too_many_arguments)]
+pub async fn download_card(
+    registry: &str,
+    name: Option<&str>,
+    version: Option<&str>,
+    repository: Option<&str>,
+    uid: Option<&str>,
+    write_dir: &str,
+    ignore_release_candidates: &bool,
+    dry_run: &bool,
+    include: &[String],
+    exclude: &[String],
+) -> Result<(), anyhow::Error> {
+    let card_downloader = CardDownloader {
+        registry,
+        name,
+        version,
+        repository,
+        uid,
+        ignore_release_candidates,
+        files: FileDownloader {
+            write_dir,
+            dry_run,
+            include,
+            exclude,
+        },
+    };
+    card_downloader.download_card().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::types::PresignedUrl;
+    use std::env;
+    use std::fs;
+    use tokio;
+
+    #[tokio::test]
+    async fn test_download_data_card() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url.clone());
+        }
+
+        let cards = fs::read_to_string("./src/api/test_utils/list_cards.json").unwrap();
+        let mock_list_cards = server
+            .mock("POST", "/opsml/cards/list")
+            .with_status(201)
+            .with_body(cards)
+            .create();
+
+        let root = "opsml-root:/OPSML_DATA_REGISTRY/repository/name/v1.0.0";
+        let files = types::ListFileResponse {
+            files: vec![
+                format!("{}/data.parquet", root),
+                format!("{}/data-profile.html", root),
+            ],
+        };
+        let mock_list_files = server
+            .mock(
+                "GET",
+                "/opsml/files/list?path=opsml-root%3A%2FOPSML_DATA_REGISTRY%2Frepository%2Fname%2Fv1.0.0",
+            )
+            .with_status(201)
+            .with_body(serde_json::to_string(&files).unwrap())
+            .create();
+
+        let presigned_url = PresignedUrl {
+            url: format!("{}/get", url),
+        };
+        let mock_presigned = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/opsml/files/presigned".to_string()),
+            )
+            .with_status(201)
+            .with_body(serde_json::to_string(&presigned_url).unwrap())
+            .expect(1)
+            .create();
+        let _mock_download = server
+            .mock("GET", "/get")
+            .with_status(201)
+            .with_body("data")
+            .create();
+
+        download_card(
+            "data",
+            None,
+            None,
+            None,
+            Some("1234567890"),
+            "downloaded_card",
+            &false,
+            &false,
+            &[],
+            &["*.html".to_string()],
+        )
+        .await
+        .unwrap();
+
+        mock_list_cards.assert();
+        mock_list_files.assert();
+        mock_presigned.assert();
+
+        assert!(Path::new("downloaded_card/data.parquet").exists());
+        assert!(!Path::new("downloaded_card/data-profile.html").exists());
+
+        fs::remove_dir_all("downloaded_card").unwrap();
+    }
+}
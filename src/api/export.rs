@@ -0,0 +1,327 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::types;
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+const PROMETHEUS_METRIC: &str = "opsml_metric";
+const PARQUET_SCHEMA: &str = "
+message metric {
+    REQUIRED BYTE_ARRAY run_uid (UTF8);
+    REQUIRED BYTE_ARRAY name (UTF8);
+    OPTIONAL DOUBLE value;
+    OPTIONAL DOUBLE step;
+    OPTIONAL DOUBLE timestamp;
+}
+";
+
+/// Formats an optional json value for text output. Strings are unquoted
+fn value_to_string(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// Quotes a csv field if it contains a delimiter, quote or newline
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Escapes a Prometheus label value
+fn escape_label(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Renders metrics as csv with a header row
+///
+/// # Arguments
+///
+/// * `metrics` - Metrics to export
+///
+pub fn metrics_to_csv(metrics: &[types::Metric]) -> String {
+    let mut lines = vec!["run_uid,name,value,step,timestamp".to_string()];
+
+    for metric in metrics.iter() {
+        let fields = [
+            metric.run_uid.clone(),
+            metric.name.clone(),
+            value_to_string(Some(&metric.value)),
+            value_to_string(metric.step.as_ref()),
+            value_to_string(metric.timestamp.as_ref()),
+        ];
+        lines.push(
+            fields
+                .iter()
+                .map(|field| escape_csv(field))
+                .collect::<Vec<String>>()
+                .join(","),
+        );
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Renders metrics in the Prometheus text exposition format.
+/// Metrics with non-numeric values are skipped
+///
+/// # Arguments
+///
+/// * `metrics` - Metrics to export
+///
+pub fn metrics_to_prometheus(metrics: &[types::Metric]) -> String {
+    let mut lines = vec![
+        format!("# HELP {} Metric logged to an OpsML run", PROMETHEUS_METRIC),
+        format!("# TYPE {} gauge", PROMETHEUS_METRIC),
+    ];
+
+    for metric in metrics.iter() {
+        let value = match metric.value.as_f64() {
+            Some(value) => value,
+            None => continue,
+        };
+
+        let mut labels = vec![
+            format!("name=\"{}\"", escape_label(&metric.name)),
+            format!("run_uid=\"{}\"", escape_label(&metric.run_uid)),
+        ];
+        if let Some(step) = metric.step.as_ref().filter(|step| !step.is_null()) {
+            labels.push(format!(
+                "step=\"{}\"",
+                escape_label(&value_to_string(Some(step)))
+            ));
+        }
+
+        lines.push(format!(
+            "{}{{{}}} {}",
+            PROMETHEUS_METRIC,
+            labels.join(","),
+            value
+        ));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Writes metrics to a parquet file
+///
+/// # Arguments
+///
+/// * `metrics` - Metrics to export
+/// * `path` - File to write
+///
+pub fn write_metrics_parquet(metrics: &[types::Metric], path: &Path) -> Result<(), anyhow::Error> {
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let props = Arc::new(WriterProperties::builder().build());
+    let file = fs::File::create(path)
+        .with_context(|| format!("Unable to create {}", path.display().to_string().red()))?;
+
+    let mut writer = SerializedFileWriter::new(file, schema, props)?;
+    let mut row_group = writer.next_row_group()?;
+
+    // definition levels mark missing values in optional columns
+    let optional = |values: Vec<Option<f64>>| -> (Vec<f64>, Vec<i16>) {
+        let levels = values.iter().map(|v| i16::from(v.is_some())).collect();
+        (values.into_iter().flatten().collect(), levels)
+    };
+
+    let mut column_index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match column_index {
+            0 | 1 => {
+                let values: Vec<ByteArray> = metrics
+                    .iter()
+                    .map(|metric| {
+                        let field = if column_index == 0 {
+                            &metric.run_uid
+                        } else {
+                            &metric.name
+                        };
+                        ByteArray::from(field.as_str())
+                    })
+                    .collect();
+                column
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, None, None)?;
+            }
+            _ => {
+                let (values, levels) = optional(
+                    metrics
+                        .iter()
+                        .map(|metric| match column_index {
+                            2 => metric.value.as_f64(),
+                            3 => metric.step.as_ref().and_then(Value::as_f64),
+                            _ => metric.timestamp.as_ref().and_then(Value::as_f64),
+                        })
+                        .collect(),
+                );
+                column
+                    .typed::<DoubleType>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+        }
+        column.close()?;
+        column_index += 1;
+    }
+
+    row_group.close()?;
+    writer.close()?;
+
+    Ok(())
+}
+
+/// Exports metrics to a file. The format is inferred from the file extension
+/// (csv, parquet, json or prom). `.txt` files need an explicit format
+///
+/// # Arguments
+///
+/// * `metrics` - Metrics to export
+/// * `path` - File to write
+/// * `format` - Format passed with --format, used for `.txt` files
+///
+pub fn export_metrics(
+    metrics: &[types::Metric],
+    path: &Path,
+    format: &types::MetricFormat,
+) -> Result<(), anyhow::Error> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let contents = match (extension.as_str(), format) {
+        ("csv", _) | ("txt", types::MetricFormat::Csv) => metrics_to_csv(metrics),
+        ("prom", _) | ("txt", types::MetricFormat::Prometheus) => metrics_to_prometheus(metrics),
+        ("json", _) | ("txt", types::MetricFormat::Json) => {
+            serde_json::to_string_pretty(&types::ListMetricResponse {
+                metric: metrics.to_vec(),
+            })?
+        }
+        ("parquet", _) => return write_metrics_parquet(metrics, path),
+        ("txt", types::MetricFormat::Table) => {
+            return Err(anyhow::Error::msg(format!(
+                "Pass --format csv, json or prometheus to export {}",
+                path.display().to_string().red()
+            )))
+        }
+        _ => {
+            return Err(anyhow::Error::msg(format!(
+                "Unsupported export format for {}. Use .csv, .parquet, .json, .prom or .txt with --format",
+                path.display().to_string().red()
+            )))
+        }
+    };
+
+    fs::write(path, contents)
+        .with_context(|| format!("Unable to write {}", path.display().to_string().red()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use uuid::Uuid;
+
+    fn metrics() -> Vec<types::Metric> {
+        vec![
+            types::Metric {
+                run_uid: "run".to_string(),
+                name: "mae".to_string(),
+                value: 5.into(),
+                step: Some(1.into()),
+                timestamp: None,
+            },
+            types::Metric {
+                run_uid: "run".to_string(),
+                name: "label, \"quoted\"".to_string(),
+                value: 0.5.into(),
+                step: None,
+                timestamp: Some(1700000000.0.into()),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_metrics_to_text() {
+        assert_eq!(
+            metrics_to_csv(&metrics()),
+            concat!(
+                "run_uid,name,value,step,timestamp\n",
+                "run,mae,5,1,\n",
+                "run,\"label, \"\"quoted\"\"\",0.5,,1700000000.0\n",
+            )
+        );
+
+        assert_eq!(
+            metrics_to_prometheus(&metrics()),
+            concat!(
+                "# HELP opsml_metric Metric logged to an OpsML run\n",
+                "# TYPE opsml_metric gauge\n",
+                "opsml_metric{name=\"mae\",run_uid=\"run\",step=\"1\"} 5\n",
+                "opsml_metric{name=\"label, \\\"quoted\\\"\",run_uid=\"run\"} 0.5\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_export_parquet() {
+        let path = format!("{}.parquet", Uuid::new_v4());
+        let path = Path::new(&path);
+
+        export_metrics(&metrics(), path, &types::MetricFormat::Table).unwrap();
+
+        let reader = SerializedFileReader::new(fs::File::open(path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        assert_eq!(
+            reader
+                .metadata()
+                .file_metadata()
+                .schema_descr()
+                .num_columns(),
+            5
+        );
+        // float steps are kept
+        assert_eq!(
+            reader
+                .metadata()
+                .file_metadata()
+                .schema_descr()
+                .column(3)
+                .physical_type(),
+            parquet::basic::Type::DOUBLE
+        );
+
+        fs::remove_file(path).unwrap();
+
+        assert!(export_metrics(
+            &metrics(),
+            Path::new("metrics.xlsx"),
+            &types::MetricFormat::Table
+        )
+        .is_err());
+        assert!(export_metrics(
+            &metrics(),
+            Path::new("metrics.txt"),
+            &types::MetricFormat::Table
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,488 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::cards;
+use crate::api::route_helper::RouteHelper;
+use crate::api::storage::{StorageBackend, STORAGE_URI_VAR};
+use crate::api::types;
+use crate::api::utils::{self, OPSML_ROOT};
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use owo_colors::OwoColorize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tabled::settings::style::Style;
+use tabled::{settings::Alignment, Table};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+pub struct FileBrowser<'a> {
+    pub target: &'a str,
+    pub registry: &'a str,
+}
+
+impl FileBrowser<'_> {
+    /// Resolves the remote root to browse. Targets starting with `opsml-root:/` are used as is,
+    /// anything else is treated as a card uid in the browser's registry
+    ///
+    /// # Returns
+    /// * `Result<PathBuf, String>` - Remote root path
+    async fn resolve_root(&self) -> Result<PathBuf, anyhow::Error> {
+        if self.target.starts_with(OPSML_ROOT) {
+            return Ok(PathBuf::from(utils::remove_suffix(self.target, '/')));
+        }
+
+        let save_root = utils::SaveRoot::from_registry(self.registry)?;
+        let card =
+            cards::get_card(self.registry, None, None, None, Some(self.target), false).await?;
+
+        Ok(save_root.card_root(&card.repository, &card.name, &card.version))
+    }
+
+    /// Lists files under the root with paths relative to the root
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - Remote root to list
+    ///
+    /// # Returns
+    /// * `Result<Vec<types::FileInfo>, String>` - Files with relative names
+    async fn relative_files(&self, root: &Path) -> Result<Vec<types::FileInfo>, anyhow::Error> {
+        let files = RouteHelper::list_files(root).await?;
+
+        let mut relative_files = Vec::new();
+        for file in files
+            .files
+            .iter()
+            .map(|file| types::FileInfo::from_name(file))
+        {
+            let relative = Path::new(&file.name)
+                .strip_prefix(root)
+                .with_context(|| format!("{} is not under {}", file.name, root.display()))?
+                .to_string_lossy()
+                .to_string();
+
+            relative_files.push(types::FileInfo {
+                name: relative,
+                ..file
+            });
+        }
+
+        relative_files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(relative_files)
+    }
+
+    /// Collapses a recursive file listing to the first level below the root.
+    /// Directories are returned with a trailing `/` and the combined size of their files
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - Files with paths relative to the root
+    ///
+    fn collapse_to_top_level(&self, files: Vec<types::FileInfo>) -> Vec<types::FileInfo> {
+        let mut entries: BTreeMap<String, types::FileInfo> = BTreeMap::new();
+
+        for file in files.into_iter() {
+            match file.name.split_once('/') {
+                Some((dir, _)) => {
+                    let name = format!("{}/", dir);
+                    let entry = entries.entry(name.clone()).or_insert(types::FileInfo {
+                        name,
+                        size: Some(0),
+                        mtime: None,
+                        file_type: Some("directory".to_string()),
+                    });
+                    entry.size = match (entry.size, file.size) {
+                        (Some(total), Some(size)) => Some(total + size),
+                        _ => None,
+                    };
+                }
+                None => {
+                    entries.insert(file.name.clone(), file);
+                }
+            }
+        }
+
+        entries.into_values().collect()
+    }
+
+    /// Renders a tree of relative file paths
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - Label for the tree root
+    /// * `files` - Files with paths relative to the root
+    ///
+    fn render_tree(&self, root: &str, files: &[types::FileInfo]) -> String {
+        #[derive(Default)]
+        struct Node {
+            children: BTreeMap<String, Node>,
+        }
+
+        let mut tree = Node::default();
+        for file in files.iter() {
+            let mut node = &mut tree;
+            for part in file.name.split('/') {
+                node = node.children.entry(part.to_string()).or_default();
+            }
+        }
+
+        fn render(node: &Node, prefix: &str, lines: &mut Vec<String>) {
+            let count = node.children.len();
+            for (i, (name, child)) in node.children.iter().enumerate() {
+                let last = i == count - 1;
+                let (branch, indent) = if last {
+                    ("└── ", "    ")
+                } else {
+                    ("├── ", "│   ")
+                };
+                let label = if child.children.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}/", name)
+                };
+                lines.push(format!("{}{}{}", prefix, branch, label));
+                render(child, &format!("{}{}", prefix, indent), lines);
+            }
+        }
+
+        let mut lines = vec![root.to_string()];
+        render(&tree, "", &mut lines);
+        lines.join("\n")
+    }
+
+    /// Lists files under the target
+    ///
+    /// # Arguments
+    ///
+    /// * `recursive` - List all nested files instead of the first level
+    /// * `long` - Show sizes and modification times
+    ///
+    async fn ls(&self, recursive: &bool, long: &bool) -> Result<(), anyhow::Error> {
+        let root = self.resolve_root().await?;
+        let mut files = self.relative_files(&root).await?;
+
+        if recursive != &true {
+            files = self.collapse_to_top_level(files);
+        }
+
+        if long != &true {
+            for file in files.iter() {
+                println!("{}", file.name);
+            }
+            return Ok(());
+        }
+
+        let file_table: Vec<types::FileTable> = files
+            .iter()
+            .map(|file| types::FileTable {
+                path: file.name.clone(),
+                size: file
+                    .size
+                    .map(utils::format_bytes)
+                    .unwrap_or("-".to_string()),
+                modified: file
+                    .mtime
+                    .as_ref()
+                    .map(format_mtime)
+                    .unwrap_or("-".to_string()),
+            })
+            .collect();
+
+        println!(
+            "{}",
+            Table::new(file_table)
+                .with(Alignment::left())
+                .with(Style::sharp())
+        );
+
+        Ok(())
+    }
+
+    /// Prints a tree of files under the target
+    async fn tree(&self) -> Result<(), anyhow::Error> {
+        let root = self.resolve_root().await?;
+        let files = self.relative_files(&root).await?;
+
+        println!(
+            "{}",
+            self.render_tree(&root.display().to_string().bold().to_string(), &files)
+        );
+
+        Ok(())
+    }
+}
+
+/// Formats a modification time. Epoch seconds are rendered as a UTC date,
+/// anything else is shown as returned by the server
+///
+/// # Arguments
+///
+/// * `mtime` - Modification time returned by the server
+///
+fn format_mtime(mtime: &Value) -> String {
+    match mtime.as_f64() {
+        Some(seconds) => utils::format_epoch(seconds as i64),
+        None => mtime.as_str().unwrap_or(&mtime.to_string()).to_string(),
+    }
+}
+
+/// Writes a byte stream to a writer, optionally stopping after `head` lines
+///
+/// # Arguments
+///
+/// * `stream` - Stream of response chunks
+/// * `writer` - Writer to copy the stream to
+/// * `head` - Number of lines to write before stopping
+///
+async fn write_stream<S, B, E, W>(
+    mut stream: S,
+    writer: &mut W,
+    head: Option<usize>,
+) -> Result<(), anyhow::Error>
+where
+    S: futures::Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: std::error::Error + Send + Sync + 'static,
+    W: AsyncWrite + Unpin,
+{
+    let mut lines_left = head;
+
+    while let Some(chunk) = stream.next().await {
+        if lines_left == Some(0) {
+            break;
+        }
+
+        let chunk = chunk.with_context(|| "failed to read file stream")?;
+        let mut bytes = chunk.as_ref();
+
+        // cut the chunk after the last requested line
+        if let Some(remaining) = lines_left.as_mut() {
+            for (i, byte) in bytes.iter().enumerate() {
+                if *byte == b'\n' {
+                    *remaining -= 1;
+                    if *remaining == 0 {
+                        bytes = &bytes[..=i];
+                        break;
+                    }
+                }
+            }
+        }
+
+        writer.write_all(bytes).await?;
+    }
+
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Streams a remote file to stdout without writing it to disk
+///
+/// * `rpath` - Remote path of the file
+/// * `head` - Only print the first `head` lines
+pub async fn cat_remote_file(rpath: &str, head: Option<usize>) -> Result<(), anyhow::Error> {
+    let stream = RouteHelper::open_file(rpath).await?;
+
+    let mut stdout = tokio::io::stdout();
+    match write_stream(stream, &mut stdout, head).await {
+        Ok(()) => Ok(()),
+        // a closed pipe (e.g. `| head`) is not an error
+        Err(e)
+            if e.downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Lists files in the artifact store
+///
+/// * `target` - Card uid or `opsml-root:/` path
+/// * `registry` - Registry used to resolve a card uid
+/// * `recursive` - List all nested files
+/// * `long` - Show sizes and modification times
+pub async fn list_remote_files(
+    target: &str,
+    registry: &str,
+    recursive: &bool,
+    long: &bool,
+) -> Result<(), anyhow::Error> {
+    let browser = FileBrowser { target, registry };
+    browser.ls(recursive, long).await
+}
+
+/// Prints a tree of files in the artifact store
+///
+/// * `target` - Card uid or `opsml-root:/` path
+/// * `registry` - Registry used to resolve a card uid
+pub async fn tree_remote_files(target: &str, registry: &str) -> Result<(), anyhow::Error> {
+    let browser = FileBrowser { target, registry };
+    browser.tree().await
+}
+
+/// Downloads a single file from the artifact store
+///
+/// * `rpath` - Remote path of the file
+/// * `lpath` - Local path to write to
+pub async fn get_remote_file(rpath: &str, lpath: &str) -> Result<(), anyhow::Error> {
+    let lpath = Path::new(lpath);
+    utils::create_dir_path(lpath)?;
+    RouteHelper::download_file(lpath, rpath).await?;
+
+    println!(
+        "Downloaded: {} from {}",
+        lpath.display().to_string().green(),
+        rpath
+    );
+    Ok(())
+}
+
+/// Uploads a file straight to the storage bucket set with `OPSML_STORAGE_URI`
+///
+/// * `lpath` - Local file to upload
+/// * `rpath` - Remote `opsml-root:/` path to write to
+pub async fn put_remote_file(lpath: &str, rpath: &str) -> Result<(), anyhow::Error> {
+    let backend = StorageBackend::from_env()?.with_context(|| {
+        format!(
+            "Uploading needs a storage bucket. Set {}, e.g. {}=s3://bucket/opsml",
+            STORAGE_URI_VAR, STORAGE_URI_VAR
+        )
+    })?;
+    backend.upload(Path::new(lpath), rpath).await?;
+
+    println!("Uploaded: {} to {}", lpath.green(), rpath);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use tokio;
+
+    fn file(name: &str, size: u64) -> types::FileInfo {
+        types::FileInfo {
+            name: name.to_string(),
+            size: Some(size),
+            mtime: None,
+            file_type: None,
+        }
+    }
+
+    #[test]
+    fn test_collapse_and_tree() {
+        let browser = FileBrowser {
+            target: "opsml-root:/OPSML_MODEL_REGISTRY/repo/model/v1.0.0",
+            registry: "model",
+        };
+        let files = vec![
+            file("config.json", 10),
+            file("onnx/model.onnx", 100),
+            file("onnx/tokenizer/vocab.txt", 5),
+        ];
+
+        let collapsed = browser.collapse_to_top_level(files.clone());
+        assert_eq!(collapsed.len(), 2);
+        assert_eq!(collapsed[0].name, "config.json");
+        assert_eq!(collapsed[1].name, "onnx/");
+        assert_eq!(collapsed[1].size, Some(105));
+
+        assert_eq!(
+            browser.render_tree("root", &files),
+            concat!(
+                "root\n",
+                "├── config.json\n",
+                "└── onnx/\n",
+                "    ├── model.onnx\n",
+                "    └── tokenizer/\n",
+                "        └── vocab.txt",
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_relative_files() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url);
+        }
+
+        let files = types::ListFileResponse {
+            files: vec![
+                "opsml-root:/OPSML_MODEL_REGISTRY/repo/model/v1.0.0/tokenizer.json".to_string(),
+                "opsml-root:/OPSML_MODEL_REGISTRY/repo/model/v1.0.0/model.onnx".to_string(),
+            ],
+        };
+        let mock_list = server
+            .mock(
+                "GET",
+                "/opsml/files/list?path=opsml-root%3A%2FOPSML_MODEL_REGISTRY%2Frepo%2Fmodel%2Fv1.0.0",
+            )
+            .with_status(200)
+            .with_body(serde_json::to_string(&files).unwrap())
+            .create();
+
+        let browser = FileBrowser {
+            target: "opsml-root:/OPSML_MODEL_REGISTRY/repo/model/v1.0.0/",
+            registry: "model",
+        };
+        let root = browser.resolve_root().await.unwrap();
+        let files = browser.relative_files(&root).await.unwrap();
+        mock_list.assert();
+
+        assert_eq!(files[0].name, "model.onnx");
+        assert_eq!(files[1].name, "tokenizer.json");
+        assert_eq!(files[0].size, None);
+    }
+
+    #[tokio::test]
+    async fn test_write_stream_head() {
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> = vec![
+            Ok(b"line1\nline2\nli".to_vec()),
+            Ok(b"ne3\nline4\n".to_vec()),
+        ];
+
+        let mut output: Vec<u8> = Vec::new();
+        write_stream(
+            futures::stream::iter(chunks.into_iter()),
+            &mut output,
+            Some(3),
+        )
+        .await
+        .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "line1\nline2\nline3\n");
+
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> =
+            vec![Ok(b"a\nb".to_vec()), Ok(b"\nc".to_vec())];
+        let mut output: Vec<u8> = Vec::new();
+        write_stream(futures::stream::iter(chunks.into_iter()), &mut output, None)
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "a\nb\nc");
+    }
+
+    #[tokio::test]
+    async fn test_get_presigned_url() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url.clone());
+        }
+
+        let presigned_url = types::PresignedUrl {
+            url: format!("{}/get", url),
+        };
+        let mock_presigned = server
+            .mock("GET", "/opsml/files/presigned?path=config.json&method=GET")
+            .with_status(200)
+            .with_body(serde_json::to_string(&presigned_url).unwrap())
+            .create();
+
+        let response = RouteHelper::get_presigned_url("config.json").await.unwrap();
+        mock_presigned.assert();
+        assert_eq!(response.url, presigned_url.url);
+    }
+}
@@ -0,0 +1,602 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::model::{download_model, MODEL_METADATA_FILE};
+use crate::api::types;
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(unix)]
+use std::os::unix::fs::symlink;
+#[cfg(windows)]
+use std::os::windows::fs::symlink_dir as symlink;
+
+#[cfg(windows)]
+use std::fs::remove_dir as remove_link;
+#[cfg(unix)]
+use std::fs::remove_file as remove_link;
+
+/// Symlink in a model directory pointing at the version in use
+pub const CURRENT_LINK: &str = "current";
+
+/// Orders semver versions first, then other version strings in string order
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let key = |version: &str| {
+        let parsed = semver::Version::parse(version).ok();
+        (parsed.is_none(), parsed)
+    };
+
+    key(a).cmp(&key(b)).then_with(|| a.cmp(b))
+}
+
+/// Checks that a model name or version read from metadata names a single directory, so it
+/// cannot write outside the write directory or clash with hidden staging directories
+///
+/// # Arguments
+///
+/// * `value` - Name or version
+/// * `field` - Field the value was read from
+///
+fn directory_name<'a>(value: &'a str, field: &str) -> Result<&'a str, anyhow::Error> {
+    let mut components = Path::new(value).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) if name == value && !value.starts_with('.') => {
+            Ok(value)
+        }
+        _ => Err(anyhow::Error::msg(format!(
+            "Model {} {:?} is not a valid directory name",
+            field,
+            value.red()
+        ))),
+    }
+}
+
+/// Lists the versions downloaded to a model directory, oldest first
+///
+/// # Arguments
+///
+/// * `model_dir` - Directory holding one directory per version
+///
+pub fn list_versions(model_dir: &Path) -> Result<Vec<String>, anyhow::Error> {
+    let mut versions = Vec::new();
+
+    for entry in fs::read_dir(model_dir)
+        .with_context(|| format!("Failed to read {}", model_dir.display()))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // skips the current link and in-flight downloads
+        // versions replaced in place are links to their directory
+        if name == CURRENT_LINK || name.starts_with('.') || !entry.path().is_dir() {
+            continue;
+        }
+        versions.push(name);
+    }
+
+    versions.sort_by(|a, b| compare_versions(a, b));
+    Ok(versions)
+}
+
+/// Version the `current` link of a model directory points at, if any
+pub fn current_version(model_dir: &Path) -> Option<String> {
+    fs::read_link(model_dir.join(CURRENT_LINK))
+        .ok()
+        .and_then(|target| {
+            target
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+}
+
+/// Points a link in a model directory at a target. The new link is created next to the old one
+/// and renamed over it, so readers never see a missing or half written link
+///
+/// # Arguments
+///
+/// * `model_dir` - Directory holding one directory per version
+/// * `name` - Name of the link
+/// * `target` - Entry of the model directory to point at
+///
+fn swap_link(model_dir: &Path, name: &str, target: &str) -> Result<(), anyhow::Error> {
+    let link = model_dir.join(name);
+    let staged = model_dir.join(format!(".{}-link-{}", name, std::process::id()));
+    if staged.symlink_metadata().is_ok() {
+        remove_link(&staged)?;
+    }
+
+    // relative targets keep the directory valid when it is moved or mounted elsewhere
+    symlink(target, &staged).with_context(|| format!("Failed to link {}", staged.display()))?;
+    fs::rename(&staged, &link).with_context(|| format!("Failed to update {}", link.display()))?;
+
+    Ok(())
+}
+
+/// Points the `current` link at a version without a moment where it is missing
+///
+/// # Arguments
+///
+/// * `model_dir` - Directory holding one directory per version
+/// * `version` - Version to point at
+///
+pub fn set_current(model_dir: &Path, version: &str) -> Result<(), anyhow::Error> {
+    if !model_dir.join(version).is_dir() {
+        return Err(anyhow::Error::msg(format!(
+            "Version {} is not in {}",
+            version.red(),
+            model_dir.display()
+        )));
+    }
+
+    swap_link(model_dir, CURRENT_LINK, version)
+}
+
+/// Removes a version, along with the directory it links to when it was replaced in place
+fn remove_version(version_path: &Path) -> Result<(), anyhow::Error> {
+    if version_path.symlink_metadata()?.file_type().is_symlink() {
+        let target = version_path.with_file_name(fs::read_link(version_path)?);
+        remove_link(version_path)?;
+        if target.exists() {
+            fs::remove_dir_all(&target)?;
+        }
+        return Ok(());
+    }
+
+    fs::remove_dir_all(version_path)?;
+    Ok(())
+}
+
+/// Removes the oldest versions until `keep` remain. The current version is never removed
+///
+/// # Arguments
+///
+/// * `model_dir` - Directory holding one directory per version
+/// * `keep` - Number of versions to keep
+///
+/// # Returns
+/// * `Result<Vec<String>, String>` - Removed versions
+///
+pub fn prune_versions(model_dir: &Path, keep: usize) -> Result<Vec<String>, anyhow::Error> {
+    let current = current_version(model_dir);
+    let versions = list_versions(model_dir)?;
+    let removable = versions.len().saturating_sub(keep);
+
+    let mut removed = Vec::new();
+    for version in versions.into_iter().take(removable) {
+        if current.as_deref() == Some(version.as_str()) {
+            continue;
+        }
+        remove_version(&model_dir.join(&version))
+            .with_context(|| format!("Failed to remove version {}", version))?;
+        removed.push(version);
+    }
+
+    Ok(removed)
+}
+
+/// Moves a finished download into place as a version directory. A version downloaded before is
+/// replaced by moving the new copy to a fresh hidden directory and swapping `<version>` for a link
+/// to it, so `current` never points at a missing directory
+///
+/// # Arguments
+///
+/// * `staging` - Finished download
+/// * `model_dir` - Directory holding one directory per version
+/// * `version` - Version of the download
+///
+fn publish_version(staging: &Path, model_dir: &Path, version: &str) -> Result<(), anyhow::Error> {
+    fs::create_dir_all(model_dir)?;
+    let version_path = model_dir.join(version);
+    if version_path.symlink_metadata().is_err() {
+        fs::rename(staging, &version_path)
+            .with_context(|| format!("Failed to move download to {}", version_path.display()))?;
+        return Ok(());
+    }
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    let fresh = format!(".{}-{}-{}", version, std::process::id(), nanos);
+    fs::rename(staging, model_dir.join(&fresh))
+        .with_context(|| format!("Failed to move download to {}", model_dir.display()))?;
+
+    if version_path.symlink_metadata()?.file_type().is_symlink() {
+        let replaced = model_dir.join(fs::read_link(&version_path)?);
+        swap_link(model_dir, version, &fresh)?;
+        if replaced.exists() {
+            fs::remove_dir_all(&replaced)?;
+        }
+        return Ok(());
+    }
+
+    // a directory cannot be swapped for a link in one step, so `current` is moved to the new copy
+    // until the link is in place
+    let is_current = current_version(model_dir).as_deref() == Some(version);
+    if is_current {
+        swap_link(model_dir, CURRENT_LINK, &fresh)?;
+    }
+    fs::remove_dir_all(&version_path)?;
+    swap_link(model_dir, version, &fresh)?;
+    if is_current {
+        swap_link(model_dir, CURRENT_LINK, version)?;
+    }
+    Ok(())
+}
+
+pub struct VersionedDownloader<'a> {
+    pub name: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub repository: Option<&'a str>,
+    pub uid: Option<&'a str>,
+    pub write_dir: &'a str,
+    pub onnx: &'a bool,
+    pub quantize: &'a bool,
+    pub preprocessor: &'a bool,
+    pub ignore_release_candidates: &'a bool,
+    pub dry_run: &'a bool,
+    pub include: &'a [String],
+    pub exclude: &'a [String],
+    pub keep: usize,
+}
+
+impl VersionedDownloader<'_> {
+    /// Downloads the model to a staging directory in the write directory
+    ///
+    /// # Returns
+    /// * `Result<types::ModelMetadata, String>` - Metadata of the downloaded model
+    ///
+    async fn download_staged(&self, staging: &Path) -> Result<types::ModelMetadata, anyhow::Error> {
+        download_model(
+            self.name,
+            self.version,
+            self.repository,
+            self.uid,
+            &staging.to_string_lossy(),
+            self.onnx,
+            self.quantize,
+            self.preprocessor,
+            self.ignore_release_candidates,
+            &false,
+            self.include,
+            self.exclude,
+        )
+        .await?;
+
+        let metadata_path = staging.join(MODEL_METADATA_FILE);
+        serde_json::from_str(
+            &fs::read_to_string(&metadata_path)
+                .with_context(|| format!("Failed to read {}", metadata_path.display()))?,
+        )
+        .with_context(|| "Failed to parse model Metadata")
+    }
+
+    /// Downloads a version, switches `current` to it once the download is complete and prunes
+    /// old versions
+    async fn download(&self) -> Result<(), anyhow::Error> {
+        if self.dry_run == &true {
+            return Err(anyhow::Error::msg(
+                "--dry-run is not supported with --layout versioned",
+            ));
+        }
+
+        let staging = Path::new(self.write_dir).join(format!(".download-{}", std::process::id()));
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+
+        let metadata = match self.download_staged(&staging).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                if staging.exists() {
+                    fs::remove_dir_all(&staging)?;
+                }
+                return Err(e);
+            }
+        };
+
+        let published = directory_name(&metadata.model_name, "name").and_then(|name| {
+            let version = directory_name(&metadata.model_version, "version")?;
+            let model_dir = Path::new(self.write_dir).join(name);
+            publish_version(&staging, &model_dir, version)?;
+            Ok(model_dir)
+        });
+        let model_dir = match published {
+            Ok(model_dir) => model_dir,
+            Err(e) => {
+                if staging.exists() {
+                    fs::remove_dir_all(&staging)?;
+                }
+                return Err(e);
+            }
+        };
+        set_current(&model_dir, &metadata.model_version)?;
+
+        println!(
+            "{} now points at {}",
+            model_dir.join(CURRENT_LINK).display().to_string().green(),
+            metadata.model_version.green()
+        );
+
+        let removed = prune_versions(&model_dir, self.keep)?;
+        if !removed.is_empty() {
+            println!("Removed old version(s): {}", removed.join(", "));
+        }
+
+        Ok(())
+    }
+}
+
+/// Downloads a model to `<write_dir>/<name>/<version>/` and points `<write_dir>/<name>/current`
+/// at it
+///
+/// # Arguments
+///
+/// * `downloader` - Model to download and the number of versions to keep
+///
+pub async fn download_model_versioned(
+    downloader: &VersionedDownloader<'_>,
+) -> Result<(), anyhow::Error> {
+    downloader.download().await
+}
+
+/// Points `current` back at an earlier version. Without a version the newest version older than
+/// the current one is used
+///
+/// # Arguments
+///
+/// * `write_dir` - Write directory the model was downloaded to
+/// * `name` - Model name
+/// * `to` - Version to roll back to
+///
+pub fn rollback(write_dir: &str, name: &str, to: Option<&str>) -> Result<(), anyhow::Error> {
+    let model_dir: PathBuf = Path::new(write_dir).join(directory_name(name, "name")?);
+    let current = current_version(&model_dir);
+
+    let target = match to {
+        Some(version) => {
+            let version = directory_name(version, "version")?;
+            if !list_versions(&model_dir)?
+                .iter()
+                .any(|other| other == version)
+            {
+                return Err(anyhow::Error::msg(format!(
+                    "Version {} is not in {}",
+                    version.red(),
+                    model_dir.display()
+                )));
+            }
+            version.to_string()
+        }
+        None => {
+            let current = current
+                .as_deref()
+                .with_context(|| format!("{} has no current version", model_dir.display()))?;
+            list_versions(&model_dir)?
+                .into_iter()
+                .rev()
+                .find(|version| compare_versions(version, current) == Ordering::Less)
+                .with_context(|| format!("No version older than {} to roll back to", current))?
+        }
+    };
+
+    set_current(&model_dir, &target)?;
+    println!(
+        "Rolled back {} from {} to {}",
+        name.green(),
+        current.unwrap_or("none".to_string()),
+        target.green()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn model_dir(versions: &[&str]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("opsml-layout-{}", uuid::Uuid::new_v4()));
+        for version in versions {
+            fs::create_dir_all(dir.join(version)).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_prune_and_rollback() {
+        let dir = model_dir(&["1.0.0", "1.2.0", "1.10.0", "1.1.0"]);
+        assert_eq!(
+            list_versions(&dir).unwrap(),
+            vec!["1.0.0", "1.1.0", "1.2.0", "1.10.0"]
+        );
+
+        // the current version survives pruning even when it is old
+        set_current(&dir, "1.0.0").unwrap();
+        assert_eq!(prune_versions(&dir, 2).unwrap(), vec!["1.1.0"]);
+        assert_eq!(
+            list_versions(&dir).unwrap(),
+            vec!["1.0.0", "1.2.0", "1.10.0"]
+        );
+
+        set_current(&dir, "1.10.0").unwrap();
+        assert_eq!(current_version(&dir).as_deref(), Some("1.10.0"));
+
+        let write_dir = dir.parent().unwrap().to_str().unwrap();
+        let name = dir.file_name().unwrap().to_string_lossy().to_string();
+        rollback(write_dir, &name, None).unwrap();
+        assert_eq!(current_version(&dir).as_deref(), Some("1.2.0"));
+        assert_eq!(
+            fs::read_link(dir.join(CURRENT_LINK)).unwrap(),
+            PathBuf::from("1.2.0")
+        );
+
+        rollback(write_dir, &name, Some("1.10.0")).unwrap();
+        assert_eq!(current_version(&dir).as_deref(), Some("1.10.0"));
+        assert!(rollback(write_dir, &name, Some("2.0.0")).is_err());
+
+        // only downloaded versions of a model in the write directory can be rolled back to
+        fs::create_dir_all(dir.join(".1.0.0-staged")).unwrap();
+        for to in ["..", ".1.0.0-staged", "../1.0.0"] {
+            assert!(rollback(write_dir, &name, Some(to)).is_err(), "{}", to);
+        }
+        assert!(rollback(write_dir, "..", Some("1.0.0")).is_err());
+        assert_eq!(current_version(&dir).as_deref(), Some("1.10.0"));
+
+        set_current(&dir, "1.0.0").unwrap();
+        let err = rollback(write_dir, &name, None).unwrap_err();
+        assert!(err.to_string().contains("No version older than 1.0.0"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        let mut versions = vec!["b", "1.10.0", "a", "1.2.0", "1.0.0-rc.1", "1.0.0"];
+        versions.sort_by(|a, b| compare_versions(a, b));
+        assert_eq!(
+            versions,
+            vec!["1.0.0-rc.1", "1.0.0", "1.2.0", "1.10.0", "a", "b"]
+        );
+        assert_eq!(compare_versions("1.0.0", "latest"), Ordering::Less);
+        assert_eq!(compare_versions("latest", "1.0.0"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_directory_name() {
+        assert_eq!(directory_name("1.0.0", "version").unwrap(), "1.0.0");
+        for value in ["", "..", ".", "../model", "a/b", "/abs", "a/", ".hidden"] {
+            assert!(directory_name(value, "name").is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_publish_current_version() {
+        let dir = model_dir(&["1.0.0"]);
+        fs::write(dir.join("1.0.0/model.onnx"), "old").unwrap();
+        set_current(&dir, "1.0.0").unwrap();
+
+        // replacing the current version swaps it for a link to the new copy
+        for bytes in ["new", "newer"] {
+            let staging = dir.parent().unwrap().join(format!(
+                "{}-staging",
+                dir.file_name().unwrap().to_string_lossy()
+            ));
+            fs::create_dir_all(&staging).unwrap();
+            fs::write(staging.join("model.onnx"), bytes).unwrap();
+
+            publish_version(&staging, &dir, "1.0.0").unwrap();
+            assert_eq!(
+                fs::read_to_string(dir.join(CURRENT_LINK).join("model.onnx")).unwrap(),
+                bytes
+            );
+        }
+        assert_eq!(current_version(&dir).as_deref(), Some("1.0.0"));
+        assert_eq!(list_versions(&dir).unwrap(), vec!["1.0.0"]);
+
+        // the replaced copies are gone and pruning removes the link with its directory
+        let hidden = |dir: &Path| {
+            fs::read_dir(dir)
+                .unwrap()
+                .filter(|entry| {
+                    entry
+                        .as_ref()
+                        .unwrap()
+                        .file_name()
+                        .to_string_lossy()
+                        .starts_with('.')
+                })
+                .count()
+        };
+        assert_eq!(hidden(&dir), 1);
+        fs::create_dir_all(dir.join("2.0.0")).unwrap();
+        set_current(&dir, "2.0.0").unwrap();
+        assert_eq!(prune_versions(&dir, 1).unwrap(), vec!["1.0.0"]);
+        assert_eq!(hidden(&dir), 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_model_versioned() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url.clone());
+        }
+
+        let metadata = fs::read_to_string("./src/api/test_utils/metadata.json").unwrap();
+        let _mock_metadata = server
+            .mock("POST", "/opsml/models/metadata")
+            .with_status(201)
+            .with_body(metadata)
+            .create();
+        let _mock_list = server
+            .mock("GET", "/opsml/files/list?path=models.json")
+            .with_status(200)
+            .with_body(
+                r#"{"files": ["opsml-root:/OPSML_MODEL_REGISTRY/devops-ml/linear-reg-model/v1.1.0/model.onnx"]}"#,
+            )
+            .create();
+        let _mock_presigned = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/opsml/files/presigned".to_string()),
+            )
+            .with_status(200)
+            .with_body(format!(r#"{{"url": "{}/model-bytes"}}"#, url))
+            .create();
+        let _mock_download = server
+            .mock("GET", "/model-bytes")
+            .with_status(200)
+            .with_body("onnx bytes")
+            .create();
+
+        let write_dir = model_dir(&[]);
+        let model_dir = write_dir.join("linear-reg-model");
+
+        // an older version that the download should replace as current
+        fs::create_dir_all(model_dir.join("1.0.0")).unwrap();
+        set_current(&model_dir, "1.0.0").unwrap();
+
+        let downloader = VersionedDownloader {
+            name: None,
+            version: None,
+            repository: None,
+            uid: Some("1234567890"),
+            write_dir: write_dir.to_str().unwrap(),
+            onnx: &true,
+            quantize: &false,
+            preprocessor: &false,
+            ignore_release_candidates: &false,
+            dry_run: &false,
+            include: &[],
+            exclude: &[],
+            keep: 2,
+        };
+
+        // downloading the same version twice replaces it in place
+        for _ in 0..2 {
+            download_model_versioned(&downloader).await.unwrap();
+        }
+
+        assert_eq!(current_version(&model_dir).as_deref(), Some("1.1.0"));
+        assert_eq!(
+            fs::read_to_string(model_dir.join(CURRENT_LINK).join("model.onnx")).unwrap(),
+            "onnx bytes"
+        );
+        assert!(model_dir.join("1.1.0").join(MODEL_METADATA_FILE).exists());
+        assert_eq!(list_versions(&model_dir).unwrap(), vec!["1.0.0", "1.1.0"]);
+
+        // only in-place versions and the link are left behind
+        let leftovers = fs::read_dir(&write_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(leftovers, vec!["linear-reg-model"]);
+
+        fs::remove_dir_all(write_dir).unwrap();
+    }
+}
@@ -0,0 +1,436 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::cards;
+use crate::api::metrics::{MetricGetter, MetricView};
+use crate::api::types;
+use anyhow::{Context, Result};
+use futures::future::try_join_all;
+use owo_colors::OwoColorize;
+use semver::Version;
+use serde_json::Value;
+use tabled::builder::Builder;
+use tabled::settings::style::Style;
+use tabled::settings::Alignment;
+
+pub struct MetricMatrix<'a> {
+    pub uids: Option<&'a [String]>,
+    pub name: Option<&'a str>,
+    pub repository: Option<&'a str>,
+    pub versions: Option<&'a str>,
+    pub metric_names: Option<&'a [String]>,
+    pub lower_is_better: &'a [String],
+    pub higher_is_better: &'a [String],
+}
+
+/// Parses an inclusive version range such as `1.0.0..1.4.0`, `1.0.0..` or `..1.4.0`.
+/// A single version matches only itself
+///
+/// # Arguments
+///
+/// * `range` - Version range string
+///
+fn parse_version_range(range: &str) -> Result<(Option<Version>, Option<Version>), anyhow::Error> {
+    let parse = |version: &str| -> Result<Option<Version>, anyhow::Error> {
+        let version = version.trim_start_matches('=').trim();
+        if version.is_empty() {
+            Ok(None)
+        } else {
+            Version::parse(version)
+                .map(Some)
+                .with_context(|| format!("Invalid version {}", version.red()))
+        }
+    };
+
+    match range.split_once("..") {
+        Some((start, end)) => Ok((parse(start)?, parse(end)?)),
+        None => {
+            let version = parse(range)?;
+            Ok((version.clone(), version))
+        }
+    }
+}
+
+impl MetricMatrix<'_> {
+    /// Resolves the runs to compare as (column label, run uid) pairs.
+    /// Run uids are used as is, model versions are resolved to their run uid in version order
+    async fn resolve_runs(&self) -> Result<Vec<(String, String)>, anyhow::Error> {
+        if let Some(uids) = self.uids {
+            return Ok(uids.iter().map(|uid| (uid.clone(), uid.clone())).collect());
+        }
+
+        let (name, repository) = match (self.name, self.repository) {
+            (Some(name), Some(repository)) => (name, repository),
+            _ => {
+                return Err(anyhow::Error::msg(
+                    "Please provide either a list of uids or a name and repository",
+                ))
+            }
+        };
+
+        let cards = cards::fetch_cards(
+            "model",
+            Some(name),
+            Some(repository),
+            None,
+            None,
+            None,
+            false,
+        )
+        .await?;
+        let cards = self.select_versions(cards.cards)?;
+
+        let mut runs = Vec::new();
+        for card in cards.into_iter() {
+            match card.runcard_uid {
+                Some(run_uid) => runs.push((card.version, run_uid)),
+                None => eprintln!(
+                    "Skipping version {}: model is not associated with a run",
+                    card.version.yellow()
+                ),
+            }
+        }
+
+        Ok(runs)
+    }
+
+    /// Keeps cards whose version falls in the requested range, sorted by version.
+    /// Versions that are not semver are kept after the others when no range is given
+    ///
+    /// # Arguments
+    ///
+    /// * `cards` - Model cards
+    ///
+    fn select_versions(&self, cards: Vec<types::Card>) -> Result<Vec<types::Card>, anyhow::Error> {
+        let (start, end) = match self.versions {
+            Some(range) => parse_version_range(range)?,
+            None => (None, None),
+        };
+
+        let has_range = start.is_some() || end.is_some();
+        let mut selected: Vec<(Option<Version>, types::Card)> = cards
+            .into_iter()
+            .map(|card| (Version::parse(&card.version).ok(), card))
+            .filter(|(version, _)| match version {
+                Some(version) => {
+                    start.as_ref().is_none_or(|start| version >= start)
+                        && end.as_ref().is_none_or(|end| version <= end)
+                }
+                // versions that are not semver can only be placed when no range is given
+                None => !has_range,
+            })
+            .collect();
+
+        // semver versions first, then other version strings in string order
+        selected.sort_by(|a, b| {
+            (a.0.is_none(), &a.0, &a.1.version).cmp(&(b.0.is_none(), &b.0, &b.1.version))
+        });
+        Ok(selected.into_iter().map(|(_, card)| card).collect())
+    }
+
+    /// Pivots metrics into rows of metric values per run. The value at the last step is used
+    /// for metrics logged more than once
+    ///
+    /// # Arguments
+    ///
+    /// * `run_metrics` - Filtered metrics for each run, in column order
+    ///
+    /// # Returns
+    /// * `Vec<(String, Vec<Option<Value>>)>` - One row per metric name
+    fn pivot(&self, run_metrics: &[Vec<types::Metric>]) -> Vec<(String, Vec<Option<Value>>)> {
+        let mut rows: Vec<(String, Vec<Option<Value>>)> = Vec::new();
+
+        for (column, metrics) in run_metrics.iter().enumerate() {
+            for metric in metrics.iter() {
+                let row = match rows.iter().position(|(name, _)| name == &metric.name) {
+                    Some(index) => index,
+                    None => {
+                        rows.push((metric.name.clone(), vec![None; run_metrics.len()]));
+                        rows.len() - 1
+                    }
+                };
+                // metrics are sorted by step so the last write wins
+                rows[row].1[column] = Some(metric.value.clone());
+            }
+        }
+
+        rows
+    }
+
+    /// Finds the column holding the best value for a metric
+    ///
+    /// # Arguments
+    ///
+    /// * `metric` - Metric name
+    /// * `values` - Metric value per run
+    ///
+    fn best_column(&self, metric: &str, values: &[Option<Value>]) -> Option<usize> {
+        let lower = self.lower_is_better.iter().any(|m| m == metric);
+        let higher = self.higher_is_better.iter().any(|m| m == metric);
+
+        if !lower && !higher {
+            return None;
+        }
+
+        let numeric = values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, value)| value.as_ref().and_then(Value::as_f64).map(|v| (i, v)));
+
+        let best = if lower {
+            numeric.min_by(|a, b| a.1.total_cmp(&b.1))
+        } else {
+            numeric.max_by(|a, b| a.1.total_cmp(&b.1))
+        };
+
+        best.map(|(i, _)| i)
+    }
+
+    /// Renders the pivoted metrics with the best value of each row highlighted
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - Column labels
+    /// * `rows` - Pivoted metric rows
+    ///
+    fn render(&self, columns: &[String], rows: &[(String, Vec<Option<Value>>)]) -> String {
+        let mut builder = Builder::default();
+
+        let mut header = vec!["metric".to_string()];
+        header.extend(columns.iter().cloned());
+        builder.set_header(header);
+
+        for (metric, values) in rows.iter() {
+            let best = self.best_column(metric, values);
+            let mut record = vec![metric.clone()];
+
+            for (i, value) in values.iter().enumerate() {
+                let cell = value
+                    .as_ref()
+                    .map(|value| value.to_string())
+                    .unwrap_or("-".to_string());
+
+                if best == Some(i) {
+                    record.push(cell.green().bold().to_string());
+                } else {
+                    record.push(cell);
+                }
+            }
+
+            builder.push_record(record);
+        }
+
+        builder
+            .build()
+            .with(Alignment::center())
+            .with(Style::sharp())
+            .to_string()
+    }
+
+    /// Fetches metrics for every run concurrently and prints the matrix
+    async fn show(&self) -> Result<(), anyhow::Error> {
+        let runs = self.resolve_runs().await?;
+
+        if runs.is_empty() {
+            return Err(anyhow::Error::msg("No runs found to compare"));
+        }
+
+        let metric_getter = MetricGetter {};
+        let view = MetricView {
+            metric_names: self.metric_names,
+            ..Default::default()
+        };
+
+        let run_metrics = try_join_all(
+            runs.iter()
+                .map(|(_, run_uid)| metric_getter.fetch_metrics(run_uid)),
+        )
+        .await?
+        .into_iter()
+        .map(|metrics| metric_getter.filter_metrics(metrics, &view))
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let columns: Vec<String> = runs.into_iter().map(|(label, _)| label).collect();
+        let rows = self.pivot(&run_metrics);
+
+        println!("\nMetrics Matrix");
+        println!("{}", self.render(&columns, &rows));
+
+        Ok(())
+    }
+}
+
+/// Compares metrics across many runs in one table
+///
+/// # Arguments
+///
+/// * `uids` - Run uids to compare
+/// * `name` - Model name
+/// * `repository` - Model repository
+/// * `versions` - Inclusive range of model versions (e.g. 1.0.0..1.4.0)
+/// * `metric_names` - Only show these metrics
+/// * `lower_is_better` - Metrics where the lowest value is best
+/// * `higher_is_better` - Metrics where the highest value is best
+#[allow(clippy::too_many_arguments)]
+pub async fn metrics_matrix(
+    uids: Option<&[String]>,
+    name: Option<&str>,
+    repository: Option<&str>,
+    versions: Option<&str>,
+    metric_names: Option<&[String]>,
+    lower_is_better: &[String],
+    higher_is_better: &[String],
+) -> Result<(), anyhow::Error> {
+    let matrix = MetricMatrix {
+        uids,
+        name,
+        repository,
+        versions,
+        metric_names,
+        lower_is_better,
+        higher_is_better,
+    };
+    matrix.show().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::env;
+    use tokio;
+
+    fn card(version: &str, runcard_uid: Option<&str>) -> types::Card {
+        types::Card {
+            name: "model".to_string(),
+            repository: "repo".to_string(),
+            date: None,
+            contact: "contact".to_string(),
+            version: version.to_string(),
+            uid: format!("uid-{}", version),
+            tags: HashMap::new(),
+            runcard_uid: runcard_uid.map(|uid| uid.to_string()),
+        }
+    }
+
+    fn metric(run_uid: &str, name: &str, value: f64) -> types::Metric {
+        types::Metric {
+            run_uid: run_uid.to_string(),
+            name: name.to_string(),
+            value: value.into(),
+            step: None,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn test_select_versions() {
+        let matrix = MetricMatrix {
+            uids: None,
+            name: Some("model"),
+            repository: Some("repo"),
+            versions: Some("1.0.0..1.4.0"),
+            metric_names: None,
+            lower_is_better: &[],
+            higher_is_better: &[],
+        };
+
+        let cards = vec![
+            card("1.5.0", Some("e")),
+            card("1.10.0", Some("f")),
+            card("1.4.0", Some("d")),
+            card("1.0.0", Some("a")),
+            card("0.9.0", Some("z")),
+            card("1.2.0", None),
+            card("latest", None),
+        ];
+        let versions: Vec<String> = matrix
+            .select_versions(cards.clone())
+            .unwrap()
+            .into_iter()
+            .map(|card| card.version)
+            .collect();
+        assert_eq!(versions, vec!["1.0.0", "1.2.0", "1.4.0"]);
+
+        // without a range, non-semver versions are kept after the others
+        let matrix = MetricMatrix {
+            versions: None,
+            ..matrix
+        };
+        let versions: Vec<String> = matrix
+            .select_versions(cards)
+            .unwrap()
+            .into_iter()
+            .map(|card| card.version)
+            .collect();
+        assert_eq!(
+            versions,
+            vec!["0.9.0", "1.0.0", "1.2.0", "1.4.0", "1.5.0", "1.10.0", "latest"]
+        );
+
+        assert!(parse_version_range("1.0..").is_err());
+    }
+
+    #[test]
+    fn test_pivot_and_best() {
+        let lower = vec!["mae".to_string()];
+        let higher = vec!["r2".to_string()];
+        let matrix = MetricMatrix {
+            uids: None,
+            name: None,
+            repository: None,
+            versions: None,
+            metric_names: None,
+            lower_is_better: &lower,
+            higher_is_better: &higher,
+        };
+
+        let run_metrics = vec![
+            vec![metric("a", "mae", 5.0), metric("a", "r2", 0.7)],
+            vec![metric("b", "mae", 3.0), metric("b", "mape", 1.0)],
+            vec![metric("c", "mae", 4.0), metric("c", "r2", 0.9)],
+        ];
+
+        let rows = matrix.pivot(&run_metrics);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1].0, "r2");
+        assert_eq!(rows[1].1[1], None);
+        assert_eq!(rows[2].0, "mape");
+
+        assert_eq!(matrix.best_column("mae", &rows[0].1), Some(1));
+        assert_eq!(matrix.best_column("r2", &rows[1].1), Some(2));
+        assert_eq!(matrix.best_column("mape", &rows[2].1), None);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_matrix() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url);
+        }
+
+        let mut mocks = Vec::new();
+        for (uid, value) in [("a", 5.0), ("b", 3.0)] {
+            let response = types::ListMetricResponse {
+                metric: vec![metric(uid, "mae", value)],
+            };
+            mocks.push(
+                server
+                    .mock("GET", format!("/opsml/metrics?run_uid={}", uid).as_str())
+                    .with_status(200)
+                    .with_body(serde_json::to_string(&response).unwrap())
+                    .create(),
+            );
+        }
+
+        let uids = vec!["a".to_string(), "b".to_string()];
+        metrics_matrix(Some(&uids), None, None, None, None, &[], &[])
+            .await
+            .unwrap();
+
+        for mock in mocks.iter() {
+            mock.assert();
+        }
+    }
+}
@@ -1,34 +1,134 @@
 /// Copyright (c) Shipt, Inc.
 /// This source code is licensed under the MIT license found in the
 /// LICENSE file in the root directory of this source tree.
+use crate::api::api_version::ApiVersion;
+use crate::api::cards;
+use crate::api::export;
 use crate::api::route_helper::RouteHelper;
 use crate::api::types;
 use crate::api::utils;
 use anyhow::Context;
+use owo_colors::OwoColorize;
+use serde::Deserialize;
+use std::path::Path;
 use tabled::settings::style::Style;
 use tabled::{settings::Alignment, Table};
 
-struct MetricGetter {}
+const SPARKLINE_TICKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const SPARKLINE_WIDTH: usize = 60;
+
+/// Controls which metrics are shown and how they are rendered
+#[derive(Default)]
+pub struct MetricView<'a> {
+    pub metric_names: Option<&'a [String]>,
+    pub step_range: Option<&'a str>,
+    pub aggregation: Option<types::MetricAggregation>,
+    pub chart: bool,
+    pub format: types::MetricFormat,
+    pub export: Option<&'a str>,
+}
+
+/// Parses an inclusive step range such as `100..500`, `100..` or `..500`
+///
+/// # Arguments
+///
+/// * `range` - Step range string
+///
+fn parse_step_range(range: &str) -> Result<(Option<f64>, Option<f64>), anyhow::Error> {
+    let (start, end) = range
+        .split_once("..")
+        .with_context(|| format!("Invalid step range {}. Expected start..end", range.red()))?;
+
+    let parse_bound = |bound: &str| -> Result<Option<f64>, anyhow::Error> {
+        let bound = bound.trim_start_matches('=').trim();
+        if bound.is_empty() {
+            Ok(None)
+        } else {
+            bound
+                .parse::<f64>()
+                .map(Some)
+                .with_context(|| format!("Invalid step {} in range {}", bound.red(), range))
+        }
+    };
+
+    Ok((parse_bound(start)?, parse_bound(end)?))
+}
+
+/// Parses a metric list response. The v2 api wraps metrics in an object while the v3 api returns
+/// them as a list
+///
+/// # Arguments
+///
+/// * `response` - Response body
+/// * `version` - Api version of the server
+///
+pub fn parse_metrics(
+    response: &str,
+    version: ApiVersion,
+) -> Result<Vec<types::Metric>, anyhow::Error> {
+    match version {
+        ApiVersion::V2 => {
+            let metrics: types::ListMetricResponse = serde_json::from_str(response)
+                .with_context(|| "Failed to load response to MetricResponse JSON")?;
+            Ok(metrics.metric)
+        }
+        ApiVersion::V3 => serde_json::from_str(response)
+            .with_context(|| "Failed to load v3 metric list response JSON"),
+    }
+}
+
+/// Renders a series of values as a unicode sparkline. Long series are averaged into buckets
+///
+/// # Arguments
+///
+/// * `values` - Values in step order
+/// * `width` - Maximum number of characters
+///
+fn sparkline(values: &[f64], width: usize) -> String {
+    if values.is_empty() {
+        return String::new();
+    }
+
+    let bucket_size = values.len().div_ceil(width);
+    let points: Vec<f64> = values
+        .chunks(bucket_size)
+        .map(|chunk| chunk.iter().sum::<f64>() / chunk.len() as f64)
+        .collect();
+
+    let min = points.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = points.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let span = max - min;
+
+    points
+        .iter()
+        .map(|point| {
+            let index = if span > 0.0 {
+                (((point - min) / span) * (SPARKLINE_TICKS.len() - 1) as f64).round() as usize
+            } else {
+                0
+            };
+            SPARKLINE_TICKS[index]
+        })
+        .collect()
+}
+
+pub struct MetricGetter {}
 
 impl MetricGetter {
-    /// Parse metric response
+    /// Creates a table of metrics
     ///
     /// # Arguments
     ///
-    /// * `response` - Response from server
+    /// * `metrics` - Metrics to display
     ///
     /// # Returns
     ///  String - Table of metrics
     ///
-    fn parse_metric_response(&self, response: &str) -> Result<String, anyhow::Error> {
-        // Parses response and creates a table
-
-        let metrics: types::ListMetricResponse =
-            serde_json::from_str(response).expect("Failed to load response to MetricResponse JSON");
-
+    #[allow(clippy::unnecessary_unwrap)]
+    pub fn metric_table(&self, metrics: &[types::Metric]) -> Result<String, anyhow::Error> {
         let mut metric_table: Vec<types::MetricTable> = Vec::new();
 
-        for metric in metrics.metric.iter() {
+        for metric in metrics.iter() {
             let step = if metric.step.is_some() {
                 metric
                     .step
@@ -61,23 +161,209 @@ impl MetricGetter {
         Ok(metric_table)
     }
 
-    /// Get model metrics
-    pub async fn get_model_metrics(&self, uid: &str) -> Result<(), anyhow::Error> {
-        // if name and version then get most recent uid
+    /// Filters metrics by name and step range. Metrics are grouped by name (in order of first
+    /// appearance) and sorted by step
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - Metrics returned by the server
+    /// * `view` - Metric view options
+    ///
+    pub fn filter_metrics(
+        &self,
+        metrics: Vec<types::Metric>,
+        view: &MetricView,
+    ) -> Result<Vec<types::Metric>, anyhow::Error> {
+        let step_range = view.step_range.map(parse_step_range).transpose()?;
+
+        let mut names: Vec<String> = Vec::new();
+        let mut filtered: Vec<types::Metric> = Vec::new();
+
+        for metric in metrics.into_iter() {
+            if let Some(metric_names) = view.metric_names {
+                if !metric_names.contains(&metric.name) {
+                    continue;
+                }
+            }
+
+            if let Some((start, end)) = step_range {
+                let step = match metric.step.as_ref().and_then(|step| step.as_f64()) {
+                    Some(step) => step,
+                    None => continue,
+                };
+                if start.is_some_and(|start| step < start) || end.is_some_and(|end| step > end) {
+                    continue;
+                }
+            }
+
+            if !names.contains(&metric.name) {
+                names.push(metric.name.clone());
+            }
+            filtered.push(metric);
+        }
+
+        let step_of = |metric: &types::Metric| metric.step.as_ref().and_then(|step| step.as_f64());
+        filtered.sort_by(|a, b| {
+            let a_pos = names.iter().position(|name| name == &a.name);
+            let b_pos = names.iter().position(|name| name == &b.name);
+            a_pos.cmp(&b_pos).then(
+                step_of(a)
+                    .partial_cmp(&step_of(b))
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            )
+        });
+
+        Ok(filtered)
+    }
+
+    /// Groups metrics by name, keeping step order
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - Filtered metrics
+    ///
+    fn group_metrics<'m>(
+        &self,
+        metrics: &'m [types::Metric],
+    ) -> Vec<(&'m str, Vec<&'m types::Metric>)> {
+        let mut groups: Vec<(&str, Vec<&types::Metric>)> = Vec::new();
+
+        for metric in metrics.iter() {
+            match groups.iter_mut().find(|(name, _)| *name == metric.name) {
+                Some((_, group)) => group.push(metric),
+                None => groups.push((&metric.name, vec![metric])),
+            }
+        }
+
+        groups
+    }
+
+    /// Aggregates each metric to a single value
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - Filtered metrics
+    /// * `aggregation` - Aggregation to apply
+    ///
+    /// # Returns
+    ///  String - Table of aggregated metrics
+    ///
+    fn aggregate_table(
+        &self,
+        metrics: &[types::Metric],
+        aggregation: &types::MetricAggregation,
+    ) -> String {
+        let mut summary_table: Vec<types::MetricSummaryTable> = Vec::new();
+
+        for (name, group) in self.group_metrics(metrics).into_iter() {
+            let numeric = || {
+                group
+                    .iter()
+                    .filter_map(|metric| metric.value.as_f64().map(|value| (value, *metric)))
+            };
+
+            let selected = match aggregation {
+                types::MetricAggregation::Last => group.last().copied(),
+                types::MetricAggregation::Min => numeric()
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+                    .map(|(_, metric)| metric),
+                types::MetricAggregation::Max => numeric()
+                    .max_by(|a, b| a.0.total_cmp(&b.0))
+                    .map(|(_, metric)| metric),
+                types::MetricAggregation::Mean => None,
+            };
+
+            let (value, step) = match (aggregation, selected) {
+                (types::MetricAggregation::Mean, _) => {
+                    let values: Vec<f64> = numeric().map(|(value, _)| value).collect();
+                    if values.is_empty() {
+                        continue;
+                    }
+                    let mean = values.iter().sum::<f64>() / values.len() as f64;
+                    (serde_json::Value::from(mean), "-".to_string())
+                }
+                (_, Some(metric)) => (
+                    metric.value.clone(),
+                    metric
+                        .step
+                        .as_ref()
+                        .map(|step| step.to_string())
+                        .unwrap_or("None".to_string()),
+                ),
+                (_, None) => continue,
+            };
+
+            summary_table.push(types::MetricSummaryTable {
+                metric: name.to_string(),
+                aggregation: aggregation.as_str().to_string(),
+                value,
+                step,
+            });
+        }
+
+        Table::new(summary_table)
+            .with(Alignment::center())
+            .with(Style::sharp())
+            .to_string()
+    }
+
+    /// Renders a sparkline per metric
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - Filtered metrics
+    ///
+    /// # Returns
+    ///  String - One sparkline row per metric
+    ///
+    fn chart(&self, metrics: &[types::Metric]) -> String {
+        let groups = self.group_metrics(metrics);
+        let name_width = groups.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+        groups
+            .iter()
+            .map(|(name, group)| {
+                let values: Vec<f64> = group
+                    .iter()
+                    .filter_map(|metric| metric.value.as_f64())
+                    .collect();
+
+                let last = match values.last() {
+                    Some(last) => *last,
+                    None => return format!("{:<width$}  n/a (n=0)", name, width = name_width),
+                };
+                let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
 
+                format!(
+                    "{:<width$}  {}  min {} max {} last {} (n={})",
+                    name,
+                    sparkline(&values, SPARKLINE_WIDTH).green(),
+                    min,
+                    max,
+                    last,
+                    values.len(),
+                    width = name_width
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Fetches all metrics logged to a run
+    ///
+    /// # Arguments
+    ///
+    /// * `uid` - Run uid
+    ///
+    pub async fn fetch_metrics(&self, uid: &str) -> Result<Vec<types::Metric>, anyhow::Error> {
         let params = [("run_uid", uid)];
         let response =
             RouteHelper::make_get_request(&utils::OpsmlPaths::Metric.as_str(), Some(&params))
                 .await?;
 
         if response.status().is_success() {
-            let metric_table = self
-                .parse_metric_response(&response.text().await?)
-                .with_context(|| "Failed to parse metrics")?;
-
-            println!("\nModel Metrics");
-            println!("{}", metric_table);
-            Ok(())
+            parse_metrics(&response.text().await?, ApiVersion::current())
         } else {
             Err(anyhow::Error::msg(format!(
                 "Request failed {:?}",
@@ -85,6 +371,270 @@ impl MetricGetter {
             )))
         }
     }
+
+    /// Resolves the run uid metrics were logged to. A provided uid is used as is,
+    /// otherwise the model card is looked up by name, repository and version
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the model
+    /// * `repository` - Repository of the model
+    /// * `version` - Version of the model. Most recent version if None
+    /// * `uid` - Run uid
+    /// * `ignore_release_candidates` - Whether to ignore release candidates
+    ///
+    /// # Returns
+    ///  String - Run uid
+    ///
+    pub async fn resolve_run_uid(
+        &self,
+        name: Option<&str>,
+        repository: Option<&str>,
+        version: Option<&str>,
+        uid: Option<&str>,
+        ignore_release_candidates: bool,
+    ) -> Result<String, anyhow::Error> {
+        utils::check_args(name, repository, version, uid).await?;
+
+        if let Some(uid) = uid {
+            return Ok(uid.to_string());
+        }
+
+        let card = cards::get_card(
+            "model",
+            name,
+            repository,
+            version,
+            None,
+            ignore_release_candidates,
+        )
+        .await?;
+
+        card.runcard_uid.with_context(|| {
+            format!(
+                "Model {} (version {}) is not associated with a run",
+                card.name.red(),
+                card.version.red()
+            )
+        })
+    }
+
+    /// Get model metrics
+    pub async fn get_model_metrics(
+        &self,
+        uid: &str,
+        view: &MetricView<'_>,
+    ) -> Result<(), anyhow::Error> {
+        let metrics = self.fetch_metrics(uid).await?;
+        let metrics = self.filter_metrics(metrics, view)?;
+
+        if let Some(export_path) = view.export {
+            export::export_metrics(&metrics, Path::new(export_path), &view.format)?;
+            eprintln!(
+                "Exported {} metric(s) to {}",
+                metrics.len(),
+                export_path.green()
+            );
+        }
+
+        match view.format {
+            types::MetricFormat::Table => {}
+            types::MetricFormat::Csv => {
+                print!("{}", export::metrics_to_csv(&metrics));
+                return Ok(());
+            }
+            types::MetricFormat::Json => {
+                let response = types::ListMetricResponse { metric: metrics };
+                println!("{}", serde_json::to_string_pretty(&response)?);
+                return Ok(());
+            }
+            types::MetricFormat::Prometheus => {
+                print!("{}", export::metrics_to_prometheus(&metrics));
+                return Ok(());
+            }
+        }
+
+        println!("\nModel Metrics");
+        if view.chart {
+            println!("{}", self.chart(&metrics));
+        } else if let Some(aggregation) = view.aggregation.as_ref() {
+            println!("{}", self.aggregate_table(&metrics, aggregation));
+        } else {
+            let metric_table = self
+                .metric_table(&metrics)
+                .with_context(|| "Failed to parse metrics")?;
+            println!("{}", metric_table);
+        }
+
+        Ok(())
+    }
+}
+
+// number of metrics sent per request when logging from a file
+pub const METRIC_BATCH_SIZE: usize = 500;
+
+/// A metric read from a jsonl file. The run uid may come from the command line instead
+#[derive(Deserialize)]
+struct MetricRecord {
+    run_uid: Option<String>,
+    name: String,
+    value: serde_json::Value,
+    step: Option<serde_json::Value>,
+    timestamp: Option<serde_json::Value>,
+}
+
+pub struct MetricLogger<'a> {
+    pub run_uid: Option<&'a str>,
+}
+
+impl MetricLogger<'_> {
+    /// Current time in seconds since the unix epoch
+    fn now() -> serde_json::Value {
+        utils::now_secs().into()
+    }
+
+    /// Creates a metric, using the logger's run uid when the record does not have one
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - Metric record
+    ///
+    fn to_metric(&self, record: MetricRecord) -> Result<types::Metric, anyhow::Error> {
+        // NaN and infinite values would be sent as null
+        if !record.value.is_number() {
+            return Err(anyhow::Error::msg(format!(
+                "Value of metric {} must be a finite number, got {}",
+                record.name.red(),
+                record.value
+            )));
+        }
+
+        let run_uid = record
+            .run_uid
+            .or(self.run_uid.map(|uid| uid.to_string()))
+            .with_context(|| {
+                format!(
+                    "No run uid for metric {}. Provide --run-uid or a run_uid field",
+                    record.name.red()
+                )
+            })?;
+
+        Ok(types::Metric {
+            run_uid,
+            name: record.name,
+            value: record.value,
+            step: record.step,
+            timestamp: record.timestamp.or(Some(MetricLogger::now())),
+        })
+    }
+
+    /// Parses metrics from jsonl. Blank lines are skipped
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - One json metric per line
+    ///
+    fn parse_jsonl(&self, contents: &str) -> Result<Vec<types::Metric>, anyhow::Error> {
+        contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let record: MetricRecord = serde_json::from_str(line)
+                    .with_context(|| format!("Failed to parse metric on line {}", i + 1))?;
+                self.to_metric(record)
+            })
+            .collect()
+    }
+
+    /// Posts metrics to the server in batches. Batches are not retried, so a failure reports
+    /// which batches were already logged
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - Metrics to log
+    ///
+    async fn log_metrics(&self, metrics: &[types::Metric]) -> Result<(), anyhow::Error> {
+        let batches: Vec<&[types::Metric]> = metrics.chunks(METRIC_BATCH_SIZE).collect();
+        let mut logged = 0;
+
+        for (i, batch) in batches.iter().enumerate() {
+            let request = types::InsertMetricRequest { metric: batch };
+            let result =
+                RouteHelper::make_post_request(&utils::OpsmlPaths::Metric.as_str(), &request).await;
+
+            let error = match result {
+                Ok(response) if response.status().is_success() => None,
+                Ok(response) => Some(anyhow::Error::msg(response.text().await?)),
+                Err(error) => Some(error),
+            };
+
+            if let Some(error) = error {
+                let succeeded = match i {
+                    0 => "none".to_string(),
+                    1 => "batch 1".to_string(),
+                    _ => format!("batches 1-{}", i),
+                };
+                return Err(error.context(format!(
+                    "Failed to log batch {} of {}. Logged {} of {} metric(s) ({} succeeded)",
+                    i + 1,
+                    batches.len(),
+                    logged,
+                    metrics.len(),
+                    succeeded
+                )));
+            }
+
+            logged += batch.len();
+        }
+
+        println!("Logged {} metric(s)", logged.to_string().green());
+        Ok(())
+    }
+}
+
+/// Logs a single metric to a run
+///
+/// # Arguments
+///
+/// * `run_uid` - Run uid to log to
+/// * `name` - Metric name
+/// * `value` - Metric value
+/// * `step` - Optional step
+pub async fn log_metric(
+    run_uid: &str,
+    name: &str,
+    value: f64,
+    step: Option<i64>,
+) -> Result<(), anyhow::Error> {
+    let logger = MetricLogger {
+        run_uid: Some(run_uid),
+    };
+    let metric = logger.to_metric(MetricRecord {
+        run_uid: None,
+        name: name.to_string(),
+        value: value.into(),
+        step: step.map(|step| step.into()),
+        timestamp: None,
+    })?;
+
+    logger.log_metrics(&[metric]).await
+}
+
+/// Logs metrics from a jsonl file to a run
+///
+/// # Arguments
+///
+/// * `file` - Path to a jsonl file with one metric per line
+/// * `run_uid` - Run uid for metrics without a run_uid field
+pub async fn log_metrics_file(file: &str, run_uid: Option<&str>) -> Result<(), anyhow::Error> {
+    let contents = std::fs::read_to_string(file)
+        .with_context(|| format!("Unable to read metrics file {}", file.red()))?;
+
+    let logger = MetricLogger { run_uid };
+    let metrics = logger.parse_jsonl(&contents)?;
+
+    logger.log_metrics(&metrics).await
 }
 
 /// List all metrics for a model
@@ -92,12 +642,24 @@ impl MetricGetter {
 /// # Arguments
 ///
 /// * `name` - Name of the model
+/// * `repository` - Repository of the model
 /// * `version` - Version of the model
-/// * `uid` - Unique identifier of the model
-/// * `url` - URL of the OpsML server
-pub async fn get_model_metrics(uid: &str) -> Result<(), anyhow::Error> {
+/// * `uid` - Run uid the metrics were logged to
+/// * `ignore_release_candidates` - Whether to ignore release candidates
+/// * `view` - Filters and rendering options
+pub async fn get_model_metrics(
+    name: Option<&str>,
+    repository: Option<&str>,
+    version: Option<&str>,
+    uid: Option<&str>,
+    ignore_release_candidates: bool,
+    view: &MetricView<'_>,
+) -> Result<(), anyhow::Error> {
     let metric_getter = MetricGetter {};
-    metric_getter.get_model_metrics(uid).await
+    let run_uid = metric_getter
+        .resolve_run_uid(name, repository, version, uid, ignore_release_candidates)
+        .await?;
+    metric_getter.get_model_metrics(&run_uid, view).await
 }
 
 #[cfg(test)]
@@ -144,14 +706,16 @@ mod tests {
             .with_body(metric_data)
             .create();
 
-        metric_getter.get_model_metrics("fake").await.unwrap();
+        metric_getter
+            .get_model_metrics("fake", &MetricView::default())
+            .await
+            .unwrap();
 
         let mock_response = types::ListMetricResponse { metric: vec };
         let string_response = serde_json::to_string(&mock_response).unwrap();
 
-        let metric_table = metric_getter
-            .parse_metric_response(&string_response)
-            .unwrap();
+        let metrics: types::ListMetricResponse = serde_json::from_str(&string_response).unwrap();
+        let metric_table = metric_getter.metric_table(&metrics.metric).unwrap();
 
         assert_eq!(
             metric_table,
@@ -167,4 +731,248 @@ mod tests {
 
         mock_get_metrics.assert();
     }
+
+    #[test]
+    fn test_parse_metrics_versions() {
+        let v2 = fs::read_to_string("./src/api/test_utils/list_metric.json").unwrap();
+        let v3 = fs::read_to_string("./src/api/test_utils/v3/list_metric.json").unwrap();
+
+        assert_eq!(parse_metrics(&v2, ApiVersion::V2).unwrap()[0].name, "mae");
+        assert_eq!(parse_metrics(&v3, ApiVersion::V3).unwrap()[0].name, "mae");
+        assert!(parse_metrics(&v2, ApiVersion::V3).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_run_uid() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url);
+        }
+
+        let path = "./src/api/test_utils/list_cards.json";
+        let card_data = fs::read_to_string(path).expect("Unable to read file");
+
+        let mock_list_cards = server
+            .mock("POST", "/opsml/cards/list")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"registry_type": "model", "name": "name", "repository": "repository"}"#
+                    .to_string(),
+            ))
+            .with_status(201)
+            .with_body(card_data)
+            .create();
+
+        let metric_getter = MetricGetter {};
+
+        let run_uid = metric_getter
+            .resolve_run_uid(Some("name"), Some("repository"), None, None, false)
+            .await
+            .unwrap();
+        assert_eq!(run_uid, "0987654321");
+        mock_list_cards.assert();
+
+        // a provided uid is used without looking up the card
+        let run_uid = metric_getter
+            .resolve_run_uid(None, None, None, Some("fake"), false)
+            .await
+            .unwrap();
+        assert_eq!(run_uid, "fake");
+
+        // uid and name together are ambiguous
+        assert!(metric_getter
+            .resolve_run_uid(Some("name"), None, None, Some("fake"), false)
+            .await
+            .is_err());
+    }
+
+    fn step_metric(name: &str, value: f64, step: i64) -> types::Metric {
+        types::Metric {
+            run_uid: "test".to_string(),
+            name: name.to_string(),
+            value: value.into(),
+            step: Some(step.into()),
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn test_filter_and_aggregate_metrics() {
+        let metric_getter = MetricGetter {};
+        let metrics = vec![
+            step_metric("loss", 0.5, 2),
+            step_metric("accuracy", 0.7, 1),
+            step_metric("loss", 0.9, 1),
+            step_metric("loss", 0.2, 3),
+            step_metric("mae", 4.0, 1),
+        ];
+
+        let names = vec!["loss".to_string(), "accuracy".to_string()];
+        let view = MetricView {
+            metric_names: Some(&names),
+            step_range: Some("2.."),
+            ..Default::default()
+        };
+        let filtered = metric_getter
+            .filter_metrics(metrics.clone(), &view)
+            .unwrap();
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(filtered[0].value, serde_json::Value::from(0.5));
+        assert_eq!(filtered[1].value, serde_json::Value::from(0.2));
+
+        // metrics are grouped by name and sorted by step
+        let sorted = metric_getter
+            .filter_metrics(metrics, &MetricView::default())
+            .unwrap();
+        let order: Vec<(&str, f64)> = sorted
+            .iter()
+            .map(|m| (m.name.as_str(), m.value.as_f64().unwrap()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("loss", 0.9),
+                ("loss", 0.5),
+                ("loss", 0.2),
+                ("accuracy", 0.7),
+                ("mae", 4.0)
+            ]
+        );
+
+        assert_eq!(
+            metric_getter.aggregate_table(&sorted, &types::MetricAggregation::Min),
+            concat!(
+                "┌──────────┬─────────────┬───────┬──────┐\n",
+                "│  metric  │ aggregation │ value │ step │\n",
+                "├──────────┼─────────────┼───────┼──────┤\n",
+                "│   loss   │     min     │  0.2  │  3   │\n",
+                "│ accuracy │     min     │  0.7  │  1   │\n",
+                "│   mae    │     min     │  4.0  │  1   │\n",
+                "└──────────┴─────────────┴───────┴──────┘",
+            )
+        );
+
+        assert!(parse_step_range("100").is_err());
+        assert_eq!(parse_step_range("..500").unwrap(), (None, Some(500.0)));
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(
+            sparkline(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0], 60),
+            "▁▂▃▄▅▆▇█"
+        );
+        assert_eq!(sparkline(&[1.0, 1.0, 8.0, 8.0], 2), "▁█");
+        assert_eq!(sparkline(&[3.0, 3.0], 60), "▁▁");
+    }
+
+    #[test]
+    fn test_chart_without_numeric_values() {
+        let metrics = vec![types::Metric {
+            run_uid: "test".to_string(),
+            name: "label".to_string(),
+            value: "cat".into(),
+            step: None,
+            timestamp: None,
+        }];
+
+        let chart = MetricGetter {}.chart(&metrics);
+        assert_eq!(chart, "label  n/a (n=0)");
+    }
+
+    #[tokio::test]
+    async fn test_log_metrics() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url);
+        }
+
+        let mock_log_metrics = server
+            .mock("POST", "/opsml/metrics")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"metric": [{"run_uid": "run", "name": "latency_p99", "value": 12.3, "step": 2}]}"#
+                    .to_string(),
+            ))
+            .with_status(200)
+            .create();
+
+        log_metric("run", "latency_p99", 12.3, Some(2))
+            .await
+            .unwrap();
+        mock_log_metrics.assert();
+
+        let logger = MetricLogger {
+            run_uid: Some("default"),
+        };
+        let metrics = logger
+            .parse_jsonl(concat!(
+                "{\"name\": \"mae\", \"value\": 5}\n",
+                "\n",
+                "{\"run_uid\": \"other\", \"name\": \"mape\", \"value\": 1.5, \"step\": 3, \"timestamp\": 10}\n",
+            ))
+            .unwrap();
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0].run_uid, "default");
+        assert!(metrics[0].timestamp.is_some());
+        assert_eq!(metrics[1].run_uid, "other");
+        assert_eq!(metrics[1].timestamp, Some(10.into()));
+
+        let logger = MetricLogger { run_uid: None };
+        assert!(logger
+            .parse_jsonl("{\"name\": \"mae\", \"value\": 5}")
+            .is_err());
+
+        // non finite values are rejected before anything is sent
+        assert!(log_metric("run", "latency_p99", f64::NAN, None)
+            .await
+            .is_err());
+        assert!(logger
+            .parse_jsonl("{\"run_uid\": \"run\", \"name\": \"mae\", \"value\": \"high\"}")
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_log_metrics_partial_failure() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url);
+        }
+
+        let mock_first_batch = server
+            .mock("POST", "/opsml/metrics")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"metric": [{"run_uid": "run", "name": "step", "value": 0.0}]}"#.to_string(),
+            ))
+            .with_status(200)
+            .create();
+        let mock_failed_batch = server
+            .mock("POST", "/opsml/metrics")
+            .with_status(500)
+            .with_body("database unavailable")
+            .create();
+
+        let logger = MetricLogger {
+            run_uid: Some("run"),
+        };
+        let metrics: Vec<types::Metric> = (0..METRIC_BATCH_SIZE + 1)
+            .map(|i| types::Metric {
+                run_uid: "run".to_string(),
+                name: "step".to_string(),
+                value: (i as f64).into(),
+                step: Some(i.into()),
+                timestamp: None,
+            })
+            .collect();
+
+        let error = logger.log_metrics(&metrics).await.unwrap_err();
+        mock_first_batch.assert();
+        mock_failed_batch.assert();
+
+        let message = format!("{:#}", error);
+        assert!(message.contains("Failed to log batch 2 of 2"));
+        assert!(message.contains("Logged 500 of 501 metric(s) (batch 1 succeeded)"));
+        assert!(message.contains("database unavailable"));
+    }
 }
@@ -0,0 +1,419 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::server::OpsmlServer;
+use crate::api::types;
+use crate::api::utils::{self, SaveRoot};
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use std::collections::HashSet;
+use std::path::Path;
+
+pub struct CardMirror<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub registry: &'a str,
+    pub name: Option<&'a str>,
+    pub repository: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub uid: Option<&'a str>,
+    pub ignore_release_candidates: &'a bool,
+}
+
+impl CardMirror<'_> {
+    /// Copies the card's files. Files of a card the target has not registered may be partial
+    /// copies from an interrupted mirror, so they are all copied again. Once the card is
+    /// registered, only files missing on the target are copied
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Server to copy from
+    /// * `target` - Server to copy to
+    /// * `rpath_root` - Remote root of the card's files
+    /// * `registered` - Whether the target has registered the card
+    ///
+    async fn mirror_files(
+        &self,
+        source: &OpsmlServer,
+        target: &OpsmlServer,
+        rpath_root: &Path,
+        registered: bool,
+    ) -> Result<(), anyhow::Error> {
+        let files = source.list_files(rpath_root).await?;
+
+        let existing = match registered {
+            true => target.list_existing_files(rpath_root).await?,
+            false => HashSet::new(),
+        };
+
+        let mut copied = 0;
+        for rpath in files.iter().filter(|rpath| !existing.contains(*rpath)) {
+            println!("Copying: {}", rpath.green());
+            let response = source.open_file(rpath).await?;
+            target.upload_file(rpath, response).await?;
+            copied += 1;
+        }
+
+        println!(
+            "Copied {} file(s), {} already on target",
+            copied,
+            files.len() - copied
+        );
+        Ok(())
+    }
+
+    /// Copies metrics the target does not have yet
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Server to copy from
+    /// * `target` - Server to copy to
+    /// * `run_uid` - Run the metrics were logged to
+    ///
+    async fn mirror_metrics(
+        &self,
+        source: &OpsmlServer,
+        target: &OpsmlServer,
+        run_uid: &str,
+    ) -> Result<(), anyhow::Error> {
+        let metrics = source.fetch_metrics(run_uid).await?;
+        let (copied, skipped) = target.log_missing_metrics(run_uid, metrics).await?;
+
+        println!("Copied {} metric(s), {} already on target", copied, skipped);
+        Ok(())
+    }
+
+    /// Copies the run a card points at with its files and metrics, so the card is never
+    /// registered on the target before its run
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Server to copy from
+    /// * `target` - Server to copy to
+    /// * `run_uid` - Uid of the run card
+    ///
+    async fn mirror_run(
+        &self,
+        source: &OpsmlServer,
+        target: &OpsmlServer,
+        run_uid: &str,
+    ) -> Result<(), anyhow::Error> {
+        let raw_run = source
+            .list_cards("run", None, None, None, Some(run_uid), &false)
+            .await?
+            .into_iter()
+            .next()
+            .with_context(|| format!("Run card {} is not on {}", run_uid.red(), source.uri))?;
+        let run: types::Card =
+            serde_json::from_value(raw_run.clone()).with_context(|| "Failed to parse run card")?;
+        println!(
+            "Mirroring run card {} (version {})",
+            run.name.green(),
+            run.version.green()
+        );
+
+        let registered = target.is_registered("run", &run).await?;
+        let rpath_root = SaveRoot::Run.card_root(&run.repository, &run.name, &run.version);
+        self.mirror_files(source, target, &rpath_root, registered)
+            .await?;
+        self.mirror_metrics(source, target, run_uid).await?;
+
+        if registered {
+            println!("Run card {} is already registered on target", run.uid);
+        } else {
+            target.register_card("run", &raw_run).await?;
+            println!("Registered run card {}", run.uid.green());
+        }
+
+        Ok(())
+    }
+
+    /// Copies a card's files, metrics and record. The card is registered last so a card on the
+    /// target always has its artifacts, and an interrupted mirror can be re-run
+    async fn mirror(&self) -> Result<(), anyhow::Error> {
+        utils::check_args(self.name, self.repository, self.version, self.uid).await?;
+        let save_root = SaveRoot::from_registry(self.registry)?;
+
+        let source = OpsmlServer::connect(self.from).await?;
+        let target = OpsmlServer::connect(self.to).await?;
+        if source.api_version != target.api_version {
+            return Err(anyhow::Error::msg(format!(
+                "Cannot mirror between a {} server and a {} server",
+                source.api_version.as_str(),
+                target.api_version.as_str()
+            )));
+        }
+
+        let raw_card = source
+            .list_cards(
+                self.registry,
+                self.name,
+                self.repository,
+                self.version,
+                self.uid,
+                self.ignore_release_candidates,
+            )
+            .await?
+            .into_iter()
+            .next()
+            .with_context(|| {
+                format!(
+                    "No card found in {} registry on {}",
+                    self.registry, source.uri
+                )
+            })?;
+        let card: types::Card =
+            serde_json::from_value(raw_card.clone()).with_context(|| "Failed to parse card")?;
+
+        println!(
+            "Mirroring {} card {} (version {}) from {} to {}",
+            self.registry,
+            card.name.green(),
+            card.version.green(),
+            source.uri,
+            target.uri
+        );
+
+        let registered = target.is_registered(self.registry, &card).await?;
+
+        let rpath_root = save_root.card_root(&card.repository, &card.name, &card.version);
+        self.mirror_files(&source, &target, &rpath_root, registered)
+            .await?;
+
+        if let Some(run_uid) = card.runcard_uid.as_deref() {
+            self.mirror_run(&source, &target, run_uid).await?;
+        }
+
+        if registered {
+            println!("Card {} is already registered on target", card.uid);
+        } else {
+            target.register_card(self.registry, &raw_card).await?;
+            println!("Registered card {}", card.uid.green());
+        }
+
+        Ok(())
+    }
+}
+
+/// Copies a card with its files and metrics from one server to another, keeping its uid and
+/// version. Re-running skips what the target already has
+///
+/// # Arguments
+///
+/// * `mirror` - Card to copy and the servers to copy between
+///
+pub async fn mirror_card(mirror: &CardMirror<'_>) -> Result<(), anyhow::Error> {
+    mirror.mirror().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+    use std::env;
+
+    #[tokio::test]
+    async fn test_mirror_card() {
+        let mut source = mockito::Server::new_async().await;
+        // a second pooled server would leave later tests on a different server than the one
+        // OPSML_TRACKING_URI was first read from
+        let mut target = mockito::Server::new_with_opts_async(mockito::ServerOpts::default()).await;
+        let source_url = source.url();
+        let target_url = target.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI_MIRROR_SOURCE", source_url.clone());
+        }
+
+        let cards = std::fs::read_to_string("./src/api/test_utils/list_cards.json").unwrap();
+        let metrics = std::fs::read_to_string("./src/api/test_utils/list_metric.json").unwrap();
+        let files =
+            r#"{"files": ["opsml-root:/OPSML_MODEL_REGISTRY/repository/name/v1.0.0/model.onnx"]}"#;
+        let runs = r#"{"cards": [{"name": "run", "repository": "repository", "contact": "devops@opsml.com",
+            "version": "1.0.0", "uid": "0987654321", "tags": {}}]}"#;
+        let model_list = Matcher::PartialJsonString(r#"{"registry_type": "model"}"#.to_string());
+        let run_list = Matcher::PartialJsonString(r#"{"registry_type": "run"}"#.to_string());
+
+        for server in [&mut source, &mut target] {
+            server
+                .mock("GET", "/opsml/healthcheck")
+                .with_status(200)
+                .create();
+        }
+
+        source
+            .mock("POST", "/opsml/cards/list")
+            .match_body(model_list.clone())
+            .with_status(200)
+            .with_body(&cards)
+            .create();
+        source
+            .mock("POST", "/opsml/cards/list")
+            .match_body(run_list.clone())
+            .with_status(200)
+            .with_body(runs)
+            .create();
+        source
+            .mock(
+                "GET",
+                Matcher::Regex("^/opsml/files/list\\?.*OPSML_MODEL_REGISTRY".to_string()),
+            )
+            .with_status(200)
+            .with_body(files)
+            .create();
+        source
+            .mock(
+                "GET",
+                Matcher::Regex("^/opsml/files/list\\?.*OPSML_RUN_REGISTRY".to_string()),
+            )
+            .with_status(200)
+            .with_body(r#"{"files": []}"#)
+            .create();
+        source
+            .mock("GET", Matcher::Regex("^/opsml/files/presigned".to_string()))
+            .with_status(200)
+            .with_body(format!(r#"{{"url": "{}/model-bytes"}}"#, source_url))
+            .create();
+        source
+            .mock("GET", "/model-bytes")
+            .with_status(200)
+            .with_body("onnx bytes")
+            .create();
+        source
+            .mock("GET", "/opsml/metrics?run_uid=0987654321")
+            .with_status(200)
+            .with_body(&metrics)
+            .create();
+
+        // the target starts empty
+        let empty_cards = target
+            .mock("POST", "/opsml/cards/list")
+            .with_status(200)
+            .with_body(r#"{"cards": []}"#)
+            .create();
+        let empty_metrics = target
+            .mock("GET", "/opsml/metrics?run_uid=0987654321")
+            .with_status(200)
+            .with_body(r#"{"metric": []}"#)
+            .create();
+        target
+            .mock(
+                "GET",
+                Matcher::Regex("^/opsml/files/presigned.*method=PUT".to_string()),
+            )
+            .with_status(200)
+            .with_body(format!(r#"{{"url": "{}/upload-bytes"}}"#, target_url))
+            .create();
+        let mock_upload = target
+            .mock("PUT", "/upload-bytes")
+            .match_header("content-length", "10")
+            .match_body("onnx bytes")
+            .with_status(200)
+            .expect(1)
+            .create();
+        let mock_log_metrics = target
+            .mock("POST", "/opsml/metrics")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"metric": [{"name": "mae", "value": 5}]}"#.to_string(),
+            ))
+            .with_status(200)
+            .expect(1)
+            .create();
+        let mock_register = target
+            .mock("POST", "/opsml/cards/create")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"registry_type": "model", "card": {"uid": "1234567890", "version": "1.0.0"}}"#
+                    .to_string(),
+            ))
+            .with_status(200)
+            .expect(1)
+            .create();
+        let mock_register_run = target
+            .mock("POST", "/opsml/cards/create")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"registry_type": "run", "card": {"uid": "0987654321"}}"#.to_string(),
+            ))
+            .with_status(200)
+            .expect(1)
+            .create();
+
+        let mirror = CardMirror {
+            from: "mirror-source",
+            to: &target_url,
+            registry: "model",
+            name: None,
+            repository: None,
+            version: None,
+            uid: Some("1234567890"),
+            ignore_release_candidates: &false,
+        };
+        mirror_card(&mirror).await.unwrap();
+
+        // a second run finds everything on the target and copies nothing
+        empty_cards.remove();
+        empty_metrics.remove();
+        target
+            .mock("POST", "/opsml/cards/list")
+            .match_body(model_list)
+            .with_status(200)
+            .with_body(&cards)
+            .create();
+        target
+            .mock("POST", "/opsml/cards/list")
+            .match_body(run_list)
+            .with_status(200)
+            .with_body(runs)
+            .create();
+        // the model's and the run's files are listed
+        let full_files = target
+            .mock("GET", Matcher::Regex("^/opsml/files/list\\?".to_string()))
+            .with_status(200)
+            .with_body(files)
+            .expect(2)
+            .create();
+        let full_metrics = target
+            .mock("GET", "/opsml/metrics?run_uid=0987654321")
+            .with_status(200)
+            .with_body(&metrics)
+            .create();
+        mirror_card(&mirror).await.unwrap();
+        full_files.assert();
+
+        mock_upload.assert();
+        mock_log_metrics.assert();
+        mock_register.assert();
+        mock_register_run.assert();
+
+        // metrics that cannot be read from the target fail the mirror
+        full_metrics.remove();
+        target
+            .mock("GET", "/opsml/metrics?run_uid=0987654321")
+            .with_status(500)
+            .create();
+        assert!(mirror_card(&mirror).await.is_err());
+
+        // paths the target does not know have no files, other failures are errors
+        full_files.remove();
+        let server = OpsmlServer::connect(&target_url).await.unwrap();
+        let missing = target
+            .mock("GET", "/opsml/files/list?path=missing")
+            .with_status(404)
+            .create();
+        assert!(server
+            .list_existing_files(Path::new("missing"))
+            .await
+            .unwrap()
+            .is_empty());
+        missing.assert();
+        target
+            .mock("GET", "/opsml/files/list?path=broken")
+            .with_status(500)
+            .create();
+        assert!(server
+            .list_existing_files(Path::new("broken"))
+            .await
+            .is_err());
+
+        mock_log_metrics.assert();
+        mock_register.assert();
+    }
+}
@@ -1,11 +1,29 @@
 /// Copyright (c) Shipt, Inc.
 /// This source code is licensed under the MIT license found in the
 /// LICENSE file in the root directory of this source tree.
+pub mod api_version;
+pub mod app;
+pub mod assertions;
+pub mod browse;
+pub mod bundle;
 pub mod cards;
 pub mod cli;
 pub mod commands;
+pub mod completions;
+pub mod docs;
+pub mod doctor;
+pub mod download;
+pub mod export;
+pub mod files;
+pub mod layout;
+pub mod matrix;
 pub mod metrics;
+pub mod mirror;
 pub mod model;
+pub mod oci;
 pub mod route_helper;
+pub mod server;
+pub mod storage;
 pub mod types;
 pub mod utils;
+pub mod watch;
@@ -1,6 +1,8 @@
 /// Copyright (c) Shipt, Inc.
 /// This source code is licensed under the MIT license found in the
 /// LICENSE file in the root directory of this source tree.
+use crate::api::api_version::ApiVersion;
+use crate::api::download::FileDownloader;
 use crate::api::route_helper::RouteHelper;
 use crate::api::types;
 use crate::api::utils;
@@ -12,10 +14,44 @@ use std::{fs, path::Path};
 
 use super::types::ModelMetadata;
 
-const MODEL_METADATA_FILE: &str = "model-metadata.json";
+pub const MODEL_METADATA_FILE: &str = "model-metadata.json";
 const NO_ONNX_URI: &str = "No onnx model uri found but onnx flag set to true";
 const NO_QUANTIZE_URI: &str = "No quantize model uri found but quantize flag set to true";
 
+/// Requests model metadata from the server
+///
+/// # Arguments
+///
+/// * `request` - Model metadata request
+/// * `api_version` - Api version of the server
+///
+/// # Returns
+/// * `Result<(types::ModelMetadata, String), String>` - Parsed metadata and the raw response
+///
+async fn request_model_metadata(
+    request: &types::ModelMetadataRequest<'_>,
+    api_version: ApiVersion,
+) -> Result<(types::ModelMetadata, String), anyhow::Error> {
+    let url = utils::OpsmlPaths::MetadataDownload.url_for(api_version);
+    let response = match api_version {
+        ApiVersion::V2 => RouteHelper::make_post_request(&url, request).await?,
+        ApiVersion::V3 => {
+            let request = types::ModelMetadataRequestV3::from(request);
+            RouteHelper::make_post_request(&url, &request).await?
+        }
+    };
+
+    let loaded_response = RouteHelper::load_stream_response(response).await?;
+    let model_metadata: types::ModelMetadata =
+        serde_json::from_str(&loaded_response).with_context(|| "Failed to parse model Metadata")?;
+
+    if let Some(warning) = model_metadata.schema_warning() {
+        eprintln!("{}", warning.yellow());
+    }
+
+    Ok((model_metadata, loaded_response))
+}
+
 pub struct ModelDownloader<'a> {
     pub name: Option<&'a str>,
     pub version: Option<&'a str>,
@@ -26,14 +62,18 @@ pub struct ModelDownloader<'a> {
     pub onnx: &'a bool,
     pub quantize: &'a bool,
     pub preprocessor: &'a bool,
+    pub dry_run: &'a bool,
+    pub include: &'a [String],
+    pub exclude: &'a [String],
 }
 
 impl ModelDownloader<'_> {
-    /// Saves metadata to json
+    /// Saves metadata to json. The server response is written verbatim so fields this client
+    /// does not know about are not lost
     ///
     /// # Arguments
     ///
-    /// * `metadata` - metadata to save
+    /// * `metadata` - raw metadata json returned by the server
     /// * `path` - path to save to
     ///
     /// # Returns
@@ -41,13 +81,11 @@ impl ModelDownloader<'_> {
     ///
     async fn save_metadata_to_json(
         &self,
-        metadata: &types::ModelMetadata,
+        metadata: &str,
         path: &Path,
     ) -> Result<(), anyhow::Error> {
-        let json_string =
-            serde_json::to_string(metadata).with_context(|| "Failed to serialize metadata")?;
         fs::File::create(path).with_context(|| "Unable to create metadata file")?;
-        fs::write(path, json_string).with_context(|| "Unable to write metadata file")?;
+        fs::write(path, metadata).with_context(|| "Unable to write metadata file")?;
         Ok(())
     }
 
@@ -70,20 +108,17 @@ impl ModelDownloader<'_> {
             uid: self.uid,
             ignore_release_candidates: self.ignore_release_candidates,
         };
+        let (model_metadata, loaded_response) =
+            request_model_metadata(&model_metadata_request, ApiVersion::current()).await?;
 
-        let response = RouteHelper::make_post_request(
-            &utils::OpsmlPaths::MetadataDownload.as_str(),
-            &model_metadata_request,
-        )
-        .await?;
-
-        let loaded_response = RouteHelper::load_stream_response(response).await?;
-        let model_metadata: types::ModelMetadata = serde_json::from_str(&loaded_response)
-            .with_context(|| "Failed to parse model Metadata")?;
+        // nothing is written to disk during a dry run
+        if self.dry_run == &true {
+            return Ok(model_metadata);
+        }
 
         // create save path for metadata
         utils::create_dir_path(&save_path)?;
-        self.save_metadata_to_json(&model_metadata, &save_path)
+        self.save_metadata_to_json(&loaded_response, &save_path)
             .await?;
 
         Ok(model_metadata)
@@ -133,6 +168,7 @@ impl ModelDownloader<'_> {
     /// # Returns
     /// * `Option<&Path>` - File path to processor or None
     ///
+    #[allow(clippy::unnecessary_unwrap)]
     fn get_preprocessor_uri(&self, model_metadata: &types::ModelMetadata) -> Option<PathBuf> {
         let uri = if model_metadata.preprocessor_uri.is_some() {
             Some(
@@ -168,38 +204,29 @@ impl ModelDownloader<'_> {
         Ok(model_metadata)
     }
 
+    /// Creates a file downloader using the model downloader's write settings
+    fn file_downloader(&self) -> FileDownloader<'_> {
+        FileDownloader {
+            write_dir: self.write_dir,
+            dry_run: self.dry_run,
+            include: self.include,
+            exclude: self.exclude,
+        }
+    }
+
     /// Downloads files associated with a model
     ///
     /// # Arguments
     ///
     /// * `rpath` - Remote path to file
+    /// * `rpath_root` - Remote root that is stripped from each file path
     ///
     /// # Returns
     /// * `Result<(), String>` - Result of file download
     async fn download_files(&self, rpath: &Path, rpath_root: &Path) -> Result<(), anyhow::Error> {
-        let rpath_files = RouteHelper::list_files(rpath).await?;
-
-        // iterate over each file and download
-        for file in rpath_files.files.iter() {
-            let rpath = Path::new(file);
-
-            let stripped_path = rpath
-                .strip_prefix(rpath_root)
-                .with_context(|| "Failed to create file path")?;
-
-            let lpath = Path::new(self.write_dir).join(stripped_path);
-
-            println!(
-                "Downloading: {} from {}",
-                lpath.display().to_string().green(),
-                file
-            );
-
-            utils::create_dir_path(&lpath)?;
-            RouteHelper::download_file(&lpath, file).await?;
-        }
-
-        Ok(())
+        self.file_downloader()
+            .download_files(rpath, rpath_root)
+            .await
     }
 
     /// Gets root to use
@@ -211,17 +238,13 @@ impl ModelDownloader<'_> {
     /// # Returns
     /// * `Result<PathBuf, String>` - Path to save root
     async fn get_save_root(&self, metadata: &ModelMetadata) -> Result<PathBuf, anyhow::Error> {
-        let root = format!(
-            "{}/{}/{}/v{}",
-            utils::SaveRoot::Model.as_str(),
-            metadata.model_repository,
-            metadata.model_name,
-            metadata.model_version
+        let root_path = utils::SaveRoot::Model.card_root(
+            &metadata.model_repository,
+            &metadata.model_name,
+            &metadata.model_version,
         );
 
-        let root_path = Path::new(&root);
-
-        Ok(root_path.to_owned())
+        Ok(root_path)
     }
 
     /// Downloads preprocessor files
@@ -233,6 +256,7 @@ impl ModelDownloader<'_> {
     ///
     /// # Returns
     /// * `Result<(), String>` - Result of file download
+    #[allow(clippy::unnecessary_unwrap)]
     async fn get_preprocessor(
         &self,
         metadata: &ModelMetadata,
@@ -297,10 +321,33 @@ pub async fn download_model_metadata(
         onnx: &false,
         quantize: &false,
         preprocessor: &false,
+        dry_run: &false,
+        include: &[],
+        exclude: &[],
     };
     model_downloader.get_metadata().await
 }
 
+/// Fetches model metadata without writing it to disk
+///
+/// * `uid` - uid of model
+/// * `api_version` - Api version of the server
+pub async fn fetch_model_metadata(
+    uid: &str,
+    api_version: ApiVersion,
+) -> Result<types::ModelMetadata, anyhow::Error> {
+    let request = types::ModelMetadataRequest {
+        name: None,
+        version: None,
+        repository: None,
+        uid: Some(uid),
+        ignore_release_candidates: &false,
+    };
+    let (model_metadata, _) = request_model_metadata(&request, api_version).await?;
+
+    Ok(model_metadata)
+}
+
 /// Downloads model file
 ///
 /// * `name` - Name of model
@@ -311,6 +358,9 @@ pub async fn download_model_metadata(
 /// * `write_dir` - directory to write to
 /// * `no_onnx` - Flag to not download onnx model
 /// * `onnx` - Flag to download onnx model
+/// * `dry_run` - Flag to print planned downloads without transferring files
+/// * `include` - Glob patterns of files to download
+/// * `exclude` - Glob patterns of files to skip
 ///
 #[allow(clippy::too_many_arguments)]
 pub async fn download_model(
@@ -323,6 +373,9 @@ pub async fn download_model(
     quantize: &bool,
     preprocessor: &bool,
     ignore_release_candidates: &bool,
+    dry_run: &bool,
+    include: &[String],
+    exclude: &[String],
 ) -> Result<(), anyhow::Error> {
     let model_downloader = ModelDownloader {
         name,
@@ -334,6 +387,9 @@ pub async fn download_model(
         onnx,
         quantize,
         preprocessor,
+        dry_run,
+        include,
+        exclude,
     };
     model_downloader.download_model().await
 }
@@ -367,7 +423,7 @@ mod tests {
         let mock_metadata_path = download_server
             .mock("POST", "/opsml/models/metadata")
             .with_status(201)
-            .with_body(serde_json::to_string(&model_metadata).unwrap())
+            .with_body(&metadata)
             .create();
 
         // mock list files
@@ -456,10 +512,21 @@ mod tests {
             onnx: &true,
             quantize: &false,
             preprocessor: &false,
+            dry_run: &false,
+            include: &[],
+            exclude: &[],
         };
 
+        let saved_metadata = metadata;
         let metadata = downloader.get_metadata().await.unwrap();
         mock_metadata_path.assert();
+        assert_eq!(metadata.opsml_version.as_deref(), Some("2.0.0"));
+
+        // the server response is saved verbatim
+        assert_eq!(
+            fs::read_to_string(Path::new("downloaded").join(MODEL_METADATA_FILE)).unwrap(),
+            saved_metadata
+        );
 
         let save_root = downloader.get_save_root(&metadata).await.unwrap();
         assert_eq!(
@@ -486,4 +553,137 @@ mod tests {
         // clean up
         fs::remove_dir_all("downloaded").unwrap();
     }
+
+    #[test]
+    fn test_parse_metadata_versions() {
+        // 1.x servers send model_team and no interface fields
+        let metadata = fs::read_to_string("./src/api/test_utils/metadata_v1.json").unwrap();
+        let model_metadata: types::ModelMetadata = serde_json::from_str(&metadata).unwrap();
+        assert_eq!(model_metadata.model_repository, "devops-ml");
+        assert_eq!(model_metadata.model_interface, "");
+        assert!(model_metadata.extra.contains_key("sample_data"));
+        assert!(model_metadata.schema_warning().is_none());
+
+        let mut value: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string("./src/api/test_utils/metadata.json").unwrap(),
+        )
+        .unwrap();
+        value["opsml_version"] = "4.0.0".into();
+        value["model_card_uid"] = "abc".into();
+        let model_metadata: types::ModelMetadata = serde_json::from_value(value).unwrap();
+        assert!(model_metadata.schema_warning().unwrap().contains("4.0.0"));
+
+        // unknown fields survive a round trip
+        let round_trip = serde_json::to_value(&model_metadata).unwrap();
+        assert_eq!(round_trip["model_card_uid"], "abc");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata_v3() {
+        let metadata = fs::read_to_string("./src/api/test_utils/v3/metadata.json").unwrap();
+
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url);
+        }
+
+        let mock = server
+            .mock("POST", "/opsml/api/card/metadata")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"uid": "uid", "space": null}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(&metadata)
+            .create();
+
+        let model_metadata = fetch_model_metadata("uid", ApiVersion::V3).await.unwrap();
+        assert_eq!(model_metadata.model_repository, "devops-ml");
+        assert!(model_metadata.schema_warning().is_none());
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_download_model_dry_run() {
+        let metadata = fs::read_to_string("./src/api/test_utils/metadata.json").unwrap();
+
+        // setup server
+        let mut download_server = mockito::Server::new_async().await;
+        let url = download_server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url.clone());
+        }
+
+        let _mock_metadata_path = download_server
+            .mock("POST", "/opsml/models/metadata")
+            .with_status(201)
+            .with_body(&metadata)
+            .create();
+
+        let files = types::ListFileResponse {
+            files: vec![
+                "opsml-root:/OPSML_MODEL_REGISTRY/devops-ml/linear-reg-model/v1.1.0/model.onnx"
+                    .to_string(),
+                "opsml-root:/OPSML_MODEL_REGISTRY/devops-ml/linear-reg-model/v1.1.0/training_args.bin"
+                    .to_string(),
+            ],
+        };
+        let mock_list = download_server
+            .mock("GET", "/opsml/files/list?path=models.json")
+            .with_status(200)
+            .with_body(serde_json::to_string(&files).unwrap())
+            .expect(2)
+            .create();
+
+        // no presigned urls should be requested during a dry run
+        let mock_presigned = download_server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/opsml/files/presigned".to_string()),
+            )
+            .expect(0)
+            .create();
+
+        let downloader = ModelDownloader {
+            name: Some("linear-reg-model"),
+            version: Some("1.1.0"),
+            repository: Some("devops-ml"),
+            uid: None,
+            write_dir: "dry_run_downloaded",
+            ignore_release_candidates: &false,
+            onnx: &true,
+            quantize: &false,
+            preprocessor: &false,
+            dry_run: &true,
+            include: &["*.onnx".to_string()],
+            exclude: &[],
+        };
+
+        let metadata = downloader.get_metadata().await.unwrap();
+        let rpath_root = downloader.get_save_root(&metadata).await.unwrap();
+        let planned_files = downloader
+            .file_downloader()
+            .plan_files(Path::new("models.json"), &rpath_root)
+            .await
+            .unwrap();
+        assert_eq!(planned_files.files.len(), 1);
+        assert_eq!(
+            planned_files.files[0].lpath,
+            Path::new("dry_run_downloaded").join("model.onnx")
+        );
+        assert_eq!(planned_files.files[0].size, None);
+        assert_eq!(planned_files.skipped.len(), 1);
+        assert_eq!(
+            planned_files.skipped[0].lpath,
+            Path::new("dry_run_downloaded").join("training_args.bin")
+        );
+
+        downloader.download_model().await.unwrap();
+
+        mock_list.assert();
+        mock_presigned.assert();
+
+        // nothing should be written during a dry run
+        assert!(!Path::new("dry_run_downloaded").exists());
+    }
 }
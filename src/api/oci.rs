@@ -0,0 +1,643 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::cards;
+use crate::api::model::{download_model, MODEL_METADATA_FILE};
+use crate::api::types;
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const LAYER_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar";
+const CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.image.config.v1+json";
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const INDEX_MEDIA_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+const REF_NAME_ANNOTATION: &str = "org.opencontainers.image.ref.name";
+
+/// Directory model files are placed under in the layer, e.g. `/model/model.onnx`
+const LAYER_ROOT: &str = "model";
+
+/// Writer that hashes everything written through it
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Lists the files and directories under a directory in a stable order
+///
+/// # Arguments
+///
+/// * `dir` - Directory to walk
+///
+/// # Returns
+/// * `Result<Vec<PathBuf>, String>` - Paths relative to `dir`, parents before children
+///
+pub fn sorted_entries(dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut entries = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative) = pending.pop() {
+        let path = dir.join(&relative);
+        for entry in
+            fs::read_dir(&path).with_context(|| format!("Failed to read {}", path.display()))?
+        {
+            let child = relative.join(entry?.file_name());
+            if dir.join(&child).is_dir() {
+                pending.push(child.clone());
+            }
+            entries.push(child);
+        }
+    }
+
+    entries.sort();
+    Ok(entries)
+}
+
+/// Writes a directory to a tar archive with fixed ownership, permissions and timestamps, so the
+/// same files always produce the same bytes
+///
+/// # Arguments
+///
+/// * `src` - Directory to archive
+/// * `prefix` - Path the directory's contents are placed under in the archive
+/// * `writer` - Archive output
+///
+pub fn write_reproducible_tar<W: Write>(
+    src: &Path,
+    prefix: &Path,
+    writer: W,
+) -> Result<W, anyhow::Error> {
+    let mut builder = tar::Builder::new(writer);
+
+    if !prefix.as_os_str().is_empty() {
+        append_dir(&mut builder, prefix)?;
+    }
+
+    for relative in sorted_entries(src)? {
+        let path = src.join(&relative);
+        let archive_path = prefix.join(&relative);
+
+        if path.is_dir() {
+            append_dir(&mut builder, &archive_path)?;
+        } else {
+            let mut header = reproducible_header(tar::EntryType::Regular, 0o644);
+            header.set_size(fs::metadata(&path)?.len());
+            let file = fs::File::open(&path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            builder
+                .append_data(&mut header, &archive_path, file)
+                .with_context(|| format!("Failed to archive {}", path.display()))?;
+        }
+    }
+
+    builder
+        .into_inner()
+        .with_context(|| "Failed to finish archive")
+}
+
+fn reproducible_header(entry_type: tar::EntryType, mode: u32) -> tar::Header {
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(entry_type);
+    header.set_mode(mode);
+    header.set_mtime(0);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_size(0);
+    header
+}
+
+fn append_dir<W: Write>(builder: &mut tar::Builder<W>, path: &Path) -> Result<(), anyhow::Error> {
+    let mut header = reproducible_header(tar::EntryType::Directory, 0o755);
+    builder
+        .append_data(&mut header, path, io::empty())
+        .with_context(|| format!("Failed to archive {}", path.display()))
+}
+
+fn blob_path(layout: &Path, digest: &str) -> PathBuf {
+    layout
+        .join("blobs")
+        .join("sha256")
+        .join(digest.trim_start_matches("sha256:"))
+}
+
+/// Writes a json document to the layout's blob store
+///
+/// # Arguments
+///
+/// * `layout` - OCI layout directory
+/// * `media_type` - Media type of the document
+/// * `document` - Document to write
+///
+fn write_json_blob<T: serde::Serialize>(
+    layout: &Path,
+    media_type: &str,
+    document: &T,
+) -> Result<types::OciDescriptor, anyhow::Error> {
+    let bytes = serde_json::to_vec(document)?;
+    let digest = format!("sha256:{:x}", Sha256::digest(&bytes));
+    fs::write(blob_path(layout, &digest), &bytes)?;
+
+    Ok(types::OciDescriptor {
+        media_type: media_type.to_string(),
+        digest,
+        size: bytes.len() as u64,
+        annotations: BTreeMap::new(),
+    })
+}
+
+/// Packages a directory as a single layer OCI image layout. Files are placed under `/model`
+///
+/// # Arguments
+///
+/// * `src` - Directory with the model files
+/// * `layout` - OCI layout directory to write
+/// * `ref_name` - Reference name the image is tagged with in the index
+/// * `architecture` - CPU architecture recorded in the image config, e.g. `amd64`
+/// * `annotations` - Manifest annotations
+///
+/// # Returns
+/// * `Result<String, String>` - Manifest digest
+///
+pub fn write_layout(
+    src: &Path,
+    layout: &Path,
+    ref_name: &str,
+    architecture: &str,
+    annotations: BTreeMap<String, String>,
+) -> Result<String, anyhow::Error> {
+    let blobs = layout.join("blobs").join("sha256");
+    fs::create_dir_all(&blobs).with_context(|| format!("Failed to create {}", blobs.display()))?;
+
+    // the layer is hashed while it is written, then moved to its content address
+    let staging = blobs.join("layer.tmp");
+    let written = fs::File::create(&staging)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| {
+            let writer = HashingWriter {
+                inner: io::BufWriter::new(file),
+                hasher: Sha256::new(),
+                size: 0,
+            };
+            let mut writer = write_reproducible_tar(src, Path::new(LAYER_ROOT), writer)?;
+            writer.flush()?;
+            Ok(writer)
+        });
+    let writer = match written {
+        Ok(writer) => writer,
+        Err(e) => {
+            if staging.exists() {
+                fs::remove_file(&staging)?;
+            }
+            return Err(e);
+        }
+    };
+    let layer = types::OciDescriptor {
+        media_type: LAYER_MEDIA_TYPE.to_string(),
+        digest: format!("sha256:{:x}", writer.hasher.finalize()),
+        size: writer.size,
+        annotations: BTreeMap::new(),
+    };
+    fs::rename(&staging, blob_path(layout, &layer.digest))?;
+
+    // the layer is uncompressed, so its diff id is its digest
+    let config = types::OciImageConfig {
+        architecture: architecture.to_string(),
+        os: "linux".to_string(),
+        rootfs: types::OciRootfs {
+            rootfs_type: "layers".to_string(),
+            diff_ids: vec![layer.digest.clone()],
+        },
+    };
+    let config = write_json_blob(layout, CONFIG_MEDIA_TYPE, &config)?;
+
+    let manifest = types::OciManifest {
+        schema_version: 2,
+        media_type: MANIFEST_MEDIA_TYPE.to_string(),
+        config,
+        layers: vec![layer],
+        annotations,
+    };
+    let mut manifest = write_json_blob(layout, MANIFEST_MEDIA_TYPE, &manifest)?;
+    manifest
+        .annotations
+        .insert(REF_NAME_ANNOTATION.to_string(), ref_name.to_string());
+    let manifest_digest = manifest.digest.clone();
+
+    let index = types::OciIndex {
+        schema_version: 2,
+        media_type: INDEX_MEDIA_TYPE.to_string(),
+        manifests: vec![manifest],
+    };
+    fs::write(layout.join("index.json"), serde_json::to_vec(&index)?)?;
+    fs::write(
+        layout.join("oci-layout"),
+        serde_json::to_vec(&types::OciLayout {
+            image_layout_version: "1.0.0".to_string(),
+        })?,
+    )?;
+
+    Ok(manifest_digest)
+}
+
+/// Manifest annotations describing the model
+///
+/// # Arguments
+///
+/// * `card` - Model card
+/// * `metadata` - Model metadata
+///
+pub fn model_annotations(
+    card: &types::Card,
+    metadata: &types::ModelMetadata,
+) -> BTreeMap<String, String> {
+    let mut annotations = BTreeMap::from([
+        (
+            "org.opencontainers.image.title".to_string(),
+            card.name.clone(),
+        ),
+        (
+            "org.opencontainers.image.version".to_string(),
+            card.version.clone(),
+        ),
+        ("ai.opsml.model.name".to_string(), card.name.clone()),
+        (
+            "ai.opsml.model.repository".to_string(),
+            card.repository.clone(),
+        ),
+        ("ai.opsml.model.version".to_string(), card.version.clone()),
+        ("ai.opsml.model.uid".to_string(), card.uid.clone()),
+        (
+            "ai.opsml.model.model_type".to_string(),
+            metadata.model_type.clone(),
+        ),
+    ]);
+    if let Some(onnx_version) = &metadata.onnx_version {
+        annotations.insert(
+            "ai.opsml.model.onnx_version".to_string(),
+            onnx_version.clone(),
+        );
+    }
+
+    annotations
+}
+
+pub struct OciExporter<'a> {
+    pub name: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub repository: Option<&'a str>,
+    pub uid: Option<&'a str>,
+    pub write_dir: &'a str,
+    pub architecture: &'a str,
+    pub onnx: &'a bool,
+    pub quantize: &'a bool,
+    pub preprocessor: &'a bool,
+    pub ignore_release_candidates: &'a bool,
+}
+
+impl OciExporter<'_> {
+    /// Downloads the model into a staging directory of its own and writes it as an OCI image
+    /// layout, so only this model's files are packaged
+    ///
+    /// # Arguments
+    ///
+    /// * `layout` - OCI layout directory to write
+    ///
+    /// # Returns
+    /// * `Result<String, String>` - Manifest digest
+    ///
+    async fn export_layout(&self, layout: &Path) -> Result<String, anyhow::Error> {
+        let card = cards::get_card(
+            "model",
+            self.name,
+            self.repository,
+            self.version,
+            self.uid,
+            *self.ignore_release_candidates,
+        )
+        .await?;
+
+        let staging = Path::new(self.write_dir).join(format!(".oci-export-{}", std::process::id()));
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+
+        let result = self.stage_and_write(&card, &staging, layout).await;
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        result
+    }
+
+    /// Downloads the model to the staging directory and writes the layout from it
+    ///
+    /// # Arguments
+    ///
+    /// * `card` - Model card
+    /// * `staging` - Empty directory the model is downloaded to
+    /// * `layout` - OCI layout directory to write
+    ///
+    async fn stage_and_write(
+        &self,
+        card: &types::Card,
+        staging: &Path,
+        layout: &Path,
+    ) -> Result<String, anyhow::Error> {
+        let staging_dir = staging
+            .to_str()
+            .with_context(|| "Invalid write directory")?;
+        download_model(
+            None,
+            None,
+            None,
+            Some(&card.uid),
+            staging_dir,
+            self.onnx,
+            self.quantize,
+            self.preprocessor,
+            self.ignore_release_candidates,
+            &false,
+            &[],
+            &[],
+        )
+        .await?;
+
+        let metadata_path = staging.join(MODEL_METADATA_FILE);
+        let metadata: types::ModelMetadata = serde_json::from_str(
+            &fs::read_to_string(&metadata_path)
+                .with_context(|| format!("Failed to read {}", metadata_path.display()))?,
+        )
+        .with_context(|| "Failed to parse model Metadata")?;
+
+        write_layout(
+            staging,
+            layout,
+            &card.version,
+            self.architecture,
+            model_annotations(card, &metadata),
+        )
+    }
+}
+
+/// Exports a model as an OCI image layout directory or a tar of one
+///
+/// # Arguments
+///
+/// * `exporter` - Model to export
+/// * `output` - Tar file to write
+/// * `layout` - Layout directory to write
+///
+pub async fn export_oci(
+    exporter: &OciExporter<'_>,
+    output: Option<&str>,
+    layout: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let digest = match (output, layout) {
+        (_, Some(layout)) => {
+            let digest = exporter.export_layout(Path::new(layout)).await?;
+            println!("Wrote OCI layout to {}", layout.green());
+            digest
+        }
+        (Some(output), None) => {
+            let staging = PathBuf::from(format!("{}.layout", output));
+            if staging.exists() {
+                fs::remove_dir_all(&staging)?;
+            }
+
+            let result = match exporter.export_layout(&staging).await {
+                Ok(digest) => fs::File::create(output)
+                    .with_context(|| format!("Failed to create {}", output))
+                    .and_then(|file| {
+                        write_reproducible_tar(&staging, Path::new(""), io::BufWriter::new(file))
+                    })
+                    .and_then(|mut writer| Ok(writer.flush()?))
+                    .map(|()| digest),
+                Err(e) => Err(e),
+            };
+            if staging.exists() {
+                fs::remove_dir_all(&staging)?;
+            }
+            let digest = result?;
+
+            println!("Wrote OCI archive to {}", output.green());
+            digest
+        }
+        (None, None) => return Err(anyhow::Error::msg("Provide --output or --layout")),
+    };
+
+    println!("Manifest digest: {}", digest);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn sha256_file(path: &Path) -> String {
+        format!("sha256:{:x}", Sha256::digest(fs::read(path).unwrap()))
+    }
+
+    #[test]
+    fn test_write_layout() {
+        let dir = env::temp_dir().join(format!("opsml-oci-{}", uuid::Uuid::new_v4()));
+        let src = dir.join("models");
+        fs::create_dir_all(src.join("preprocessor")).unwrap();
+        fs::write(src.join("model.onnx"), "onnx").unwrap();
+        fs::write(src.join("preprocessor").join("scaler.json"), "{}").unwrap();
+
+        let annotations = BTreeMap::from([("ai.opsml.model.uid".to_string(), "uid".to_string())]);
+        let layout = dir.join("layout");
+        let digest = write_layout(&src, &layout, "1.0.0", "arm64", annotations.clone()).unwrap();
+
+        // every blob is stored under its digest
+        let index: types::OciIndex =
+            serde_json::from_slice(&fs::read(layout.join("index.json")).unwrap()).unwrap();
+        assert_eq!(index.manifests[0].digest, digest);
+        assert_eq!(index.manifests[0].annotations[REF_NAME_ANNOTATION], "1.0.0");
+
+        let manifest_path = blob_path(&layout, &digest);
+        assert_eq!(sha256_file(&manifest_path), digest);
+        let manifest: types::OciManifest =
+            serde_json::from_slice(&fs::read(manifest_path).unwrap()).unwrap();
+        assert_eq!(manifest.annotations["ai.opsml.model.uid"], "uid");
+        let config: types::OciImageConfig =
+            serde_json::from_slice(&fs::read(blob_path(&layout, &manifest.config.digest)).unwrap())
+                .unwrap();
+        assert_eq!(config.architecture, "arm64");
+
+        let layer_path = blob_path(&layout, &manifest.layers[0].digest);
+        assert_eq!(sha256_file(&layer_path), manifest.layers[0].digest);
+        assert_eq!(
+            fs::metadata(&layer_path).unwrap().len(),
+            manifest.layers[0].size
+        );
+
+        let mut archive = tar::Archive::new(fs::File::open(&layer_path).unwrap());
+        let paths = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "model",
+                "model/model.onnx",
+                "model/preprocessor",
+                "model/preprocessor/scaler.json"
+            ]
+        );
+
+        // the same files always produce the same image
+        let again = write_layout(&src, &dir.join("again"), "1.0.0", "arm64", annotations).unwrap();
+        assert_eq!(again, digest);
+
+        // a failed layer write leaves no partial blob behind
+        let failed = dir.join("failed");
+        assert!(write_layout(
+            &dir.join("missing"),
+            &failed,
+            "1.0.0",
+            "arm64",
+            BTreeMap::new()
+        )
+        .is_err());
+        assert!(!failed.join("blobs/sha256/layer.tmp").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_export_oci() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url.clone());
+        }
+
+        let cards = fs::read_to_string("./src/api/test_utils/list_cards.json").unwrap();
+        let metadata = fs::read_to_string("./src/api/test_utils/metadata.json").unwrap();
+        let files = types::ListFileResponse {
+            files: vec![
+                "opsml-root:/OPSML_MODEL_REGISTRY/devops-ml/linear-reg-model/v1.1.0/model.onnx"
+                    .to_string(),
+            ],
+        };
+
+        let mock_cards = server
+            .mock("POST", "/opsml/cards/list")
+            .with_status(201)
+            .with_body(cards)
+            .create();
+        let _mock_metadata = server
+            .mock("POST", "/opsml/models/metadata")
+            .with_status(201)
+            .with_body(metadata)
+            .create();
+        let _mock_list = server
+            .mock("GET", "/opsml/files/list?path=models.json")
+            .with_status(200)
+            .with_body(serde_json::to_string(&files).unwrap())
+            .create();
+        let _mock_presigned = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/opsml/files/presigned".to_string()),
+            )
+            .with_status(200)
+            .with_body(format!(r#"{{"url": "{}/model-bytes"}}"#, url))
+            .create();
+        let _mock_download = server
+            .mock("GET", "/model-bytes")
+            .with_status(200)
+            .with_body("onnx bytes")
+            .create();
+
+        let dir = env::temp_dir().join(format!("opsml-export-oci-{}", uuid::Uuid::new_v4()));
+        let write_dir = dir.join("models");
+        // files of other models in the write dir are not packaged
+        fs::create_dir_all(&write_dir).unwrap();
+        fs::write(write_dir.join("stale.bin"), "stale").unwrap();
+        let output = write_dir.join("model.tar");
+        let exporter = OciExporter {
+            name: None,
+            version: None,
+            repository: None,
+            uid: Some("1234567890"),
+            write_dir: write_dir.to_str().unwrap(),
+            architecture: "amd64",
+            onnx: &true,
+            quantize: &false,
+            preprocessor: &false,
+            ignore_release_candidates: &false,
+        };
+        export_oci(&exporter, output.to_str(), None).await.unwrap();
+        mock_cards.assert();
+
+        // the archive holds the layout and the staging directory is cleaned up
+        let layout = dir.join("unpacked");
+        tar::Archive::new(fs::File::open(&output).unwrap())
+            .unpack(&layout)
+            .unwrap();
+        assert!(layout.join("oci-layout").exists());
+        assert!(!write_dir.join("model.tar.layout").exists());
+        assert_eq!(
+            fs::read_dir(&write_dir).unwrap().count(),
+            2,
+            "only the stale file and the archive are left in the write dir"
+        );
+
+        let index: types::OciIndex =
+            serde_json::from_slice(&fs::read(layout.join("index.json")).unwrap()).unwrap();
+        let manifest: types::OciManifest = serde_json::from_slice(
+            &fs::read(blob_path(&layout, &index.manifests[0].digest)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest.annotations["ai.opsml.model.uid"], "1234567890");
+        assert_eq!(
+            manifest.annotations["ai.opsml.model.model_type"],
+            "LinearRegression"
+        );
+        assert_eq!(
+            manifest.annotations["ai.opsml.model.onnx_version"],
+            "1.14.1"
+        );
+
+        let mut layer = tar::Archive::new(
+            fs::File::open(blob_path(&layout, &manifest.layers[0].digest)).unwrap(),
+        );
+        let paths = layer
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "model".to_string(),
+                format!("model/{}", MODEL_METADATA_FILE),
+                "model/model.onnx".to_string(),
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
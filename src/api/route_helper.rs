@@ -1,6 +1,7 @@
 /// Copyright (c) Shipt, Inc.
 /// This source code is licensed under the MIT license found in the
 /// LICENSE file in the root directory of this source tree.
+use crate::api::storage::{self, ByteStream, StorageBackend};
 use crate::api::types;
 use crate::api::types::PresignedUrl;
 use crate::api::utils;
@@ -78,26 +79,20 @@ impl RouteHelper {
         Ok(files)
     }
 
-    /// Downloads a stream to a file
+    /// Writes a byte stream to a file
     ///
     /// # Arguments
     ///
-    /// * `response` - Response object
+    /// * `stream` - Bytes of the remote file
     /// * `filename` - Path to save file to
     ///
     /// # Returns
     /// * `Result<(), String>` - Result of file download
     ///
-    pub async fn download_presigned_url_to_file(
-        presigned_url: PresignedUrl,
+    pub async fn download_stream_to_file(
+        mut stream: ByteStream,
         filename: &Path,
     ) -> Result<(), anyhow::Error> {
-        let response = RouteHelper::make_get_request(&presigned_url.url, None)
-            .await
-            .with_context(|| format!("failed to download file for {:?}", filename))?;
-
-        let mut response_stream = response.bytes_stream().chunks(8192);
-
         let mut file = tokio::fs::File::create(filename).await.with_context(|| {
             format!(
                 "failed to create file for {:?}",
@@ -105,69 +100,159 @@ impl RouteHelper {
             )
         })?;
 
-        while let Some(chunk) = response_stream.next().await {
-            let chunk = chunk
-                .into_iter()
-                .collect::<Result<Vec<_>, _>>()
-                .with_context(|| format!("failed to read response for {:?}", filename))?;
-            file.write_all(&chunk.concat())
+        while let Some(chunk) = stream.next().await {
+            let chunk =
+                chunk.with_context(|| format!("failed to read response for {:?}", filename))?;
+            file.write_all(&chunk)
                 .await
                 .with_context(|| format!("failed to write response to file {:?}", filename))?;
         }
+
+        // tokio writes in the background, so the file is incomplete until flushed
+        file.flush()
+            .await
+            .with_context(|| format!("failed to write response to file {:?}", filename))?;
         Ok(())
     }
 
-    /// Downloads an artifact file. Retries up to 3 times if download fails.
+    /// Gets a presigned url for reading a remote file
     ///
     /// # Arguments
     ///
-    /// * `url` - url of opsml server
-    /// * `uri` - uri of model
-    /// * `local_save_path` - path to save model to
+    /// * `rpath` - Remote path to file
     ///
     /// # Returns
-    /// * `Result<(), String>` - Result of file download
+    /// * `Result<PresignedUrl, String>` - Presigned url for the file
     ///
-    pub async fn download_file(lpath: &Path, rpath: &str) -> Result<(), anyhow::Error> {
+    pub async fn get_presigned_url(rpath: &str) -> Result<PresignedUrl, anyhow::Error> {
         let params = [("path", rpath), ("method", "GET")];
-        let max_attempts = 3;
+        let response = RouteHelper::make_get_request(
+            &utils::OpsmlPaths::DownloadPresigned.as_str(),
+            Some(&params),
+        )
+        .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to get presigned url for {}: {}",
+                rpath.red(),
+                response.text().await?
+            ));
+        }
 
-        for attempt in 1..=max_attempts {
-            let response = RouteHelper::make_get_request(
-                &utils::OpsmlPaths::DownloadPresigned.as_str(),
-                Some(&params),
-            )
+        let presigned_url = response
+            .json::<PresignedUrl>()
             .await
-            .with_context(|| format!("failed to download model on attempt {}", attempt))?;
-
-            if response.status().is_success() {
-                let presigned_uri: PresignedUrl = response.json().await.with_context(|| {
-                    format!(
-                        "failed to parse presigned url for {:?} on attempt {}",
-                        rpath.to_string().red(),
-                        attempt
-                    )
+            .with_context(|| format!("failed to parse presigned url for {:?}", rpath.red()))?;
+
+        // servers on local storage hand back file paths rather than urls
+        if !presigned_url.url.starts_with("http") {
+            return Err(anyhow::anyhow!(
+                "Server returned a non-http presigned url for {}: {}",
+                rpath.red(),
+                presigned_url.url
+            ));
+        }
+
+        Ok(presigned_url)
+    }
+
+    /// Streams a remote file through the server's download route. Used when the server cannot
+    /// presign urls, e.g. when it is configured with local storage
+    ///
+    /// # Arguments
+    ///
+    /// * `rpath` - Remote path to file
+    ///
+    pub async fn stream_from_server(rpath: &str) -> Result<ByteStream, anyhow::Error> {
+        let params = [("path", rpath)];
+        let response =
+            RouteHelper::make_get_request(&utils::OpsmlPaths::DownloadFile.as_str(), Some(&params))
+                .await?
+                .error_for_status()
+                .with_context(|| {
+                    format!("failed to download {:?} through the server", rpath.red())
                 })?;
 
-                if let Err(e) =
-                    RouteHelper::download_presigned_url_to_file(presigned_uri, lpath).await
-                {
-                    eprintln!(
-                        "Attempt {}: failed to download file for {:?}: {}",
-                        attempt,
-                        lpath.to_str().unwrap().red(),
-                        e
-                    );
-                } else {
-                    return Ok(());
-                }
-            } else {
-                let error_message = format!(
-                    "Attempt {}: Failed to download model: {}",
+        Ok(response
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(std::io::Error::other))
+            .boxed())
+    }
+
+    /// Opens a remote file as a byte stream. Files on a configured storage mount are read from
+    /// disk and files in a configured storage bucket are read from the bucket. Otherwise the file
+    /// is fetched from a presigned url, falling back to streaming it through the server when no
+    /// presigned url is available
+    ///
+    /// # Arguments
+    ///
+    /// * `rpath` - Remote path to file
+    ///
+    pub async fn open_file(rpath: &str) -> Result<ByteStream, anyhow::Error> {
+        if let Some(stream) = storage::open_mounted(rpath).await? {
+            return Ok(stream);
+        }
+        if let Some(backend) = StorageBackend::from_env()? {
+            if let Some(stream) = backend.open(rpath).await? {
+                return Ok(stream);
+            }
+        }
+
+        let presigned_url = match RouteHelper::get_presigned_url(rpath).await {
+            Ok(presigned_url) => presigned_url,
+            Err(presign_error) => {
+                return RouteHelper::stream_from_server(rpath)
+                    .await
+                    .with_context(|| format!("{:#}", presign_error));
+            }
+        };
+
+        let response = RouteHelper::make_get_request(&presigned_url.url, None)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("failed to read {:?}", rpath.red()))?;
+
+        Ok(response
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(std::io::Error::other))
+            .boxed())
+    }
+
+    /// Downloads an artifact file. Files in a configured storage bucket are downloaded with
+    /// parallel range requests, anything else retries up to 3 times if download fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `lpath` - path to save the file to
+    /// * `rpath` - remote path of the file
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Result of file download
+    ///
+    pub async fn download_file(lpath: &Path, rpath: &str) -> Result<(), anyhow::Error> {
+        if let Some(backend) = StorageBackend::from_env()? {
+            if backend.download(rpath, lpath).await? {
+                return Ok(());
+            }
+        }
+
+        let max_attempts = 3;
+
+        for attempt in 1..=max_attempts {
+            let result = match RouteHelper::open_file(rpath).await {
+                Ok(stream) => RouteHelper::download_stream_to_file(stream, lpath).await,
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) => eprintln!(
+                    "Attempt {}: failed to download file for {:?}: {:#}",
                     attempt,
-                    response.text().await.unwrap().red()
-                );
-                eprintln!("{}", error_message);
+                    lpath.to_str().unwrap().red(),
+                    e
+                ),
             }
         }
 
@@ -338,4 +423,74 @@ mod tests {
         // delte path
         fs::remove_file(lpath).unwrap();
     }
+
+    #[tokio::test]
+    async fn test_download_file_fallback() {
+        let mut download_server = mockito::Server::new_async().await;
+        let url = download_server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url.clone());
+        }
+
+        // a server on local storage cannot presign, so the file is streamed through it
+        let mock_presigned_path = download_server
+            .mock(
+                "GET",
+                "/opsml/files/presigned?path=fallback.json&method=GET",
+            )
+            .with_status(501)
+            .with_body("presigned urls are not supported")
+            .create();
+        let mock_download_path = download_server
+            .mock("GET", "/opsml/files/download?path=fallback.json")
+            .with_status(200)
+            .with_body("streamed")
+            .create();
+
+        let lpath = env::temp_dir().join(format!("{}.json", Uuid::new_v4()));
+        RouteHelper::download_file(&lpath, "fallback.json")
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&lpath).unwrap(), "streamed");
+
+        mock_presigned_path.assert();
+        mock_download_path.assert();
+
+        // files on a storage mount never reach the server
+        mock_presigned_path.remove();
+        mock_download_path.remove();
+        let mount = env::temp_dir().join(format!("opsml-mount-{}", Uuid::new_v4()));
+        fs::create_dir_all(mount.join("OPSML_MODEL_REGISTRY")).unwrap();
+        fs::write(mount.join("OPSML_MODEL_REGISTRY/model.onnx"), "mounted").unwrap();
+
+        unsafe {
+            env::set_var(storage::STORAGE_MOUNT_VAR, &mount);
+        }
+        let result =
+            RouteHelper::download_file(&lpath, "opsml-root:/OPSML_MODEL_REGISTRY/model.onnx").await;
+        unsafe {
+            env::remove_var(storage::STORAGE_MOUNT_VAR);
+        }
+        result.unwrap();
+        assert_eq!(fs::read_to_string(&lpath).unwrap(), "mounted");
+
+        // as do files in a storage bucket
+        unsafe {
+            env::set_var(
+                storage::STORAGE_URI_VAR,
+                format!("file://{}", mount.display()),
+            );
+        }
+        fs::write(mount.join("OPSML_MODEL_REGISTRY/model.onnx"), "bucket").unwrap();
+        let result =
+            RouteHelper::download_file(&lpath, "opsml-root:/OPSML_MODEL_REGISTRY/model.onnx").await;
+        unsafe {
+            env::remove_var(storage::STORAGE_URI_VAR);
+        }
+        result.unwrap();
+        assert_eq!(fs::read_to_string(&lpath).unwrap(), "bucket");
+
+        fs::remove_file(lpath).unwrap();
+        fs::remove_dir_all(mount).unwrap();
+    }
 }
@@ -0,0 +1,428 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::api_version::{self, ApiVersion};
+use crate::api::cards;
+use crate::api::metrics::{parse_metrics, METRIC_BATCH_SIZE};
+use crate::api::types;
+use crate::api::utils::{self, OpsmlPaths};
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use reqwest::header::CONTENT_LENGTH;
+use reqwest::{Response, StatusCode, Url};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::Path;
+use tokio_util::io::ReaderStream;
+
+/// Prefix of environment variables naming servers, e.g. `OPSML_TRACKING_URI_STAGING` for a
+/// server named `staging`
+pub const SERVER_VAR_PREFIX: &str = "OPSML_TRACKING_URI_";
+
+/// Resolves a server given on the command line. Urls are used as is, names are read from
+/// `OPSML_TRACKING_URI_<NAME>`
+///
+/// # Arguments
+///
+/// * `server` - Server url or name
+///
+/// # Returns
+/// * `Result<String, String>` - Server uri without a trailing slash
+///
+pub fn resolve_server_uri(server: &str) -> Result<String, anyhow::Error> {
+    if server.starts_with("http://") || server.starts_with("https://") {
+        return Ok(utils::remove_suffix(server, '/'));
+    }
+
+    let var = format!(
+        "{}{}",
+        SERVER_VAR_PREFIX,
+        server.to_uppercase().replace('-', "_")
+    );
+    let uri = env::var(&var)
+        .with_context(|| format!("{} is not a url and {} is not set", server.red(), var))?;
+
+    Ok(utils::remove_suffix(&uri, '/'))
+}
+
+/// An OpsML server other than the one at `OPSML_TRACKING_URI`. Requests are routed for the api
+/// version the server serves rather than the process wide one
+pub struct OpsmlServer {
+    pub uri: String,
+    pub api_version: ApiVersion,
+    client: reqwest::Client,
+}
+
+impl OpsmlServer {
+    /// Resolves a server and detects its api version
+    ///
+    /// # Arguments
+    ///
+    /// * `server` - Server url or name
+    ///
+    pub async fn connect(server: &str) -> Result<Self, anyhow::Error> {
+        let uri = resolve_server_uri(server)?;
+        let client = reqwest::Client::new();
+        let api_version = api_version::detect(&client, &uri)
+            .await
+            .with_context(|| format!("Failed to reach {}", uri.red()))?;
+
+        Ok(OpsmlServer {
+            uri,
+            api_version,
+            client,
+        })
+    }
+
+    fn url(&self, path: &OpsmlPaths, params: &[(&str, &str)]) -> Result<Url, anyhow::Error> {
+        let mut url = Url::parse(&self.api_version.url(&self.uri, path))
+            .with_context(|| "Failed to parse url")?;
+        for (key, value) in params {
+            url.query_pairs_mut().append_pair(key, value);
+        }
+        Ok(url)
+    }
+
+    /// Returns the response if it succeeded, otherwise an error with the response body
+    async fn check(response: Response, action: &str) -> Result<Response, anyhow::Error> {
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(anyhow::Error::msg(format!(
+                "Failed to {}: {} {}",
+                action,
+                response.status(),
+                response.text().await?
+            )))
+        }
+    }
+
+    /// Lists cards as the server returns them
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - Registry to list cards from
+    /// * `name` - Name of card
+    /// * `repository` - Repository of card
+    /// * `version` - Card version
+    /// * `uid` - Card uid
+    /// * `ignore_release_candidates` - Whether to ignore release candidates
+    ///
+    pub async fn list_cards(
+        &self,
+        registry: &str,
+        name: Option<&str>,
+        repository: Option<&str>,
+        version: Option<&str>,
+        uid: Option<&str>,
+        ignore_release_candidates: &bool,
+    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        let tags = HashMap::new();
+        let request = types::ListTableRequest {
+            registry_type: registry,
+            name,
+            repository,
+            version,
+            uid,
+            limit: None,
+            tags: &tags,
+            max_date: None,
+            ignore_release_candidates,
+        };
+
+        let url = self.url(&OpsmlPaths::ListCard, &[])?;
+        let request = match self.api_version {
+            ApiVersion::V2 => self.client.post(url).json(&request),
+            ApiVersion::V3 => self
+                .client
+                .post(url)
+                .json(&types::ListCardRequestV3::from(&request)),
+        };
+        let response = OpsmlServer::check(request.send().await?, "list cards").await?;
+
+        cards::parse_raw_cards(&response.text().await?, self.api_version)
+    }
+
+    /// Registers a card
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - Registry to register the card in
+    /// * `card` - Card as returned by another server
+    ///
+    pub async fn register_card(
+        &self,
+        registry: &str,
+        card: &serde_json::Value,
+    ) -> Result<(), anyhow::Error> {
+        let request = types::CreateCardRequest {
+            registry_type: registry,
+            card,
+        };
+        let url = self.url(&OpsmlPaths::CreateCard, &[])?;
+        OpsmlServer::check(
+            self.client.post(url).json(&request).send().await?,
+            "register card",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Checks whether the server has registered a card. The server may only hold the card's
+    /// version under the same uid
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - Registry of the card
+    /// * `card` - Card to look for
+    ///
+    pub async fn is_registered(
+        &self,
+        registry: &str,
+        card: &types::Card,
+    ) -> Result<bool, anyhow::Error> {
+        let registered = self
+            .list_cards(
+                registry,
+                Some(&card.name),
+                Some(&card.repository),
+                Some(&card.version),
+                None,
+                &false,
+            )
+            .await?;
+
+        match registered.first().and_then(|other| other.get("uid")) {
+            Some(uid) if uid.as_str() == Some(card.uid.as_str()) => Ok(true),
+            Some(uid) => Err(anyhow::Error::msg(format!(
+                "Version {} of {} is already registered on {} with uid {}",
+                card.version.red(),
+                card.name.red(),
+                self.uri,
+                uid
+            ))),
+            None => Ok(false),
+        }
+    }
+
+    /// Lists the files under a remote path
+    pub async fn list_files(&self, rpath: &Path) -> Result<Vec<String>, anyhow::Error> {
+        let url = self.url(&OpsmlPaths::ListFile, &[("path", &rpath.to_string_lossy())])?;
+        let response = OpsmlServer::check(self.client.get(url).send().await?, "list files").await?;
+
+        Ok(response.json::<types::ListFileResponse>().await?.files)
+    }
+
+    /// Lists the files under a remote path that may not exist yet. A path the server does not
+    /// know has no files, any other failure is an error
+    pub async fn list_existing_files(
+        &self,
+        rpath: &Path,
+    ) -> Result<HashSet<String>, anyhow::Error> {
+        let url = self.url(&OpsmlPaths::ListFile, &[("path", &rpath.to_string_lossy())])?;
+        let response = self.client.get(url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(HashSet::new());
+        }
+
+        let response = OpsmlServer::check(response, "list files").await?;
+        Ok(response
+            .json::<types::ListFileResponse>()
+            .await?
+            .files
+            .into_iter()
+            .collect())
+    }
+
+    /// Gets a presigned url for a remote file. Returns `None` when the server cannot presign,
+    /// e.g. when it is configured with local storage
+    ///
+    /// # Arguments
+    ///
+    /// * `rpath` - Remote path of the file
+    /// * `method` - Http method the url is used with
+    ///
+    async fn presigned_url(
+        &self,
+        rpath: &str,
+        method: &str,
+    ) -> Result<Option<String>, anyhow::Error> {
+        let url = self.url(
+            &OpsmlPaths::DownloadPresigned,
+            &[("path", rpath), ("method", method)],
+        )?;
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        let presigned_url = response.json::<types::PresignedUrl>().await?;
+        Ok(presigned_url
+            .url
+            .starts_with("http")
+            .then_some(presigned_url.url))
+    }
+
+    /// Opens a remote file from a presigned url, or through the server's download route
+    pub async fn open_file(&self, rpath: &str) -> Result<Response, anyhow::Error> {
+        let request = match self.presigned_url(rpath, "GET").await? {
+            Some(url) => self.client.get(url),
+            None => self
+                .client
+                .get(self.url(&OpsmlPaths::DownloadFile, &[("path", rpath)])?),
+        };
+
+        OpsmlServer::check(request.send().await?, &format!("read {}", rpath)).await
+    }
+
+    /// Streams a body to a presigned url, or through the server's upload route
+    ///
+    /// # Arguments
+    ///
+    /// * `rpath` - Remote path to write to
+    /// * `body` - File's bytes
+    /// * `length` - Size of the file, if known
+    ///
+    async fn upload_body(
+        &self,
+        rpath: &str,
+        body: reqwest::Body,
+        length: Option<u64>,
+    ) -> Result<(), anyhow::Error> {
+        let mut request = match self.presigned_url(rpath, "PUT").await? {
+            Some(url) => self.client.put(url),
+            None => self
+                .client
+                .post(self.url(&OpsmlPaths::UploadFile, &[("path", rpath)])?),
+        };
+
+        // presigned uploads are rejected without a content length
+        if let Some(length) = length {
+            request = request.header(CONTENT_LENGTH, length);
+        }
+
+        OpsmlServer::check(
+            request.body(body).send().await?,
+            &format!("write {}", rpath),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Streams a file read from another server
+    ///
+    /// # Arguments
+    ///
+    /// * `rpath` - Remote path to write to
+    /// * `source` - Response with the file's bytes
+    ///
+    pub async fn upload_file(&self, rpath: &str, source: Response) -> Result<(), anyhow::Error> {
+        let length = source.content_length();
+        let body = reqwest::Body::wrap_stream(source.bytes_stream());
+
+        self.upload_body(rpath, body, length).await
+    }
+
+    /// Streams a local file
+    ///
+    /// # Arguments
+    ///
+    /// * `rpath` - Remote path to write to
+    /// * `lpath` - Local file to read
+    ///
+    pub async fn upload_local_file(&self, rpath: &str, lpath: &Path) -> Result<(), anyhow::Error> {
+        let file = tokio::fs::File::open(lpath)
+            .await
+            .with_context(|| format!("Failed to open {}", lpath.display()))?;
+        let length = file.metadata().await?.len();
+        let body = reqwest::Body::wrap_stream(ReaderStream::new(file));
+
+        self.upload_body(rpath, body, Some(length)).await
+    }
+
+    /// Fetches the metrics logged to a run
+    pub async fn fetch_metrics(&self, run_uid: &str) -> Result<Vec<types::Metric>, anyhow::Error> {
+        let url = self.url(&OpsmlPaths::Metric, &[("run_uid", run_uid)])?;
+        let response =
+            OpsmlServer::check(self.client.get(url).send().await?, "fetch metrics").await?;
+
+        parse_metrics(&response.text().await?, self.api_version)
+    }
+
+    /// Logs the metrics a run does not have yet. Metrics keep their run uid, step and timestamp
+    ///
+    /// # Arguments
+    ///
+    /// * `run_uid` - Run the metrics were logged to
+    /// * `metrics` - Metrics to copy
+    ///
+    /// # Returns
+    /// * `Result<(usize, usize), String>` - Number of metrics logged and already on the server
+    ///
+    pub async fn log_missing_metrics(
+        &self,
+        run_uid: &str,
+        metrics: Vec<types::Metric>,
+    ) -> Result<(usize, usize), anyhow::Error> {
+        let existing = self.fetch_metrics(run_uid).await?;
+        let total = metrics.len();
+
+        let missing: Vec<types::Metric> = metrics
+            .into_iter()
+            .filter(|metric| {
+                !existing.iter().any(|other| {
+                    other.name == metric.name
+                        && other.step == metric.step
+                        && other.value == metric.value
+                })
+            })
+            .collect();
+
+        if !missing.is_empty() {
+            self.log_metrics(&missing).await?;
+        }
+
+        Ok((missing.len(), total - missing.len()))
+    }
+
+    /// Logs metrics to a run
+    async fn log_metrics(&self, metrics: &[types::Metric]) -> Result<(), anyhow::Error> {
+        let url = self.url(&OpsmlPaths::Metric, &[])?;
+        for batch in metrics.chunks(METRIC_BATCH_SIZE) {
+            let request = types::InsertMetricRequest { metric: batch };
+            OpsmlServer::check(
+                self.client.post(url.clone()).json(&request).send().await?,
+                "log metrics",
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_server_uri() {
+        assert_eq!(
+            resolve_server_uri("https://opsml.example.com/").unwrap(),
+            "https://opsml.example.com"
+        );
+
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI_PROD_EU", "http://prod-eu:8888/");
+        }
+        assert_eq!(
+            resolve_server_uri("prod-eu").unwrap(),
+            "http://prod-eu:8888"
+        );
+
+        let err = resolve_server_uri("missing").unwrap_err();
+        assert!(err.to_string().contains("OPSML_TRACKING_URI_MISSING"));
+    }
+}
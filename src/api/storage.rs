@@ -0,0 +1,419 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::utils::OPSML_ROOT;
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures_util::StreamExt;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, ObjectStoreScheme, WriteMultipart};
+use reqwest::Url;
+use std::env;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+/// Environment variable pointing at a local mount of the opsml storage root, e.g. a shared NFS
+/// registry. `opsml-root:/` paths found under it are read from disk instead of the server
+pub const STORAGE_MOUNT_VAR: &str = "OPSML_STORAGE_MOUNT";
+
+/// Environment variable naming the bucket behind `opsml-root:/`, e.g. `s3://bucket/opsml`.
+/// Artifacts are then transferred with the bucket's own api instead of through the server
+pub const STORAGE_URI_VAR: &str = "OPSML_STORAGE_URI";
+
+/// Size of each ranged GET and multipart upload part
+const PART_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Parts transferred at once
+const CONCURRENCY: usize = 8;
+
+/// Stream of file bytes, whether read from disk or from the network
+pub type ByteStream = BoxStream<'static, Result<Bytes, std::io::Error>>;
+
+/// Configured storage mount, if any
+pub fn storage_mount() -> Option<PathBuf> {
+    env::var_os(STORAGE_MOUNT_VAR)
+        .filter(|mount| !mount.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Path of an `opsml-root:/` uri relative to the storage root. Other paths and paths that would
+/// escape the root return `None`
+fn root_relative(rpath: &str) -> Option<&Path> {
+    let relative = Path::new(rpath.strip_prefix(OPSML_ROOT)?);
+
+    let inside_root = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    (inside_root && relative.components().next().is_some()).then_some(relative)
+}
+
+/// Maps an `opsml-root:/` path to its location under a storage mount. Other paths and paths that
+/// would escape the mount return `None`
+///
+/// # Arguments
+///
+/// * `mount` - Local directory the storage root is mounted at
+/// * `rpath` - Remote path of the file
+///
+pub fn mounted_path(mount: &Path, rpath: &str) -> Option<PathBuf> {
+    root_relative(rpath).map(|relative| mount.join(relative))
+}
+
+/// Opens a remote file from the storage mount. Returns `None` when no mount is configured or the
+/// file is not on it, so the caller can read it through the server instead
+///
+/// # Arguments
+///
+/// * `rpath` - Remote path of the file
+///
+pub async fn open_mounted(rpath: &str) -> Result<Option<ByteStream>, anyhow::Error> {
+    let Some(path) = storage_mount().and_then(|mount| mounted_path(&mount, rpath)) else {
+        return Ok(None);
+    };
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let file = tokio::fs::File::open(&path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    Ok(Some(ReaderStream::new(file).boxed()))
+}
+
+/// Cargo feature that enables a storage scheme
+fn scheme_feature(scheme: &ObjectStoreScheme) -> Option<&'static str> {
+    match scheme {
+        ObjectStoreScheme::AmazonS3 => Some("s3"),
+        ObjectStoreScheme::GoogleCloudStorage => Some("gcs"),
+        ObjectStoreScheme::MicrosoftAzure => Some("azure"),
+        _ => None,
+    }
+}
+
+/// Backend built for the last `OPSML_STORAGE_URI` read
+static SHARED_BACKEND: Mutex<Option<(String, Arc<StorageBackend>)>> = Mutex::new(None);
+
+/// Bucket the opsml storage root lives in. `opsml-root:/` paths are mapped under the bucket
+/// prefix and transferred with parallel range GETs and multipart uploads
+pub struct StorageBackend {
+    store: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+    part_size: u64,
+}
+
+impl StorageBackend {
+    /// Creates a backend from a storage uri. `file://` uris are always available, while `s3://`,
+    /// `gs://` and `az://` need the matching cargo feature. Credentials are read from the usual
+    /// `AWS_*`, `GOOGLE_*` and `AZURE_*` environment variables. Setting `AWS_ENDPOINT` and
+    /// `AWS_ALLOW_HTTP=true` points the s3 backend at a MinIO compatible server
+    ///
+    /// # Arguments
+    ///
+    /// * `uri` - Storage uri, e.g. `s3://bucket/opsml`
+    ///
+    pub fn from_uri(uri: &str) -> Result<Self, anyhow::Error> {
+        let url = Url::parse(uri).with_context(|| format!("Invalid storage uri: {}", uri))?;
+        let (scheme, prefix) = ObjectStoreScheme::parse(&url)
+            .with_context(|| format!("Unsupported storage uri: {}", uri))?;
+
+        let store: Arc<dyn ObjectStore> = match scheme {
+            ObjectStoreScheme::Local => Arc::new(LocalFileSystem::new()),
+            #[cfg(feature = "s3")]
+            ObjectStoreScheme::AmazonS3 => Arc::new(
+                object_store::aws::AmazonS3Builder::from_env()
+                    .with_url(uri)
+                    .build()?,
+            ),
+            #[cfg(feature = "gcs")]
+            ObjectStoreScheme::GoogleCloudStorage => Arc::new(
+                object_store::gcp::GoogleCloudStorageBuilder::from_env()
+                    .with_url(uri)
+                    .build()?,
+            ),
+            #[cfg(feature = "azure")]
+            ObjectStoreScheme::MicrosoftAzure => Arc::new(
+                object_store::azure::MicrosoftAzureBuilder::from_env()
+                    .with_url(uri)
+                    .build()?,
+            ),
+            other => {
+                return Err(anyhow::Error::msg(match scheme_feature(&other) {
+                    Some(feature) => format!(
+                        "{} needs opsml-cli built with the {} feature, e.g. cargo install opsml-cli --features {}",
+                        uri, feature, feature
+                    ),
+                    None => format!("Unsupported storage uri: {}", uri),
+                }))
+            }
+        };
+
+        Ok(StorageBackend {
+            store,
+            prefix,
+            part_size: PART_SIZE,
+        })
+    }
+
+    /// Backend configured with `OPSML_STORAGE_URI`, if any. The backend is built once per uri and
+    /// shared, so transfers of many files reuse one client and its connections
+    pub fn from_env() -> Result<Option<Arc<Self>>, anyhow::Error> {
+        let uri = match env::var(STORAGE_URI_VAR) {
+            Ok(uri) if !uri.is_empty() => uri,
+            _ => return Ok(None),
+        };
+
+        let mut shared = SHARED_BACKEND.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((shared_uri, backend)) = shared.as_ref() {
+            if *shared_uri == uri {
+                return Ok(Some(backend.clone()));
+            }
+        }
+
+        let backend = Arc::new(StorageBackend::from_uri(&uri)?);
+        *shared = Some((uri, backend.clone()));
+        Ok(Some(backend))
+    }
+
+    /// Location of an `opsml-root:/` path in the bucket
+    fn object_path(&self, rpath: &str) -> Option<ObjectPath> {
+        let relative = root_relative(rpath)?.to_str()?;
+        Some(ObjectPath::from(format!("{}/{}", self.prefix, relative)))
+    }
+
+    /// Opens a remote file as a byte stream. Returns `None` for files outside the storage root or
+    /// missing from the bucket
+    ///
+    /// # Arguments
+    ///
+    /// * `rpath` - Remote path of the file
+    ///
+    pub async fn open(&self, rpath: &str) -> Result<Option<ByteStream>, anyhow::Error> {
+        let Some(path) = self.object_path(rpath) else {
+            return Ok(None);
+        };
+
+        match self.store.get(&path).await {
+            Ok(result) => Ok(Some(
+                result
+                    .into_stream()
+                    .map(|chunk| chunk.map_err(std::io::Error::other))
+                    .boxed(),
+            )),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {} from storage", path)),
+        }
+    }
+
+    /// Downloads a remote file with parallel range GETs. Returns false for files outside the
+    /// storage root or missing from the bucket
+    ///
+    /// # Arguments
+    ///
+    /// * `rpath` - Remote path of the file
+    /// * `lpath` - Local path to write to
+    ///
+    pub async fn download(&self, rpath: &str, lpath: &Path) -> Result<bool, anyhow::Error> {
+        let Some(path) = self.object_path(rpath) else {
+            return Ok(false);
+        };
+
+        let size = match self.store.head(&path).await {
+            Ok(meta) => meta.size,
+            Err(object_store::Error::NotFound { .. }) => return Ok(false),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {} from storage", path))
+            }
+        };
+
+        let mut file = tokio::fs::File::create(lpath)
+            .await
+            .with_context(|| format!("Failed to create {}", lpath.display()))?;
+
+        // parts are fetched concurrently but written in order
+        let ranges = (0..size)
+            .step_by(self.part_size as usize)
+            .map(|start| start..(start + self.part_size).min(size));
+        let mut parts = futures::stream::iter(ranges)
+            .map(|range| self.store.get_range(&path, range))
+            .buffered(CONCURRENCY);
+
+        while let Some(part) = parts.next().await {
+            let part = part.with_context(|| format!("Failed to read {} from storage", path))?;
+            file.write_all(&part)
+                .await
+                .with_context(|| format!("Failed to write {}", lpath.display()))?;
+        }
+        file.flush()
+            .await
+            .with_context(|| format!("Failed to write {}", lpath.display()))?;
+
+        Ok(true)
+    }
+
+    /// Uploads a local file with a multipart upload
+    ///
+    /// # Arguments
+    ///
+    /// * `lpath` - Local file to upload
+    /// * `rpath` - Remote `opsml-root:/` path to write to
+    ///
+    pub async fn upload(&self, lpath: &Path, rpath: &str) -> Result<(), anyhow::Error> {
+        let path = self.object_path(rpath).with_context(|| {
+            format!(
+                "{} is not a path in the storage root ({}...)",
+                rpath, OPSML_ROOT
+            )
+        })?;
+
+        let mut file = tokio::fs::File::open(lpath)
+            .await
+            .with_context(|| format!("Failed to open {}", lpath.display()))?;
+        let upload = self
+            .store
+            .put_multipart(&path)
+            .await
+            .with_context(|| format!("Failed to start upload of {}", path))?;
+        let mut writer = WriteMultipart::new_with_chunk_size(upload, self.part_size as usize);
+
+        let mut buf = vec![0; 1024 * 1024];
+        loop {
+            let read = match file.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) => {
+                    writer.abort().await?;
+                    return Err(e).with_context(|| format!("Failed to read {}", lpath.display()));
+                }
+            };
+
+            writer.wait_for_capacity(CONCURRENCY).await?;
+            writer.write(&buf[..read]);
+        }
+
+        writer
+            .finish()
+            .await
+            .with_context(|| format!("Failed to upload {}", path))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_mounted_path() {
+        let mount = Path::new("/mnt/opsml");
+
+        assert_eq!(
+            mounted_path(
+                mount,
+                "opsml-root:/OPSML_MODEL_REGISTRY/repo/model/v1.0.0/model.onnx"
+            ),
+            Some(mount.join("OPSML_MODEL_REGISTRY/repo/model/v1.0.0/model.onnx"))
+        );
+        assert_eq!(mounted_path(mount, "models/model.onnx"), None);
+        assert_eq!(mounted_path(mount, "opsml-root:/../etc/passwd"), None);
+        assert_eq!(mounted_path(mount, "opsml-root://etc/passwd"), None);
+        assert_eq!(mounted_path(mount, "opsml-root:/"), None);
+    }
+
+    #[tokio::test]
+    async fn test_open_mounted() {
+        let mount = env::temp_dir().join(format!("opsml-mount-{}", uuid::Uuid::new_v4()));
+        let registry = mount.join("OPSML_DATA_REGISTRY");
+        fs::create_dir_all(&registry).unwrap();
+        fs::write(registry.join("data.csv"), "a,b\n1,2\n").unwrap();
+
+        unsafe {
+            env::set_var(STORAGE_MOUNT_VAR, &mount);
+        }
+        let stream = open_mounted("opsml-root:/OPSML_DATA_REGISTRY/data.csv").await;
+        let missing = open_mounted("opsml-root:/OPSML_DATA_REGISTRY/missing.csv").await;
+        unsafe {
+            env::remove_var(STORAGE_MOUNT_VAR);
+        }
+
+        let chunks = stream.unwrap().unwrap().collect::<Vec<_>>().await;
+        let bytes = chunks
+            .into_iter()
+            .map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>()
+            .concat();
+        assert_eq!(bytes, b"a,b\n1,2\n");
+        assert!(missing.unwrap().is_none());
+
+        fs::remove_dir_all(mount).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_backend_round_trip() {
+        let dir = env::temp_dir().join(format!("opsml-bucket-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // the local filesystem stands in for a bucket, with parts small enough to split the file
+        let mut backend =
+            StorageBackend::from_uri(&format!("file://{}/bucket", dir.display())).unwrap();
+        backend.part_size = 4;
+
+        let rpath = "opsml-root:/OPSML_MODEL_REGISTRY/repo/model/v1.0.0/model.onnx";
+        let source = dir.join("source.onnx");
+        fs::write(&source, "multipart model bytes").unwrap();
+        backend.upload(&source, rpath).await.unwrap();
+        assert!(dir
+            .join("bucket/OPSML_MODEL_REGISTRY/repo/model/v1.0.0/model.onnx")
+            .exists());
+
+        let downloaded = dir.join("downloaded.onnx");
+        assert!(backend.download(rpath, &downloaded).await.unwrap());
+        assert_eq!(
+            fs::read_to_string(&downloaded).unwrap(),
+            "multipart model bytes"
+        );
+
+        let chunks = backend
+            .open(rpath)
+            .await
+            .unwrap()
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            chunks
+                .into_iter()
+                .map(|c| c.unwrap())
+                .collect::<Vec<_>>()
+                .concat(),
+            b"multipart model bytes"
+        );
+
+        // missing files and paths outside the storage root fall back to the server
+        let missing = "opsml-root:/OPSML_MODEL_REGISTRY/missing.onnx";
+        assert!(!backend.download(missing, &downloaded).await.unwrap());
+        assert!(backend.open(missing).await.unwrap().is_none());
+        assert!(!backend.download("model.onnx", &downloaded).await.unwrap());
+        assert!(backend.upload(&source, "model.onnx").await.is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_backend_from_uri() {
+        assert!(StorageBackend::from_uri("ftp://bucket/opsml").is_err());
+
+        #[cfg(not(feature = "s3"))]
+        assert!(StorageBackend::from_uri("s3://bucket/opsml")
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("s3 feature"));
+    }
+}
@@ -1,9 +1,11 @@
 /// Copyright (c) Shipt, Inc.
 /// This source code is licensed under the MIT license found in the
 /// LICENSE file in the root directory of this source tree.
+use crate::api::api_version::ApiVersion;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use tabled::Tabled;
 
 #[derive(Debug, Serialize)]
@@ -19,6 +21,45 @@ pub struct ListTableRequest<'a> {
     pub ignore_release_candidates: &'a bool,
 }
 
+/// Card list request of the v3 api, which names repositories spaces
+#[derive(Debug, Serialize)]
+pub struct ListCardRequestV3<'a> {
+    pub registry_type: &'a str,
+    pub name: Option<&'a str>,
+    pub space: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub uid: Option<&'a str>,
+    pub limit: Option<&'a i16>,
+    pub tags: &'a HashMap<String, String>,
+    pub max_date: Option<&'a str>,
+    pub ignore_release_candidates: &'a bool,
+}
+
+impl<'a> From<&ListTableRequest<'a>> for ListCardRequestV3<'a> {
+    fn from(request: &ListTableRequest<'a>) -> Self {
+        ListCardRequestV3 {
+            registry_type: request.registry_type,
+            name: request.name,
+            space: request.repository,
+            version: request.version,
+            uid: request.uid,
+            limit: request.limit,
+            tags: request.tags,
+            max_date: request.max_date,
+            ignore_release_candidates: request.ignore_release_candidates,
+        }
+    }
+}
+
+/// Card registration request. The card is sent as the source server returned it so fields this
+/// client does not know about are kept
+#[derive(Debug, Serialize)]
+pub struct CreateCardRequest<'a> {
+    pub registry_type: &'a str,
+    pub card: &'a Value,
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Serialize)]
 pub struct CardRequest<'a> {
     pub name: Option<&'a str>,
@@ -35,15 +76,40 @@ pub struct ModelMetadataRequest<'a> {
     pub ignore_release_candidates: &'a bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Model metadata request of the v3 api, which names repositories spaces
+#[derive(Serialize)]
+pub struct ModelMetadataRequestV3<'a> {
+    pub name: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub space: Option<&'a str>,
+    pub uid: Option<&'a str>,
+    pub ignore_release_candidates: &'a bool,
+}
+
+impl<'a> From<&ModelMetadataRequest<'a>> for ModelMetadataRequestV3<'a> {
+    fn from(request: &ModelMetadataRequest<'a>) -> Self {
+        ModelMetadataRequestV3 {
+            name: request.name,
+            version: request.version,
+            space: request.repository,
+            uid: request.uid,
+            ignore_release_candidates: request.ignore_release_candidates,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Card {
     pub name: String,
+    /// Named `space` by the v3 api
+    #[serde(alias = "space")]
     pub repository: String,
     pub date: Option<String>,
     pub contact: String,
     pub version: String,
     pub uid: String,
     pub tags: HashMap<String, String>,
+    pub runcard_uid: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,11 +117,64 @@ pub struct ListFileResponse {
     pub files: Vec<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileInfo {
+    pub name: String,
+    pub size: Option<u64>,
+    pub mtime: Option<Value>,
+    #[serde(rename = "type")]
+    pub file_type: Option<String>,
+}
+
+impl FileInfo {
+    /// Creates a FileInfo from a path when no other info is available
+    pub fn from_name(name: &str) -> Self {
+        FileInfo {
+            name: name.to_string(),
+            size: None,
+            mtime: None,
+            file_type: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedFile {
+    pub rpath: String,
+    pub lpath: PathBuf,
+    pub size: Option<u64>,
+}
+
+#[derive(Tabled)]
+pub struct FileTable {
+    pub path: String,
+    pub size: String,
+    pub modified: String,
+}
+
+#[derive(Debug, Default)]
+pub struct DownloadPlan {
+    pub files: Vec<PlannedFile>,
+    pub skipped: Vec<PlannedFile>,
+}
+
+#[derive(Tabled)]
+pub struct DownloadPlanTable {
+    pub remote: String,
+    pub local: String,
+    pub size: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ListMetricResponse {
     pub metric: Vec<Metric>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct InsertMetricRequest<'a> {
+    pub metric: &'a [Metric],
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Metric {
     pub run_uid: String,
@@ -73,6 +192,61 @@ pub struct MetricTable {
     pub timestamp: String,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricAggregation {
+    Last,
+    Min,
+    Max,
+    Mean,
+}
+
+impl MetricAggregation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricAggregation::Last => "last",
+            MetricAggregation::Min => "min",
+            MetricAggregation::Max => "max",
+            MetricAggregation::Mean => "mean",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, clap::ValueEnum)]
+pub enum MetricFormat {
+    #[default]
+    Table,
+    Csv,
+    Json,
+    Prometheus,
+}
+
+/// How downloaded models are laid out in the write directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum ModelLayout {
+    /// Files are written straight into the write directory
+    #[default]
+    Flat,
+    /// Each version gets `<write-dir>/<name>/<version>/` and `current` links to the latest download
+    Versioned,
+}
+
+#[derive(Tabled)]
+pub struct MetricAssertionTable {
+    pub metric: String,
+    pub rule: String,
+    pub value: String,
+    pub passed: bool,
+}
+
+#[derive(Tabled)]
+pub struct MetricSummaryTable {
+    pub metric: String,
+    pub aggregation: String,
+    pub value: Value,
+    pub step: String,
+}
+
+#[allow(dead_code)]
 #[derive(Tabled)]
 pub struct CompareMetricTable {
     pub champion_name: String,
@@ -83,6 +257,53 @@ pub struct CompareMetricTable {
     pub challenger_win: bool,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ServerVersion {
+    pub version: String,
+}
+
+#[derive(Tabled)]
+pub struct DoctorCheckTable {
+    pub check: String,
+    pub status: String,
+    pub detail: String,
+}
+
+/// Last card seen by a watch
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WatchedCard {
+    pub uid: String,
+    pub version: String,
+}
+
+/// Cards seen by `watch`, keyed by `{registry}/{repository}/{name}`
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct WatchState {
+    pub cards: BTreeMap<String, WatchedCard>,
+}
+
+/// Card names and repositories cached for shell completion
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompletionCache {
+    pub tracking_uri: String,
+    pub fetched_at: u64,
+    pub names: Vec<String>,
+    pub repositories: Vec<String>,
+}
+
+/// Api version detected for a server
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DetectedApiVersion {
+    pub api_version: ApiVersion,
+    pub detected_at: u64,
+}
+
+/// Detected api versions keyed by tracking uri
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ApiVersionCache {
+    pub servers: BTreeMap<String, DetectedApiVersion>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ListCardResponse {
     pub cards: Vec<Card>,
@@ -115,18 +336,30 @@ pub struct DataSchema {
     onnx_version: Option<String>,
 }
 
+/// Newest major opsml version whose metadata schema this client understands
+pub const METADATA_SCHEMA_MAJOR_VERSION: u64 = 3;
+
+/// Model metadata as returned by the server. 1.x servers send `model_team` and omit the interface
+/// and sample data fields, and 3.x servers send `model_space`. The aliases and defaults cover
+/// both. Fields this client does not know are kept in `extra`
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelMetadata {
     pub model_name: String,
+    #[serde(default)]
     pub model_class: String,
     pub model_type: String,
+    #[serde(default)]
     pub model_interface: String,
     pub onnx_uri: Option<String>,
     pub onnx_version: Option<String>,
     pub model_uri: String,
     pub model_version: String,
+    #[serde(alias = "model_team", alias = "model_space")]
     pub model_repository: String,
+    #[serde(default)]
     pub sample_data_uri: String,
+    #[serde(default)]
+    pub opsml_version: Option<String>,
     pub data_schema: DataSchema,
     pub preprocessor_uri: Option<String>,
     pub preprocessor_name: Option<String>,
@@ -135,8 +368,30 @@ pub struct ModelMetadata {
     pub feature_extractor_uri: Option<String>,
     pub feature_extractor_name: Option<String>,
     pub quantized_model_uri: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl ModelMetadata {
+    /// Warning for metadata written by an opsml version newer than this client understands
+    ///
+    /// # Returns
+    /// * `Option<String>` - Warning message, if the schema is newer
+    ///
+    pub fn schema_warning(&self) -> Option<String> {
+        let opsml_version = self.opsml_version.as_deref()?;
+        let version = semver::Version::parse(opsml_version).ok()?;
+
+        (version.major > METADATA_SCHEMA_MAJOR_VERSION).then(|| {
+            format!(
+                "Model metadata was written by opsml {}, which is newer than this client understands ({}.x). Unknown fields are saved but not used",
+                opsml_version, METADATA_SCHEMA_MAJOR_VERSION
+            )
+        })
+    }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize)]
 pub struct CompareMetricRequest<'a> {
     pub metric_name: &'a Vec<String>,
@@ -145,6 +400,7 @@ pub struct CompareMetricRequest<'a> {
     pub champion_uid: &'a Vec<String>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BattleReport {
     pub champion_name: String,
@@ -154,6 +410,7 @@ pub struct BattleReport {
     pub challenger_win: bool,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CompareMetricResponse {
     pub challenger_name: String,
@@ -165,3 +422,83 @@ pub struct CompareMetricResponse {
 pub struct PresignedUrl {
     pub url: String,
 }
+
+/// Content descriptor of an OCI blob
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OciDescriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OciManifest {
+    pub schema_version: u8,
+    pub media_type: String,
+    pub config: OciDescriptor,
+    pub layers: Vec<OciDescriptor>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OciIndex {
+    pub schema_version: u8,
+    pub media_type: String,
+    pub manifests: Vec<OciDescriptor>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OciRootfs {
+    #[serde(rename = "type")]
+    pub rootfs_type: String,
+    pub diff_ids: Vec<String>,
+}
+
+/// Image config, which lets the model layer be used in `FROM scratch` builds
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OciImageConfig {
+    pub architecture: String,
+    pub os: String,
+    pub rootfs: OciRootfs,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OciLayout {
+    pub image_layout_version: String,
+}
+
+/// Table of contents of a bundle written by `bundle create`. It holds no timestamps so the same
+/// cards always produce the same bundle
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    /// Api version of the server the card records were read from
+    pub api_version: ApiVersion,
+    pub models: Vec<BundleModel>,
+    pub files: Vec<BundleFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleModel {
+    pub uid: String,
+    pub name: String,
+    pub repository: String,
+    pub version: String,
+    /// Remote root the model's files were stored under
+    pub rpath_root: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BundleFile {
+    /// Path inside the bundle
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
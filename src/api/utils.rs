@@ -1,60 +1,80 @@
 /// Copyright (c) Shipt, Inc.
 /// This source code is licensed under the MIT license found in the
 /// LICENSE file in the root directory of this source tree.
+use crate::api::api_version::ApiVersion;
 use anyhow::Context;
+use glob::Pattern;
 use lazy_static::lazy_static;
 
 use owo_colors::OwoColorize;
 use reqwest::Url;
 use reqwest::{self};
 use std::env;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{format, path::Path};
 
 lazy_static! {
-    static ref OPSML_TRACKING_URI: String = match env::var("OPSML_TRACKING_URI") {
-        Ok(val) =>
-            if val.ends_with('/') {
-                remove_suffix(&val, '/')
-            } else {
-                val
-            },
-
-        Err(_e) => {
-            panic!(
-                "{}",
-                "No OPSML_TRACKING_URI found. Check your environment"
-                    .bold()
-                    .red()
-            )
-        }
+    static ref OPSML_TRACKING_URI: String = match tracking_uri_from_env() {
+        Ok(val) => val,
+        Err(e) => panic!("{}", e.to_string().bold().red()),
     };
 }
 
+/// Reads the opsml tracking uri from the environment without a trailing slash
+pub fn tracking_uri_from_env() -> Result<String, anyhow::Error> {
+    let uri = env::var("OPSML_TRACKING_URI").with_context(|| {
+        "No OPSML_TRACKING_URI found. Check your environment or run `opsml-cli doctor`"
+    })?;
+
+    Ok(remove_suffix(&uri, '/'))
+}
+
+/// Directory caches of server responses are written to
+pub fn cache_dir() -> PathBuf {
+    let base = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".cache"))
+            .unwrap_or_else(env::temp_dir),
+    };
+    base.join("opsml-cli")
+}
+
+/// Current time in seconds since the unix epoch
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Prefix of paths in the opsml storage root
+pub const OPSML_ROOT: &str = "opsml-root:/";
+
+/// Logical server operations. Routes depend on the server's api version
 pub enum OpsmlPaths {
     ListCard,
+    CreateCard,
     MetadataDownload,
     DownloadPresigned,
+    DownloadFile,
+    UploadFile,
     Metric,
     ListFile,
+    Healthcheck,
+    Version,
 }
 
 impl OpsmlPaths {
+    /// Url of the operation on the server at `OPSML_TRACKING_URI` for the current api version
     pub fn as_str(&self) -> String {
-        match self {
-            OpsmlPaths::ListCard => format!("{}/opsml/cards/list", *OPSML_TRACKING_URI),
-            OpsmlPaths::MetadataDownload => {
-                format!("{}/opsml/models/metadata", *OPSML_TRACKING_URI)
-            }
-
-            OpsmlPaths::Metric => {
-                format!("{}/opsml/metrics", *OPSML_TRACKING_URI)
-            }
-            OpsmlPaths::ListFile => format!("{}/opsml/files/list", *OPSML_TRACKING_URI),
+        self.url_for(ApiVersion::current())
+    }
 
-            OpsmlPaths::DownloadPresigned => {
-                format!("{}/opsml/files/presigned", *OPSML_TRACKING_URI)
-            }
-        }
+    /// Url of the operation on the server at `OPSML_TRACKING_URI` for an api version
+    pub fn url_for(&self, api_version: ApiVersion) -> String {
+        api_version.url(&OPSML_TRACKING_URI, self)
     }
 }
 
@@ -128,16 +148,211 @@ pub fn create_dir_path(path: &Path) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Formats a byte count as a human readable size
+///
+/// # Arguments
+///
+/// * `bytes` - Number of bytes
+///
+pub fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, units[unit])
+    } else {
+        format!("{:.1} {}", size, units[unit])
+    }
+}
+
+/// Formats seconds since the unix epoch as a UTC date time
+///
+/// # Arguments
+///
+/// * `seconds` - Seconds since 1970-01-01
+///
+pub fn format_epoch(seconds: i64) -> String {
+    let days = seconds.div_euclid(86_400);
+    let secs_of_day = seconds.rem_euclid(86_400);
+
+    // civil date from days since epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        (secs_of_day % 3_600) / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Include/exclude glob filters applied to remote file paths
+pub struct FileFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl FileFilter {
+    /// Creates a new file filter from glob patterns
+    ///
+    /// # Arguments
+    ///
+    /// * `include` - Glob patterns a file must match to be kept. All files are kept if empty
+    /// * `exclude` - Glob patterns that remove a file when matched
+    ///
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, anyhow::Error> {
+        let compile = |patterns: &[String]| -> Result<Vec<Pattern>, anyhow::Error> {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern)
+                        .with_context(|| format!("Invalid glob pattern: {}", pattern.red()))
+                })
+                .collect()
+        };
+
+        Ok(FileFilter {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    /// Checks whether a relative path passes the filter.
+    /// Patterns are matched against both the full relative path and the file name
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path relative to the remote root
+    ///
+    pub fn is_match(&self, path: &Path) -> bool {
+        let file_name = path.file_name().map(Path::new);
+        let matches = |pattern: &Pattern| {
+            pattern.matches_path(path) || file_name.is_some_and(|f| pattern.matches_path(f))
+        };
+
+        let included = self.include.is_empty() || self.include.iter().any(matches);
+        let excluded = self.exclude.iter().any(matches);
+
+        included && !excluded
+    }
+}
+
 pub enum SaveRoot {
+    Data,
     Model,
+    Run,
+    Pipeline,
 }
 
 impl SaveRoot {
     pub fn as_str(&self) -> &'static str {
         match self {
+            SaveRoot::Data => "opsml-root:/OPSML_DATA_REGISTRY",
             SaveRoot::Model => "opsml-root:/OPSML_MODEL_REGISTRY",
+            SaveRoot::Run => "opsml-root:/OPSML_RUN_REGISTRY",
+            SaveRoot::Pipeline => "opsml-root:/OPSML_PIPELINE_REGISTRY",
+        }
+    }
+
+    /// Gets the save root for a registry that stores artifacts
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - Name of the registry (data, model, run, pipeline)
+    ///
+    pub fn from_registry(registry: &str) -> Result<SaveRoot, anyhow::Error> {
+        match registry {
+            "data" => Ok(SaveRoot::Data),
+            "model" => Ok(SaveRoot::Model),
+            "run" => Ok(SaveRoot::Run),
+            "pipeline" => Ok(SaveRoot::Pipeline),
+            _ => Err(anyhow::Error::msg(format!(
+                "Registry {} does not store artifacts. Valid registries are: data, model, run, pipeline",
+                registry
+            ))),
         }
     }
+
+    /// Gets the remote root for a card version
+    ///
+    /// # Arguments
+    ///
+    /// * `repository` - Card repository
+    /// * `name` - Card name
+    /// * `version` - Card version
+    ///
+    pub fn card_root(&self, repository: &str, name: &str, version: &str) -> PathBuf {
+        PathBuf::from(format!(
+            "{}/{}/{}/v{}",
+            self.as_str(),
+            repository,
+            name,
+            version
+        ))
+    }
+}
+
+/// Parses an interval such as `500ms`, `30s`, `5m` or `1h`. A bare number is read as seconds
+///
+/// # Arguments
+///
+/// * `interval` - Interval to parse
+///
+pub fn parse_interval(interval: &str) -> Result<Duration, anyhow::Error> {
+    let interval = interval.trim();
+    let split = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(interval.len());
+    let (value, unit) = interval.split_at(split);
+
+    let value: u64 = value
+        .parse()
+        .with_context(|| format!("Invalid interval: {}", interval))?;
+
+    let seconds_per_unit = match unit {
+        "ms" => None,
+        "" | "s" => Some(1),
+        "m" => Some(60),
+        "h" => Some(60 * 60),
+        _ => {
+            return Err(anyhow::Error::msg(format!(
+                "Invalid interval unit: {}. Valid units are: ms, s, m, h",
+                unit
+            )))
+        }
+    };
+
+    let duration = match seconds_per_unit {
+        None => Duration::from_millis(value),
+        Some(seconds_per_unit) => Duration::from_secs(
+            value
+                .checked_mul(seconds_per_unit)
+                .with_context(|| format!("Interval is too long: {}", interval))?,
+        ),
+    };
+
+    if duration.is_zero() {
+        return Err(anyhow::Error::msg("Interval must be greater than zero"));
+    }
+
+    Ok(duration)
 }
 
 #[cfg(test)]
@@ -153,4 +368,61 @@ mod tests {
         assert_eq!(processed_with_slash_uri, "http://localhost:8080");
         assert_eq!(processed_without_slash_uri, test_uri_without_slash);
     }
+
+    #[test]
+    fn test_file_filter() {
+        let filter = FileFilter::new(
+            &["*.safetensors".to_string(), "*.json".to_string()],
+            &["optimizer*".to_string()],
+        )
+        .unwrap();
+
+        assert!(filter.is_match(Path::new("model.safetensors")));
+        assert!(filter.is_match(Path::new("tokenizer/tokenizer.json")));
+        assert!(!filter.is_match(Path::new("pytorch_model.bin")));
+        assert!(!filter.is_match(Path::new("optimizer.safetensors")));
+        assert!(!filter.is_match(Path::new("checkpoint/optimizer.json")));
+
+        // an empty filter keeps everything
+        let filter = FileFilter::new(&[], &[]).unwrap();
+        assert!(filter.is_match(Path::new("pytorch_model.bin")));
+
+        assert!(FileFilter::new(&["[".to_string()], &[]).is_err());
+    }
+
+    #[test]
+    fn test_save_root() {
+        let root = SaveRoot::from_registry("data").unwrap();
+        assert_eq!(
+            root.card_root("repo", "name", "1.0.0").to_str().unwrap(),
+            "opsml-root:/OPSML_DATA_REGISTRY/repo/name/v1.0.0"
+        );
+        assert!(SaveRoot::from_registry("audit").is_err());
+    }
+
+    #[test]
+    fn test_format_epoch() {
+        assert_eq!(format_epoch(0), "1970-01-01 00:00:00");
+        assert_eq!(format_epoch(1_709_251_199), "2024-02-29 23:59:59");
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(2048), "2.0 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GB");
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("60s").unwrap(), Duration::from_secs(60));
+        assert_eq!(parse_interval("45").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_interval("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_interval("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_interval("250ms").unwrap(), Duration::from_millis(250));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("10d").is_err());
+        assert!(parse_interval("s").is_err());
+        assert!(parse_interval(&format!("{}h", u64::MAX / 60)).is_err());
+    }
 }
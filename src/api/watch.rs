@@ -0,0 +1,349 @@
+/// Copyright (c) Shipt, Inc.
+/// This source code is licensed under the MIT license found in the
+/// LICENSE file in the root directory of this source tree.
+use crate::api::cards;
+use crate::api::download::download_card;
+use crate::api::model::download_model;
+use crate::api::types;
+use crate::api::utils;
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use std::fs;
+use std::path::Path;
+
+/// Quotes a value for use in a `sh -c` command line
+fn shell_quote(value: &str) -> String {
+    let safe = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._-/:+@=,".contains(c));
+
+    match safe {
+        true => value.to_string(),
+        false => format!("'{}'", value.replace('\'', r"'\''")),
+    }
+}
+
+/// Fills the `{uid}`, `{version}`, `{name}`, `{repository}` and `{registry}` placeholders of a hook
+/// command. Values are shell quoted
+///
+/// # Arguments
+///
+/// * `template` - Hook command template
+/// * `registry` - Registry of the card
+/// * `card` - New card
+///
+fn render_hook(template: &str, registry: &str, card: &types::Card) -> String {
+    template
+        .replace("{uid}", &shell_quote(&card.uid))
+        .replace("{version}", &shell_quote(&card.version))
+        .replace("{name}", &shell_quote(&card.name))
+        .replace("{repository}", &shell_quote(&card.repository))
+        .replace("{registry}", &shell_quote(registry))
+}
+
+pub struct CardWatcher<'a> {
+    pub registry: &'a str,
+    pub name: Option<&'a str>,
+    pub repository: Option<&'a str>,
+    pub ignore_release_candidates: &'a bool,
+    pub download: &'a bool,
+    pub write_dir: &'a str,
+    pub exec: Option<&'a str>,
+    pub state_file: &'a Path,
+}
+
+impl CardWatcher<'_> {
+    /// Key identifying this watch in the state file, so several watches can share one file
+    fn state_key(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.registry,
+            self.repository.unwrap_or("*"),
+            self.name.unwrap_or("*")
+        )
+    }
+
+    /// Loads the watch state. A missing state file means nothing has been seen yet
+    fn load_state(&self) -> Result<types::WatchState, anyhow::Error> {
+        if !self.state_file.exists() {
+            return Ok(types::WatchState::default());
+        }
+
+        let state = fs::read_to_string(self.state_file)
+            .with_context(|| format!("Failed to read {}", self.state_file.display()))?;
+        serde_json::from_str(&state)
+            .with_context(|| format!("Failed to parse {}", self.state_file.display()))
+    }
+
+    fn save_state(&self, state: &types::WatchState) -> Result<(), anyhow::Error> {
+        if let Some(parent) = self.state_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(self.state_file, serde_json::to_string_pretty(state)?)
+            .with_context(|| format!("Failed to write {}", self.state_file.display()))
+    }
+
+    /// Gets the most recent card matching the watch
+    async fn latest_card(&self) -> Result<Option<types::Card>, anyhow::Error> {
+        let response = cards::fetch_cards(
+            self.registry,
+            self.name,
+            self.repository,
+            None,
+            None,
+            Some(1),
+            *self.ignore_release_candidates,
+        )
+        .await?;
+
+        Ok(response.cards.into_iter().next())
+    }
+
+    /// Downloads the new card if requested and runs the hook command
+    ///
+    /// # Arguments
+    ///
+    /// * `card` - New card
+    ///
+    async fn on_new_card(&self, card: &types::Card) -> Result<(), anyhow::Error> {
+        if *self.download {
+            match self.registry {
+                "model" => {
+                    download_model(
+                        None,
+                        None,
+                        None,
+                        Some(&card.uid),
+                        self.write_dir,
+                        &false,
+                        &false,
+                        &false,
+                        self.ignore_release_candidates,
+                        &false,
+                        &[],
+                        &[],
+                    )
+                    .await?
+                }
+                _ => {
+                    download_card(
+                        self.registry,
+                        None,
+                        None,
+                        None,
+                        Some(&card.uid),
+                        self.write_dir,
+                        self.ignore_release_candidates,
+                        &false,
+                        &[],
+                        &[],
+                    )
+                    .await?
+                }
+            }
+        }
+
+        if let Some(template) = self.exec {
+            let command = render_hook(template, self.registry, card);
+            println!("Running {}", command.bold());
+
+            let status = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .status()
+                .await
+                .with_context(|| format!("Failed to run {}", command))?;
+
+            if !status.success() {
+                return Err(anyhow::Error::msg(format!(
+                    "Hook exited with {}: {}",
+                    status, command
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks for a new card. The state file is only updated once the download and hook succeed,
+    /// so a failed hook is retried on the next poll. A watch without state records the current
+    /// card without reacting to it
+    ///
+    /// # Returns
+    /// * `Result<Option<types::Card>, String>` - The new card, if one was found
+    ///
+    pub async fn poll(&self) -> Result<Option<types::Card>, anyhow::Error> {
+        let Some(card) = self.latest_card().await? else {
+            return Ok(None);
+        };
+
+        let mut state = self.load_state()?;
+        let key = self.state_key();
+        let seen = types::WatchedCard {
+            uid: card.uid.clone(),
+            version: card.version.clone(),
+        };
+
+        match state.cards.get(&key) {
+            None => {
+                println!(
+                    "Watching {} card {}/{} from v{} ({})",
+                    self.registry,
+                    card.repository,
+                    card.name,
+                    card.version.green(),
+                    card.uid
+                );
+                state.cards.insert(key, seen);
+                self.save_state(&state)?;
+                return Ok(None);
+            }
+            Some(watched) if watched.uid == card.uid => return Ok(None),
+            Some(_) => {}
+        }
+
+        println!(
+            "New {} card: {}/{} v{} ({})",
+            self.registry,
+            card.repository,
+            card.name,
+            card.version.green(),
+            card.uid
+        );
+        self.on_new_card(&card).await?;
+
+        state.cards.insert(key, seen);
+        self.save_state(&state)?;
+
+        Ok(Some(card))
+    }
+}
+
+/// Polls a registry and reacts to new card versions
+///
+/// # Arguments
+///
+/// * `watcher` - Watch configuration
+/// * `interval` - Polling interval, e.g. `60s`
+/// * `once` - Poll a single time and exit
+///
+pub async fn watch_registry(
+    watcher: &CardWatcher<'_>,
+    interval: &str,
+    once: bool,
+) -> Result<(), anyhow::Error> {
+    let interval = utils::parse_interval(interval)?;
+
+    // fail fast instead of reporting the same error on every poll
+    if !cards::REGISTRIES.contains(&watcher.registry) {
+        return Err(anyhow::Error::msg(format!(
+            "Invalid registry: {}. Valid registries are: {}",
+            watcher.registry,
+            cards::REGISTRIES.join(", ")
+        )));
+    }
+
+    loop {
+        match watcher.poll().await {
+            Ok(_) => {}
+            // a failed poll in one-shot mode should fail the command
+            Err(e) if once => return Err(e),
+            Err(e) => eprintln!("{} {:#}", "Watch poll failed:".bold().red(), e),
+        }
+
+        if once {
+            return Ok(());
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::env;
+
+    #[test]
+    fn test_render_hook() {
+        let card = types::Card {
+            name: "my model".to_string(),
+            repository: "repo".to_string(),
+            date: None,
+            contact: "fake".to_string(),
+            version: "1.2.0".to_string(),
+            uid: "abc123".to_string(),
+            tags: HashMap::new(),
+            runcard_uid: None,
+        };
+
+        assert_eq!(
+            render_hook("./reload.sh {uid} {version} {name}", "model", &card),
+            "./reload.sh abc123 1.2.0 'my model'"
+        );
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[tokio::test]
+    async fn test_poll() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        unsafe {
+            env::set_var("OPSML_TRACKING_URI", url);
+        }
+
+        let path = "./src/api/test_utils/list_cards.json";
+        let data = fs::read_to_string(path).expect("Unable to read file");
+        let mock = server
+            .mock("POST", "/opsml/cards/list")
+            .with_status(201)
+            .with_body(data)
+            .expect(4)
+            .create();
+
+        let dir = env::temp_dir().join(format!("opsml-watch-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let state_file = dir.join("state.json");
+        let hook_output = dir.join("hook.txt");
+        let exec = format!("echo {{name}} {{version}} >> {}", hook_output.display());
+
+        let watcher = CardWatcher {
+            registry: "data",
+            name: Some("name"),
+            repository: Some("repository"),
+            ignore_release_candidates: &false,
+            download: &false,
+            write_dir: dir.to_str().unwrap(),
+            exec: Some(&exec),
+            state_file: &state_file,
+        };
+
+        // the first poll only records the current card
+        assert!(watcher.poll().await.unwrap().is_none());
+        assert!(!hook_output.exists());
+
+        // a different card than the one recorded fires the hook
+        fs::write(
+            &state_file,
+            r#"{"cards": {"data/repository/name": {"uid": "old", "version": "0.9.0"}}}"#,
+        )
+        .unwrap();
+        let card = watcher.poll().await.unwrap().unwrap();
+        assert_eq!(card.uid, "1234567890");
+        assert_eq!(fs::read_to_string(&hook_output).unwrap(), "name 1.0.0\n");
+
+        // state is read from disk on every poll, so a seen card never fires again after a restart
+        assert!(watcher.poll().await.unwrap().is_none());
+        assert!(watcher.poll().await.unwrap().is_none());
+        assert_eq!(fs::read_to_string(&hook_output).unwrap(), "name 1.0.0\n");
+
+        let state: types::WatchState =
+            serde_json::from_str(&fs::read_to_string(&state_file).unwrap()).unwrap();
+        assert_eq!(state.cards["data/repository/name"].version, "1.0.0");
+
+        mock.assert();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,20 +1,52 @@
+use api::api_version;
+use api::app::launch_app;
+use api::assertions::assert_metrics;
+use api::browse::browse;
+use api::bundle::{create_bundle, import_bundle, verify_bundle, BundleCreator};
 use api::cards::list_cards;
-use api::metrics::get_model_metrics;
+use api::completions::{write_completion_script, COMPLETE_VAR};
+use api::docs::gen_docs;
+use api::doctor::run_doctor;
+use api::download::download_card;
+use api::files::{
+    cat_remote_file, get_remote_file, list_remote_files, put_remote_file, tree_remote_files,
+};
+use api::layout::{download_model_versioned, rollback, VersionedDownloader};
+use api::matrix::metrics_matrix;
+use api::metrics::{get_model_metrics, log_metric, log_metrics_file, MetricView};
+use api::mirror::{mirror_card, CardMirror};
 /// Copyright (c) Shipt, Inc.
 /// This source code is licensed under the MIT license found in the
 /// LICENSE file in the root directory of this source tree.
 use api::model::download_model;
 use api::model::download_model_metadata;
+use api::oci::{export_oci, OciExporter};
+use api::types::ModelLayout;
 mod api;
 use anyhow::{Context, Result};
-use api::cli::{Cli, Commands, LOGO_TEXT};
-use clap::Parser;
+use api::cli::{BundleCommands, Cli, Commands, FilesCommands, LOGO_TEXT};
+use api::watch::{watch_registry, CardWatcher};
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
 use owo_colors::OwoColorize;
 
+fn main() -> Result<()> {
+    // answers completion requests from the scripts generated by `opsml-cli completions`
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_VAR)
+        .complete();
+
+    run()
+}
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn run() -> Result<()> {
     let cli = Cli::parse();
 
+    if cli.command.as_ref().is_some_and(Commands::uses_server) {
+        api_version::init(cli.api_version).await;
+    }
+
     match &cli.command {
         // subcommand for list cards
         Some(Commands::ListCards(args)) => {
@@ -57,40 +89,328 @@ async fn main() -> Result<()> {
         }
         // subcommand for downloading a model
         Some(Commands::DownloadModel(args)) => {
-            download_model(
+            match args.layout {
+                ModelLayout::Flat => {
+                    download_model(
+                        args.name.as_deref(),
+                        args.version.as_deref(),
+                        args.repository.as_deref(),
+                        args.uid.as_deref(),
+                        &args.write_dir,
+                        &args.onnx,
+                        &args.quantize,
+                        &args.preprocessor,
+                        &args.ignore_release_candidates,
+                        &args.dry_run,
+                        &args.include,
+                        &args.exclude,
+                    )
+                    .await
+                }
+                ModelLayout::Versioned => {
+                    let downloader = VersionedDownloader {
+                        name: args.name.as_deref(),
+                        version: args.version.as_deref(),
+                        repository: args.repository.as_deref(),
+                        uid: args.uid.as_deref(),
+                        write_dir: &args.write_dir,
+                        onnx: &args.onnx,
+                        quantize: &args.quantize,
+                        preprocessor: &args.preprocessor,
+                        ignore_release_candidates: &args.ignore_release_candidates,
+                        dry_run: &args.dry_run,
+                        include: &args.include,
+                        exclude: &args.exclude,
+                        keep: args.keep as usize,
+                    };
+                    download_model_versioned(&downloader).await
+                }
+            }
+            .with_context(|| {
+                format!(
+                    "Failed to download model for {:?}",
+                    args.name.clone().bold().red()
+                )
+            })?;
+            Ok(())
+        }
+        // subcommand for switching a versioned download back to an earlier version
+        Some(Commands::Rollback(args)) => {
+            rollback(&args.write_dir, &args.name, args.to.as_deref())
+                .with_context(|| format!("Failed to roll back {:?}", args.name.red()))?;
+            Ok(())
+        }
+        // subcommand for downloading card artifacts from any registry
+        Some(Commands::DownloadCard(args)) => {
+            download_card(
+                args.registry.as_str(),
                 args.name.as_deref(),
                 args.version.as_deref(),
                 args.repository.as_deref(),
                 args.uid.as_deref(),
                 &args.write_dir,
-                &args.onnx,
-                &args.quantize,
-                &args.preprocessor,
                 &args.ignore_release_candidates,
+                &args.dry_run,
+                &args.include,
+                &args.exclude,
             )
             .await
             .with_context(|| {
                 format!(
-                    "Failed to download model for {:?}",
+                    "Failed to download {} card {:?}",
+                    args.registry,
                     args.name.clone().bold().red()
                 )
             })?;
             Ok(())
         }
+
+        // subcommand for exporting a model as an OCI image layout
+        Some(Commands::ExportOci(args)) => {
+            let exporter = OciExporter {
+                name: args.name.as_deref(),
+                version: args.version.as_deref(),
+                repository: args.repository.as_deref(),
+                uid: args.uid.as_deref(),
+                write_dir: &args.write_dir,
+                architecture: &args.architecture,
+                onnx: &args.onnx,
+                quantize: &args.quantize,
+                preprocessor: &args.preprocessor,
+                ignore_release_candidates: &args.ignore_release_candidates,
+            };
+            export_oci(&exporter, args.output.as_deref(), args.layout.as_deref())
+                .await
+                .with_context(|| format!("{}", "Failed to export model as OCI".bold().red()))?;
+            Ok(())
+        }
+        // subcommands for air-gapped bundles
+        Some(Commands::Bundle(BundleCommands::Create(args))) => {
+            let creator = BundleCreator {
+                uids: &args.uid,
+                out: &args.out,
+            };
+            create_bundle(&creator)
+                .await
+                .with_context(|| format!("{}", "Failed to create bundle".bold().red()))?;
+            Ok(())
+        }
+        Some(Commands::Bundle(BundleCommands::Import(args))) => {
+            import_bundle(&args.bundle, args.into.as_deref(), args.server.as_deref())
+                .await
+                .with_context(|| format!("Failed to import {:?}", args.bundle.red()))?;
+            Ok(())
+        }
+        Some(Commands::Bundle(BundleCommands::Verify(args))) => {
+            verify_bundle(&args.bundle)
+                .await
+                .with_context(|| format!("Failed to verify {:?}", args.bundle.red()))?;
+            Ok(())
+        }
+        // subcommand for copying cards between servers
+        Some(Commands::Mirror(args)) => {
+            let mirror = CardMirror {
+                from: &args.from,
+                to: &args.to,
+                registry: &args.registry,
+                name: args.name.as_deref(),
+                repository: args.repository.as_deref(),
+                version: args.version.as_deref(),
+                uid: args.uid.as_deref(),
+                ignore_release_candidates: &args.ignore_release_candidates,
+            };
+            mirror_card(&mirror)
+                .await
+                .with_context(|| format!("{}", "Failed to mirror card".bold().red()))?;
+            Ok(())
+        }
+        // subcommands for browsing the artifact store
+        Some(Commands::Files(FilesCommands::Ls(args))) => {
+            list_remote_files(&args.target, &args.registry, &args.recursive, &args.long)
+                .await
+                .with_context(|| format!("Failed to list files for {:?}", args.target.red()))?;
+            Ok(())
+        }
+        Some(Commands::Files(FilesCommands::Tree(args))) => {
+            tree_remote_files(&args.target, &args.registry)
+                .await
+                .with_context(|| format!("Failed to list files for {:?}", args.target.red()))?;
+            Ok(())
+        }
+        Some(Commands::Files(FilesCommands::Get(args))) => {
+            get_remote_file(&args.rpath, &args.lpath)
+                .await
+                .with_context(|| format!("Failed to download {:?}", args.rpath.red()))?;
+            Ok(())
+        }
+        Some(Commands::Files(FilesCommands::Cat(args))) => {
+            cat_remote_file(&args.rpath, args.head)
+                .await
+                .with_context(|| format!("Failed to read {:?}", args.rpath.red()))?;
+            Ok(())
+        }
+        Some(Commands::Files(FilesCommands::Put(args))) => {
+            put_remote_file(&args.lpath, &args.rpath)
+                .await
+                .with_context(|| format!("Failed to upload {:?}", args.lpath.red()))?;
+            Ok(())
+        }
+
         // subcommand for getting model metrics
         Some(Commands::GetModelMetrics(args)) => {
-            get_model_metrics(args.uid.as_str())
+            get_model_metrics(
+                args.name.as_deref(),
+                args.repository.as_deref(),
+                args.version.as_deref(),
+                args.uid.as_deref(),
+                args.ignore_release_candidates,
+                &MetricView {
+                    metric_names: args.metric.as_deref(),
+                    step_range: args.step_range.as_deref(),
+                    aggregation: args.aggregation(),
+                    chart: args.chart,
+                    format: args.format.clone(),
+                    export: args.export.as_deref(),
+                },
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get model metrics for {:?}",
+                    args.uid.clone().or(args.name.clone()).bold().red()
+                )
+            })?;
+
+            Ok(())
+        }
+
+        // subcommand for comparing metrics across runs
+        Some(Commands::MetricsMatrix(args)) => {
+            metrics_matrix(
+                args.uid.as_deref(),
+                args.name.as_deref(),
+                args.repository.as_deref(),
+                args.versions.as_deref(),
+                args.metric.as_deref(),
+                &args.lower_is_better,
+                &args.higher_is_better,
+            )
+            .await
+            .with_context(|| format!("{}", "Failed to build metrics matrix".bold().red()))?;
+
+            Ok(())
+        }
+
+        // subcommand for checking metrics against threshold rules
+        Some(Commands::AssertMetrics(args)) => {
+            assert_metrics(
+                args.name.as_deref(),
+                args.repository.as_deref(),
+                args.version.as_deref(),
+                args.uid.as_deref(),
+                &args.rule,
+                args.rules_file.as_deref(),
+                args.ignore_release_candidates,
+            )
+            .await
+            .with_context(|| format!("{}", "Metric assertions failed".bold().red()))?;
+
+            Ok(())
+        }
+
+        // subcommands for logging metrics to a run
+        Some(Commands::LogMetric(args)) => {
+            log_metric(&args.run_uid, &args.name, args.value, args.step)
+                .await
+                .with_context(|| format!("Failed to log metric {:?}", args.name.bold().red()))?;
+
+            Ok(())
+        }
+        Some(Commands::LogMetrics(args)) => {
+            log_metrics_file(&args.file, args.run_uid.as_deref())
                 .await
                 .with_context(|| {
-                    format!(
-                        "Failed to get model metrics for {:?}",
-                        args.uid.clone().bold().red()
-                    )
+                    format!("Failed to log metrics from {:?}", args.file.bold().red())
                 })?;
 
             Ok(())
         }
 
+        // subcommand for the interactive registry browser
+        Some(Commands::Browse(args)) => {
+            browse(args.registry.as_deref(), &args.write_dir)
+                .await
+                .with_context(|| format!("{}", "Failed to run registry browser".bold().red()))?;
+
+            Ok(())
+        }
+
+        // subcommand for launching a local opsml server
+        Some(Commands::LaunchApp(args)) => {
+            launch_app(
+                &args.port,
+                &args.login,
+                &args.host,
+                args.command.as_deref(),
+                &args.startup_timeout,
+                &args.run,
+            )
+            .await
+            .with_context(|| format!("{}", "Failed to launch opsml server".bold().red()))?;
+
+            Ok(())
+        }
+
+        // subcommand for watching a registry for new versions
+        Some(Commands::Watch(args)) => {
+            let watcher = CardWatcher {
+                registry: &args.registry,
+                name: args.name.as_deref(),
+                repository: args.repository.as_deref(),
+                ignore_release_candidates: &args.ignore_release_candidates,
+                download: &args.download,
+                write_dir: &args.write_dir,
+                exec: args.exec.as_deref(),
+                state_file: std::path::Path::new(&args.state_file),
+            };
+
+            watch_registry(&watcher, &args.interval, args.once)
+                .await
+                .with_context(|| format!("{}", "Failed to watch registry".bold().red()))?;
+
+            Ok(())
+        }
+
+        // subcommand for checking the client configuration and server
+        Some(Commands::Doctor(args)) => {
+            run_doctor(args.tracking_uri.as_deref())
+                .await
+                .with_context(|| format!("{}", "Doctor found problems".bold().red()))?;
+
+            Ok(())
+        }
+
+        // subcommand for generating shell completions
+        Some(Commands::Completions(args)) => {
+            write_completion_script(
+                &mut Cli::command(),
+                args.shell,
+                args.static_only,
+                &mut std::io::stdout(),
+            )
+            .with_context(|| format!("{}", "Failed to generate completions".bold().red()))?;
+
+            Ok(())
+        }
+
+        // subcommand for generating man pages and the command reference
+        Some(Commands::GenDocs(args)) => {
+            gen_docs(Cli::command(), &args.out)
+                .with_context(|| format!("{}", "Failed to generate docs".bold().red()))?;
+
+            Ok(())
+        }
+
         // subcommand for listing opsml-cli version
         Some(Commands::Version) => {
             println!(